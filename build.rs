@@ -0,0 +1,32 @@
+// Build script
+//
+// Embeds the git commit the contract is built from as `GIT_COMMIT`, so a deployment can
+// report which build it is running. Builds outside a git checkout report "unknown".
+//
+// Also stamps that commit into the near-abi description checked in as abi.json and writes it
+// zstd compressed to `$OUT_DIR/abi.json.zst`, where it is embedded as `CONTRACT_ABI` and
+// served by `__contract_abi`. The `contract_abi_matches_methods` test keeps abi.json in step
+// with the contract's methods.
+use std::process::Command;
+
+fn main() {
+    let commit = Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|commit| commit.trim().to_string())
+        .unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=GIT_COMMIT={}", commit);
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs");
+
+    let abi = std::fs::read_to_string("abi.json").expect("Failed to read abi.json");
+    let mut abi: serde_json::Value = serde_json::from_str(&abi).expect("Invalid abi.json");
+    abi["metadata"]["build"]["source_commit"] = serde_json::json!(commit);
+    let compressed = zstd::encode_all(abi.to_string().as_bytes(), 19).unwrap();
+    let out_dir = std::env::var("OUT_DIR").unwrap();
+    std::fs::write(format!("{}/abi.json.zst", out_dir), compressed).unwrap();
+    println!("cargo:rerun-if-changed=abi.json");
+}
@@ -0,0 +1,106 @@
+// Fuzz target for key and value inputs
+//
+// Splits the input at its first NUL byte into a key and a value and drives them through the
+// write, read and delete paths, decoded lossily so that invalid UTF-8 arrives as replacement
+// characters, the nearest the JSON layer lets it get. The raw bytes are also parsed as the
+// JSON arguments of `create_update_typed`. The contract may reject any input with one of its
+// own messages, but must never fail with an unchecked panic such as an overflow, an out of
+// bounds index or a broken collection.
+#![no_main]
+
+use key_value_storage::{KeyValue, Value};
+use libfuzzer_sys::fuzz_target;
+use near_sdk::serde::Deserialize;
+use near_sdk::test_utils::VMContextBuilder;
+use near_sdk::{env, testing_env, MockedBlockchain};
+use std::cell::RefCell;
+use std::convert::TryInto;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Once;
+
+/// Panic messages that point at an unchecked failure rather than one of the contract's own
+/// assertions
+const UNDEFINED_PANICS: &[&str] = &[
+    "attempt to",
+    "out of bounds",
+    "is not a char boundary",
+    "on a `None` value",
+    "InconsistentStateError",
+];
+
+/// Arguments of `create_update_typed` as the JSON layer decodes them
+#[derive(Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+struct TypedArgs {
+    k: String,
+    v: Value,
+}
+
+thread_local! {
+    static PANIC_MESSAGE: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Replaces libFuzzer's aborting panic hook with one that only records the message, so the
+/// contract's own assertions can be told apart from unchecked panics.
+fn record_panics() {
+    static HOOK: Once = Once::new();
+    HOOK.call_once(|| {
+        panic::set_hook(Box::new(|info| {
+            PANIC_MESSAGE.with(|message| *message.borrow_mut() = Some(info.to_string()));
+        }));
+    });
+}
+
+/// Runs `call` on `contract` and returns whether it succeeded. A rejected input leaves the
+/// mocked state half written, so callers stop at the first `false`. Aborts, which libFuzzer
+/// reports as a crash, when the panic is not one of the contract's own.
+fn run(contract: &mut KeyValue, call: impl FnOnce(&mut KeyValue)) -> bool {
+    if panic::catch_unwind(AssertUnwindSafe(|| call(contract))).is_ok() {
+        return true;
+    }
+    let message = PANIC_MESSAGE.with(|message| message.borrow_mut().take().unwrap_or_default());
+    if UNDEFINED_PANICS.iter().any(|pattern| message.contains(pattern)) {
+        eprintln!("{}", message);
+        std::process::abort();
+    }
+    false
+}
+
+fuzz_target!(|data: &[u8]| {
+    record_panics();
+    // Start every input from empty storage
+    env::take_blockchain_interface();
+    testing_env!(VMContextBuilder::new()
+        .predecessor_account_id("carol_near".try_into().unwrap())
+        .attached_deposit(10u128.pow(24))
+        .build());
+    let mut contract = KeyValue::new("alice_near".try_into().unwrap(), None);
+
+    let split = data.iter().position(|&b| b == 0).unwrap_or(data.len());
+    let k = String::from_utf8_lossy(&data[..split]).into_owned();
+    let v = String::from_utf8_lossy(data.get(split + 1..).unwrap_or_default()).into_owned();
+    let typed = near_sdk::serde_json::from_slice::<TypedArgs>(data).ok();
+
+    let mut read = None;
+    if !run(&mut contract, |c| {
+        c.create_update(k.clone(), v.clone());
+    }) || !run(&mut contract, |c| read = c.read(k.clone()))
+    {
+        return;
+    }
+    assert_eq!(Some(&v), read.as_ref());
+    if !run(&mut contract, |c| {
+        c.create_update_typed(k.clone(), Value::Json(v.clone()));
+    }) || !run(&mut contract, |c| c.read_raw(k.clone()))
+        || !run(&mut contract, |c| c.delete(k.clone()))
+        || !run(&mut contract, |c| read = c.read(k.clone()))
+    {
+        return;
+    }
+    assert_eq!(None, read);
+    if let Some(args) = typed {
+        run(&mut contract, |c| {
+            c.create_update_typed(args.k, args.v);
+        });
+    }
+});
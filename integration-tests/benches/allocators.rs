@@ -0,0 +1,130 @@
+// Allocator benchmark
+//
+// Builds the contract for wasm32 with each allocator configuration and reports the size of
+// the resulting wasm, which sets the storage staked for the code. Each wasm is then deployed
+// to a local NEAR sandbox node, where representative calls of the most used methods run the
+// allocator's own code, and the gas they burn is reported per allocator.
+//
+// Run with `make bench`; near-workspaces downloads the sandbox node on first run.
+use near_workspaces::types::NearToken;
+use serde_json::json;
+use std::path::Path;
+use std::process::Command;
+
+/// Allocator configurations: a name and the cargo feature arguments selecting it
+const ALLOCATORS: &[(&str, &[&str])] = &[("wee_alloc", &[]), ("std", &["--no-default-features"])];
+
+/// Methods measured, in the order they are called
+const METHODS: &[&str] = &[
+    "create_update (new)",
+    "create_update (update)",
+    "read",
+    "keys",
+    "delete",
+];
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let mut wasms = vec![];
+    println!("allocator    wasm bytes");
+    for (name, feature_args) in ALLOCATORS {
+        let wasm = build_wasm(name, feature_args)?;
+        println!("{:<12} {}", name, wasm.len());
+        wasms.push(wasm);
+    }
+
+    let mut gas = vec![];
+    for wasm in &wasms {
+        gas.push(method_gas(wasm).await?);
+    }
+    println!();
+    print!("{:<24}", "gas (sandbox)");
+    for (name, _) in ALLOCATORS {
+        print!(" {:>16}", name);
+    }
+    println!();
+    for (i, method) in METHODS.iter().enumerate() {
+        print!("{:<24}", method);
+        for allocator_gas in &gas {
+            print!(" {:>16}", allocator_gas[i]);
+        }
+        println!();
+    }
+    Ok(())
+}
+
+/// Builds the release wasm with `feature_args` into a target directory of its own and
+/// returns it.
+fn build_wasm(name: &str, feature_args: &[&str]) -> anyhow::Result<Vec<u8>> {
+    let target_dir = format!("target/bench-{}", name);
+    let status = Command::new(env!("CARGO"))
+        .args([
+            "build",
+            "--lib",
+            "--release",
+            "--target",
+            "wasm32-unknown-unknown",
+        ])
+        .args(feature_args)
+        .args(["--target-dir", &target_dir])
+        .current_dir("..")
+        .status()?;
+    anyhow::ensure!(
+        status.success(),
+        "building the {} wasm failed; is the wasm32-unknown-unknown target installed?",
+        name
+    );
+    let wasm = Path::new("..")
+        .join(&target_dir)
+        .join("wasm32-unknown-unknown/release/key_value_storage.wasm");
+    Ok(std::fs::read(wasm)?)
+}
+
+/// Deploys `wasm` to a fresh sandbox and returns the gas burnt by each of `METHODS`.
+async fn method_gas(wasm: &[u8]) -> anyhow::Result<Vec<u64>> {
+    let worker = near_workspaces::sandbox().await?;
+    let contract = worker.dev_deploy(wasm).await?;
+    contract
+        .call("new")
+        .args_json(json!({ "owner_id": contract.id() }))
+        .transact()
+        .await?
+        .into_result()?;
+    let user = worker.dev_create_account().await?;
+
+    let calls = [
+        (
+            "create_update",
+            json!({ "k": "key", "v": "x".repeat(100) }),
+            NearToken::from_near(1),
+        ),
+        (
+            "create_update",
+            json!({ "k": "key", "v": "y".repeat(100) }),
+            NearToken::from_near(1),
+        ),
+        ("read", json!({ "k": "key" }), NearToken::from_yoctonear(0)),
+        (
+            "keys",
+            json!({ "from_index": 0, "limit": 10 }),
+            NearToken::from_yoctonear(0),
+        ),
+        (
+            "delete",
+            json!({ "k": "key" }),
+            NearToken::from_yoctonear(0),
+        ),
+    ];
+    let mut gas = vec![];
+    for (method, args, deposit) in calls {
+        let outcome = user
+            .call(contract.id(), method)
+            .args_json(args)
+            .deposit(deposit)
+            .transact()
+            .await?;
+        gas.push(outcome.total_gas_burnt.as_gas());
+        outcome.into_result()?;
+    }
+    Ok(gas)
+}
@@ -0,0 +1,192 @@
+// Sandbox integration tests
+//
+// Deploys the release wasm of the contract to a local NEAR sandbox node and drives it from
+// several accounts, checking results, balances, storage usage and logs end to end.
+//
+// Run with `make integration-test`; the wasm is built first if it is missing.
+use near_workspaces::types::NearToken;
+use near_workspaces::{Account, Contract, Worker};
+use serde_json::json;
+use std::path::Path;
+use std::process::Command;
+
+/// Path of the release wasm, relative to this crate
+const WASM: &str = "../target/wasm32-unknown-unknown/release/key_value_storage.wasm";
+
+/// Price of a byte of storage on the sandbox node, in yoctoNEAR
+const STORAGE_BYTE_COST: u128 = 10_000_000_000_000_000_000;
+
+/// Builds the contract for wasm32 unless the wasm is already there, and returns it.
+fn wasm() -> anyhow::Result<Vec<u8>> {
+    if !Path::new(WASM).exists() {
+        let status = Command::new(env!("CARGO"))
+            .args([
+                "build",
+                "--lib",
+                "--release",
+                "--target",
+                "wasm32-unknown-unknown",
+            ])
+            .current_dir("..")
+            .status()?;
+        anyhow::ensure!(status.success(), "building the contract wasm failed");
+    }
+    Ok(std::fs::read(WASM)?)
+}
+
+/// Deploys and initializes the contract, owned by its own account, and creates two users.
+async fn setup() -> anyhow::Result<(
+    Worker<near_workspaces::network::Sandbox>,
+    Contract,
+    Account,
+    Account,
+)> {
+    let worker = near_workspaces::sandbox().await?;
+    let contract = worker.dev_deploy(&wasm()?).await?;
+    contract
+        .call("new")
+        .args_json(json!({ "owner_id": contract.id() }))
+        .transact()
+        .await?
+        .into_result()?;
+    let alice = worker.dev_create_account().await?;
+    let bob = worker.dev_create_account().await?;
+    Ok((worker, contract, alice, bob))
+}
+
+async fn read(contract: &Contract, k: &str) -> anyhow::Result<Option<String>> {
+    Ok(contract
+        .view("read")
+        .args_json(json!({ "k": k }))
+        .await?
+        .json()?)
+}
+
+async fn storage_usage(contract: &Contract) -> anyhow::Result<u64> {
+    Ok(contract.view_account().await?.storage_usage)
+}
+
+// Test that a pair written by one account is readable by everyone and charges the writer for
+// the storage it adds.
+#[tokio::test]
+async fn create_read_charges_storage() -> anyhow::Result<()> {
+    let (_worker, contract, alice, _bob) = setup().await?;
+    let value = "x".repeat(1000);
+    let storage_before = storage_usage(&contract).await?;
+    let balance_before = alice.view_account().await?.balance;
+
+    alice
+        .call(contract.id(), "create_update")
+        .args_json(json!({ "k": "greeting", "v": value }))
+        .deposit(NearToken::from_near(1))
+        .transact()
+        .await?
+        .into_result()?;
+
+    assert_eq!(Some(value), read(&contract, "greeting").await?);
+    let grown = storage_usage(&contract).await? - storage_before;
+    assert!(grown > 1000, "storage grew by only {} bytes", grown);
+    let spent = balance_before.as_yoctonear() - alice.view_account().await?.balance.as_yoctonear();
+    assert!(
+        spent >= u128::from(grown) * STORAGE_BYTE_COST,
+        "alice paid {} for {} bytes",
+        spent,
+        grown
+    );
+    assert!(
+        spent < NearToken::from_millinear(100).as_yoctonear(),
+        "the surplus deposit was not refunded"
+    );
+    Ok(())
+}
+
+// Test that updates and deletes by another account are refused, while the key owner can
+// update and delete and is refunded the freed storage.
+#[tokio::test]
+async fn update_delete_across_accounts() -> anyhow::Result<()> {
+    let (_worker, contract, alice, bob) = setup().await?;
+    let storage_empty = storage_usage(&contract).await?;
+    alice
+        .call(contract.id(), "create_update")
+        .args_json(json!({ "k": "k", "v": "x".repeat(1000) }))
+        .deposit(NearToken::from_near(1))
+        .transact()
+        .await?
+        .into_result()?;
+
+    let outcome = bob
+        .call(contract.id(), "create_update")
+        .args_json(json!({ "k": "k", "v": "bob" }))
+        .deposit(NearToken::from_near(1))
+        .transact()
+        .await?;
+    assert!(outcome.is_failure());
+    assert!(format!("{:?}", outcome.into_result().unwrap_err())
+        .contains("Only the owner or the key owner can update 'k'"));
+    let outcome = bob
+        .call(contract.id(), "delete")
+        .args_json(json!({ "k": "k" }))
+        .transact()
+        .await?;
+    assert!(outcome.is_failure());
+    assert_eq!(Some("x".repeat(1000)), read(&contract, "k").await?);
+
+    let previous: Option<String> = alice
+        .call(contract.id(), "create_update")
+        .args_json(json!({ "k": "k", "v": "y".repeat(1000) }))
+        .deposit(NearToken::from_near(1))
+        .transact()
+        .await?
+        .json()?;
+    assert_eq!(Some("x".repeat(1000)), previous);
+
+    let balance_before = alice.view_account().await?.balance;
+    alice
+        .call(contract.id(), "delete")
+        .args_json(json!({ "k": "k" }))
+        .transact()
+        .await?
+        .into_result()?;
+    assert_eq!(None, read(&contract, "k").await?);
+    assert_eq!(storage_empty, storage_usage(&contract).await?);
+    assert!(
+        alice.view_account().await?.balance > balance_before,
+        "the freed storage was not refunded"
+    );
+    Ok(())
+}
+
+// Test that writes and deletes log NEP-297 events naming the acting account.
+#[tokio::test]
+async fn events_are_logged() -> anyhow::Result<()> {
+    let (_worker, contract, alice, _bob) = setup().await?;
+    let outcome = alice
+        .call(contract.id(), "create_update")
+        .args_json(json!({ "k": "k", "v": "v" }))
+        .deposit(NearToken::from_near(1))
+        .transact()
+        .await?
+        .into_result()?;
+    let event = find_event(&outcome.logs(), "kv_create").expect("no kv_create event");
+    assert_eq!(json!("k"), event["data"][0]["key"]);
+    assert_eq!(json!(alice.id()), event["data"][0]["actor"]);
+    assert_eq!(json!("v"), event["data"][0]["new_value"]);
+
+    let outcome = alice
+        .call(contract.id(), "delete")
+        .args_json(json!({ "k": "k" }))
+        .transact()
+        .await?
+        .into_result()?;
+    let event = find_event(&outcome.logs(), "kv_delete").expect("no kv_delete event");
+    assert_eq!(json!("v"), event["data"][0]["old_value"]);
+    Ok(())
+}
+
+/// The first `EVENT_JSON:` log of `name`, parsed
+fn find_event(logs: &[&str], name: &str) -> Option<serde_json::Value> {
+    logs.iter()
+        .filter_map(|log| log.strip_prefix("EVENT_JSON:"))
+        .filter_map(|payload| serde_json::from_str::<serde_json::Value>(payload).ok())
+        .find(|event| event["event"] == name)
+}
@@ -3,19 +3,500 @@
 /// Create a simple CRUD backend in Rust that utilizes
 /// the on-chain storage offered by NEAR.
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
-use near_sdk::collections::UnorderedMap;
-use near_sdk::{env, near_bindgen};
+use near_sdk::collections::{
+    LazyOption, LookupMap, LookupSet, TreeMap, UnorderedMap, UnorderedSet, Vector,
+};
+use near_sdk::json_types::ValidAccountId;
+use near_sdk::json_types::{Base64VecU8, I128, I64, U128, U64};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{
+    env, near_bindgen, AccountId, Balance, Gas, PanicOnDefault, Promise, PromiseOrValue,
+    PromiseResult, StorageUsage,
+};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::ops::Bound;
+use unicode_normalization::{is_nfc, UnicodeNormalization};
 
 // near_sdk::setup_alloc!();
 
 // Setup the global allocator from the [wee_alloc] crate using the [setup_alloc!()] macro.
 // Allocators are the way that programs in Rust obtain memory from the system at runtime. [wee_alloc] is a
 // memory allocator designed for WebAssembly. It generates less than a kilobyte of uncompressed WebAssyembly code.
+// Without the `wee_alloc` feature the Rust standard allocator is used.
 
-#[cfg(target_arch = "wasm32")]
+#[cfg(all(target_arch = "wasm32", feature = "wee_alloc"))]
 #[global_allocator]
 static ALLOC: near_sdk::wee_alloc::WeeAlloc<'_> = near_sdk::wee_alloc::WeeAlloc::INIT;
 
+/// Storage prefix of the [KeyValue::pairs] map.
+const PAIRS_PREFIX: &[u8] = b"r";
+
+/// Storage prefix of the values [StoredValue::Large] keeps out of [KeyValue::pairs].
+const LARGE_VALUES_PREFIX: &[u8] = b"B";
+
+/// Storage prefix of the [KeyValue::key_list] map, and of the keys of each shard.
+const KEY_LIST_PREFIX: &[u8] = b"R";
+const KEY_SHARD_PREFIX: &[u8] = b"Q";
+
+/// Storage prefix of the [KeyValue::index] ordered key index.
+const INDEX_PREFIX: &[u8] = b"t";
+
+/// Storage prefix of the [KeyValue::meta] per-key metadata map.
+const META_PREFIX: &[u8] = b"m";
+
+/// Storage prefix of the [KeyValue::expiry_index] map.
+const EXPIRY_INDEX_PREFIX: &[u8] = b"e";
+
+/// Storage prefix of the [KeyValue::history] map.
+const HISTORY_PREFIX: &[u8] = b"h";
+
+/// Storage prefix of the [KeyValue::lists] map, and of the elements of each list.
+const LISTS_PREFIX: &[u8] = b"l";
+const LIST_ELEMENTS_PREFIX: &[u8] = b"L";
+
+/// Storage prefix of the [KeyValue::sets] map, and of the members of each set.
+const SETS_PREFIX: &[u8] = b"s";
+const SET_MEMBERS_PREFIX: &[u8] = b"S";
+
+/// Storage prefix of the [KeyValue::hashes] map, and of the fields of each hash.
+const HASHES_PREFIX: &[u8] = b"f";
+const HASH_FIELDS_PREFIX: &[u8] = b"F";
+
+/// Storage prefix of the [KeyValue::sorted_sets] map, and of the contents of each sorted set.
+const SORTED_SETS_PREFIX: &[u8] = b"z";
+const SORTED_SET_MEMBERS_PREFIX: &[u8] = b"Z";
+
+/// Storage prefix of the [KeyValue::value_index] map, and of the key set of each value.
+const VALUE_INDEX_PREFIX: &[u8] = b"v";
+const VALUE_KEYS_PREFIX: &[u8] = b"V";
+
+/// Storage prefix of the [KeyValue::tag_index] map, and of the key set of each tag.
+const TAG_INDEX_PREFIX: &[u8] = b"g";
+const TAG_KEYS_PREFIX: &[u8] = b"G";
+
+/// Storage prefix of the [KeyValue::roles] map.
+const ROLES_PREFIX: &[u8] = b"a";
+
+/// Storage prefix of the [KeyValue::writers] allowlist.
+const WRITERS_PREFIX: &[u8] = b"w";
+
+/// Storage prefix of the [KeyValue::blacklist] set.
+const BLACKLIST_PREFIX: &[u8] = b"b";
+
+/// Storage prefix of the [KeyValue::proposals] map.
+const PROPOSALS_PREFIX: &[u8] = b"p";
+
+/// Storage prefix of the [KeyValue::queued] map.
+const QUEUED_PREFIX: &[u8] = b"q";
+
+/// Storage prefix of the [KeyValue::storage_paid] map.
+const STORAGE_PAID_PREFIX: &[u8] = b"u";
+
+/// Storage prefix of the [KeyValue::storage_deposits] map.
+const STORAGE_DEPOSITS_PREFIX: &[u8] = b"c";
+
+/// Storage prefix of the [KeyValue::credits] token balances.
+const CREDITS_PREFIX: &[u8] = b"j";
+
+/// Storage prefix of the [KeyValue::namespaces] map, and of the per-namespace usage in
+/// [KeyValue::namespace_usage].
+const NAMESPACES_PREFIX: &[u8] = b"i";
+const NAMESPACE_USAGE_PREFIX: &[u8] = b"I";
+
+/// Storage prefix of the [KeyValue::relay_keys] map, and of the last nonce of each author in
+/// [KeyValue::relay_nonces].
+const RELAY_KEYS_PREFIX: &[u8] = b"K";
+const RELAY_NONCES_PREFIX: &[u8] = b"N";
+
+/// Storage prefix of the [KeyValue::audit] log, and of the per-key lengths in
+/// [KeyValue::audit_lengths].
+const AUDIT_PREFIX: &[u8] = b"d";
+const AUDIT_LENGTHS_PREFIX: &[u8] = b"D";
+
+/// Storage prefix of the [KeyValue::changes] feed.
+const CHANGES_PREFIX: &[u8] = b"n";
+
+/// Storage prefix of the [KeyValue::known_writers] set.
+const KNOWN_WRITERS_PREFIX: &[u8] = b"k";
+
+/// Storage prefix of the [KeyValue::subscribers] map.
+const SUBSCRIBERS_PREFIX: &[u8] = b"x";
+
+/// Storage prefix of the [KeyValue::oracles] set.
+const ORACLES_PREFIX: &[u8] = b"o";
+
+/// Storage prefix of the [KeyValue::feeds] map.
+const FEEDS_PREFIX: &[u8] = b"y";
+
+/// Upper bound on the number of entries a single batch call may touch, so one
+/// call can never run out of gas half-way through a large batch.
+pub const MAX_BATCH_SIZE: usize = 100;
+
+/// Number of shards [KeyValue::key_list] is split into, by the first byte of the key's hash.
+pub const KEY_LIST_SHARDS: u8 = 16;
+
+/// Upper bound on the number of items a single page of a listing view returns.
+pub const MAX_PAGE_SIZE: u64 = 100;
+
+/// Upper bound on the number of changes a single [KeyValue::diff] call compacts.
+pub const MAX_DIFF_SPAN: u64 = 1000;
+
+/// Size limits applied until the owner configures otherwise.
+pub const DEFAULT_MAX_KEY_LEN: u64 = 256;
+pub const DEFAULT_MAX_VALUE_LEN: u64 = 4096;
+
+/// Values longer than this many bytes are stored behind their own storage entry, see
+/// [StoredValue].
+pub const LARGE_VALUE_THRESHOLD: usize = 1024;
+
+/// Upper bound on the number of tags attached to a single key.
+pub const MAX_TAGS_PER_KEY: usize = 10;
+
+/// Upper bound on the number of accounts granted write access to a single key.
+pub const MAX_WRITE_GRANTS_PER_KEY: usize = 10;
+
+/// Upper bound on the number of designated readers of a single unlisted key.
+pub const MAX_READERS_PER_KEY: usize = 10;
+
+/// Number of previous values kept per key until the owner configures otherwise.
+pub const DEFAULT_HISTORY_DEPTH: u64 = 10;
+
+/// Upper bound on the configurable history depth.
+pub const MAX_HISTORY_DEPTH: u64 = 100;
+
+/// Flags of [KeyValue::set_paused_methods], each disabling one group of mutating methods.
+pub const PAUSE_WRITE: u64 = 1 << 0;
+pub const PAUSE_DELETE: u64 = 1 << 1;
+pub const PAUSE_RENAME: u64 = 1 << 2;
+pub const PAUSE_TAGS: u64 = 1 << 3;
+pub const PAUSE_COLLECTIONS: u64 = 1 << 4;
+pub const PAUSE_ACCESS: u64 = 1 << 5;
+pub const PAUSE_PURGE: u64 = 1 << 6;
+pub const PAUSE_RESTORE: u64 = 1 << 7;
+pub const PAUSE_STORAGE: u64 = 1 << 8;
+
+/// Bytes reserved by a NEP-145 registration, covering the account's own ledger entries.
+pub const STORAGE_REGISTRATION_BYTES: StorageUsage = 128;
+
+/// Time (ns) a multisig proposal stays open for confirmations: 7 days.
+pub const PROPOSAL_LIFETIME_NS: u64 = 7 * 24 * 60 * 60 * 1_000_000_000;
+
+/// Gas attached to the batch of changes a call forwards to the mirror contract, plus
+/// [MIRROR_GAS_PER_ENTRY] for each changed key: 20 TGas.
+pub const MIRROR_GAS: Gas = 20_000_000_000_000;
+
+/// Gas attached to the [KeyValue::on_mirror] callback of a mirrored batch, plus
+/// [MIRROR_GAS_PER_ENTRY] for each changed key: 20 TGas.
+pub const MIRROR_CALLBACK_GAS: Gas = 20_000_000_000_000;
+
+/// Gas added to both the mirrored batch and its callback for each changed key it carries:
+/// 5 TGas. A call changing more keys than the gas it has left covers is rejected.
+pub const MIRROR_GAS_PER_ENTRY: Gas = 5_000_000_000_000;
+
+/// Gas attached to a lookup forwarded to the upstream contract: 30 TGas, leaving room for it
+/// to forward the lookup further.
+pub const UPSTREAM_GAS: Gas = 30_000_000_000_000;
+
+/// Gas attached to the [KeyValue::on_upstream_read] callback: 10 TGas.
+pub const UPSTREAM_CALLBACK_GAS: Gas = 10_000_000_000_000;
+
+/// Gas attached to the `nft_token` lookup of a [KeyValue::gated_write]: 10 TGas.
+pub const NFT_LOOKUP_GAS: Gas = 10_000_000_000_000;
+
+/// Gas attached to the [KeyValue::on_nft_verified] callback, which commits the write: 30 TGas.
+pub const NFT_CALLBACK_GAS: Gas = 30_000_000_000_000;
+
+/// Gas attached to each `on_kv_change` notification of a subscriber: 5 TGas.
+pub const SUBSCRIBER_GAS: Gas = 5_000_000_000_000;
+
+/// Upper bound on the subscribers of a single key prefix.
+pub const MAX_SUBSCRIBERS_PER_PREFIX: usize = 10;
+
+/// Upper bound on the notifications a single write fires, across all matching prefixes.
+pub const MAX_NOTIFICATIONS_PER_WRITE: usize = 10;
+
+/// Gas attached to the receiver's `ft_on_transfer` in [KeyValue::ft_transfer_call]: 30 TGas.
+pub const FT_TRANSFER_CALL_GAS: Gas = 30_000_000_000_000;
+
+/// Gas attached to the [KeyValue::ft_resolve_transfer] callback: 10 TGas.
+pub const FT_RESOLVE_GAS: Gas = 10_000_000_000_000;
+
+/// Gas attached to the receiver's `nft_on_transfer` in [KeyValue::nft_transfer_call]: 30 TGas.
+pub const NFT_TRANSFER_CALL_GAS: Gas = 30_000_000_000_000;
+
+/// Gas attached to the [KeyValue::nft_resolve_transfer] callback: 10 TGas.
+pub const NFT_RESOLVE_GAS: Gas = 10_000_000_000_000;
+
+/// Gas attached to the `migrate` call that follows an [KeyValue::upgrade]: 100 TGas.
+pub const MIGRATE_GAS: Gas = 100_000_000_000_000;
+
+/// NEP-297 standard name under which data events are logged.
+pub const EVENT_STANDARD: &str = "kv";
+
+/// Version of the [EVENT_STANDARD] event format.
+pub const EVENT_VERSION: &str = "1.0.0";
+
+/// Values longer than this many bytes are carried in events by their hash only.
+pub const EVENT_VALUE_INLINE_LIMIT: usize = 256;
+
+/// Logs a NEP-297 `EVENT_JSON:` event (`kv_create`, `kv_update` or `kv_delete`) for key `k`,
+/// naming the `actor` and the current block. The previous and new values are
+/// included as `old_value`/`new_value`, or as base64 sha256 `old_value_hash`/`new_value_hash`
+/// when they exceed [EVENT_VALUE_INLINE_LIMIT] or `reveal` is false (unlisted and premium
+/// entries).
+fn emit_kv_event(
+    event: &str,
+    k: &str,
+    actor: &str,
+    old: Option<&Value>,
+    new: Option<&Value>,
+    reveal: bool,
+) {
+    let mut data = near_sdk::serde_json::json!({
+        "key": k,
+        "actor": actor,
+        "block_height": U64(env::block_index()),
+        "block_timestamp": U64(env::block_timestamp()),
+    });
+    for (field, value) in [("old_value", old), ("new_value", new)].iter() {
+        if let Some(value) = value {
+            let text = value.text();
+            if reveal && text.len() <= EVENT_VALUE_INLINE_LIMIT {
+                data[*field] = near_sdk::serde_json::json!(text);
+            } else {
+                let hash = near_sdk::base64::encode(env::sha256(text.as_bytes()));
+                data[format!("{}_hash", field)] = near_sdk::serde_json::json!(hash);
+            }
+        }
+    }
+    emit_event(EVENT_STANDARD, EVENT_VERSION, event, data);
+}
+
+/// Logs a NEP-297 [EVENT_STANDARD] event that is not a data change, such as `kv_rollback`,
+/// the blacklist events or the ownership events, with the current block added to `data`.
+fn emit_kv_control_event(event: &str, mut data: near_sdk::serde_json::Value) {
+    data["block_height"] = near_sdk::serde_json::json!(U64(env::block_index()));
+    data["block_timestamp"] = near_sdk::serde_json::json!(U64(env::block_timestamp()));
+    emit_event(EVENT_STANDARD, EVENT_VERSION, event, data);
+}
+
+/// Logs a NEP-297 `EVENT_JSON:` event of `standard` with a single `data` entry.
+fn emit_event(standard: &str, version: &str, event: &str, data: near_sdk::serde_json::Value) {
+    let payload = near_sdk::serde_json::json!({
+        "standard": standard,
+        "version": version,
+        "event": event,
+        "data": [data],
+    });
+    env::log(format!("EVENT_JSON:{}", payload).as_bytes());
+}
+
+/// Deploys `code` to the contract account and then calls `migrate` on it.
+fn internal_upgrade(code: Base64VecU8) -> Promise {
+    env::log(format!("upgrade: deploying {} bytes", code.0.len()).as_bytes());
+    Promise::new(env::current_account_id())
+        .deploy_contract(code.0)
+        .function_call(b"migrate".to_vec(), vec![], 0, MIGRATE_GAS)
+}
+
+/// Public key of an implicit account, whose id is the hex encoding of its ed25519 key
+fn implicit_account_key(account_id: &str) -> Option<Vec<u8>> {
+    if account_id.len() != 64 {
+        return None;
+    }
+    (0..64)
+        .step_by(2)
+        .map(|i| u8::from_str_radix(account_id.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// True if `signature` is a valid ed25519 signature of `message` by `public_key`
+fn verify_ed25519(public_key: &[u8], message: &[u8], signature: &[u8]) -> bool {
+    <[u8; 32]>::try_from(public_key)
+        .ok()
+        .and_then(|key| ed25519_dalek::VerifyingKey::from_bytes(&key).ok())
+        .zip(<[u8; 64]>::try_from(signature).ok())
+        .is_some_and(|(key, signature)| {
+            let signature = ed25519_dalek::Signature::from_bytes(&signature);
+            key.verify_strict(message, &signature).is_ok()
+        })
+}
+
+/// near-abi description of the contract's methods and argument schemas, zstd compressed as
+/// [__contract_abi] serves it. Checked in as abi.json and embedded by the build script.
+pub const CONTRACT_ABI: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/abi.json.zst"));
+
+/// Returns [CONTRACT_ABI] as raw bytes for tooling such as near-cli-rs, the way contracts
+/// built with ABI support on newer SDKs do.
+#[cfg(target_arch = "wasm32")]
+#[no_mangle]
+pub extern "C" fn __contract_abi() {
+    env::setup_panic_hook();
+    env::set_blockchain_interface(Box::new(near_blockchain::NearBlockchain {}));
+    env::value_return(CONTRACT_ABI);
+}
+
+/// Shard of [KeyValue::key_list] holding `k`
+fn key_shard(k: &str) -> u8 {
+    env::sha256(k.as_bytes())[0] % KEY_LIST_SHARDS
+}
+
+/// Unique storage prefix for a collection nested under key `k`
+fn nested_prefix(prefix: &[u8], k: &str) -> Vec<u8> {
+    let mut nested = prefix.to_vec();
+    nested.extend(env::sha256(k.as_bytes()));
+    nested
+}
+
+/// Why a call failed. Every panic of the contract reads `<code>: <message>` with the stable
+/// [KvError::code] first, e.g. `key_not_found: Key 'k' does not exist`, so clients can match
+/// on the code while the message stays free to change.
+#[derive(Debug, Clone, PartialEq)]
+pub enum KvError {
+    /// No live entry under the key
+    KeyNotFound(String),
+    /// A live entry already exists under the key
+    KeyExists(String),
+    /// The record a call refers to (version, proposal, queued action, ...) does not exist
+    NotFound(String),
+    /// The caller may not perform the call
+    Unauthorized(String),
+    KeyTooLarge {
+        len: u64,
+        max: u64,
+    },
+    ValueTooLarge(String),
+    /// A storage quota of the caller or a namespace would be exceeded
+    QuotaExceeded(String),
+    /// A fixed limit of the contract (batch size, readers per key, ...) would be exceeded
+    LimitExceeded(String),
+    InsufficientDeposit(String),
+    /// A storage balance, credit or treasury balance is too low
+    InsufficientBalance(String),
+    /// The account has no storage deposit
+    NotRegistered(AccountId),
+    InvalidArgument(String),
+    /// A retention window, relayed write or proposal has run out
+    Expired(String),
+    /// The call needs a multisig proposal, more confirmations or the timelock to pass
+    ApprovalRequired(String),
+    /// The contract, or the named method group, is paused
+    Paused(String),
+    /// Writes are rejected while the contract is in maintenance mode
+    Maintenance,
+    Unsupported(String),
+    Overflow(String),
+    /// Contract state is missing or in an unexpected layout
+    InvalidState(String),
+}
+
+impl KvError {
+    /// Stable code of the error, the part of the panic message before the colon
+    pub fn code(&self) -> &'static str {
+        match self {
+            KvError::KeyNotFound(_) => "key_not_found",
+            KvError::KeyExists(_) => "key_exists",
+            KvError::NotFound(_) => "not_found",
+            KvError::Unauthorized(_) => "unauthorized",
+            KvError::KeyTooLarge { .. } => "key_too_large",
+            KvError::ValueTooLarge(_) => "value_too_large",
+            KvError::QuotaExceeded(_) => "quota_exceeded",
+            KvError::LimitExceeded(_) => "limit_exceeded",
+            KvError::InsufficientDeposit(_) => "insufficient_deposit",
+            KvError::InsufficientBalance(_) => "insufficient_balance",
+            KvError::NotRegistered(_) => "not_registered",
+            KvError::InvalidArgument(_) => "invalid_argument",
+            KvError::Expired(_) => "expired",
+            KvError::ApprovalRequired(_) => "approval_required",
+            KvError::Paused(_) => "paused",
+            KvError::Maintenance => "maintenance",
+            KvError::Unsupported(_) => "unsupported",
+            KvError::Overflow(_) => "overflow",
+            KvError::InvalidState(_) => "invalid_state",
+        }
+    }
+
+    /// Human readable part of the panic message
+    pub fn message(&self) -> String {
+        match self {
+            KvError::KeyNotFound(k) => format!("Key '{}' does not exist", k),
+            KvError::KeyExists(k) => format!("Key '{}' already exists", k),
+            KvError::KeyTooLarge { len, max } => {
+                format!("Key is {} bytes, exceeding the limit of {}", len, max)
+            }
+            KvError::NotRegistered(account_id) => {
+                format!("Account {} is not registered", account_id)
+            }
+            KvError::Paused(what) => format!("{} is paused", what),
+            KvError::Maintenance => "The contract is read-only during maintenance".to_string(),
+            KvError::NotFound(message)
+            | KvError::Unauthorized(message)
+            | KvError::ValueTooLarge(message)
+            | KvError::QuotaExceeded(message)
+            | KvError::LimitExceeded(message)
+            | KvError::InsufficientDeposit(message)
+            | KvError::InsufficientBalance(message)
+            | KvError::InvalidArgument(message)
+            | KvError::Expired(message)
+            | KvError::ApprovalRequired(message)
+            | KvError::Unsupported(message)
+            | KvError::Overflow(message)
+            | KvError::InvalidState(message) => message.clone(),
+        }
+    }
+
+    /// Aborts the call with this error.
+    pub fn panic(&self) -> ! {
+        env::panic(self.to_string().as_bytes())
+    }
+}
+
+impl std::fmt::Display for KvError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.code(), self.message())
+    }
+}
+
+/// Like `assert!`, but fails with a [KvError], which is only built when `cond` is false.
+macro_rules! require {
+    ($cond:expr, $error:expr) => {
+        if !$cond {
+            $error.panic()
+        }
+    };
+}
+
+/// Like [require], but returns the [KvError] from a `Result<_, KvError>` function instead of
+/// panicking, for checks a caller must be able to recover from.
+macro_rules! ensure {
+    ($cond:expr, $error:expr) => {
+        if !$cond {
+            return Err($error);
+        }
+    };
+}
+
+fn assert_history_depth(depth: u64) {
+    require!(
+        depth <= MAX_HISTORY_DEPTH,
+        KvError::LimitExceeded(format!(
+            "History depth exceeds the limit of {}",
+            MAX_HISTORY_DEPTH
+        ))
+    );
+}
+
+fn assert_batch_size(len: usize) {
+    require!(
+        len <= MAX_BATCH_SIZE,
+        KvError::LimitExceeded(format!(
+            "Batch size exceeds the limit of {} entries",
+            MAX_BATCH_SIZE
+        ))
+    );
+}
+
 // Follow a patter using one structure (struct) and an implementation (impl)
 // associated with it. This is a pattern used in most Rust contracts on NEAR.
 
@@ -32,102 +513,9681 @@ static ALLOC: near_sdk::wee_alloc::WeeAlloc<'_> = near_sdk::wee_alloc::WeeAlloc:
 
 // Attributes of struct KeyValue
 #[near_bindgen]
-#[derive(BorshDeserialize, BorshSerialize)]
+#[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
 pub struct KeyValue {
+    // Values by key; a read or write touches a single storage entry
+    pairs: LookupMap<String, StoredValue>,
+    // Number of keys in `pairs`
+    pair_count: u64,
+    // Number of keys in `lists`, `sets`, `hashes`, `sorted_sets` and `namespaces`
+    collection_count: u64,
+    // Key, expiry, value and tag indexes
+    indexes: Lazy<Indexes>,
+    // Per-key metadata, kept apart from `pairs` so it can be checked without reading values
+    meta: LookupMap<String, EntryMeta>,
+    // Previous values of each key, addressed by (key, version)
+    history: LookupMap<(String, u64), Revision>,
+    // Audit log, change feed and operation counters
+    audit_log: Lazy<AuditLog>,
+    // List values, a keyspace separate from `pairs`; elements are only loaded when accessed
+    lists: LookupMap<String, Vector<String>>,
+    // Set values, a keyspace separate from `pairs` and `lists`
+    sets: LookupMap<String, UnorderedSet<String>>,
+    // Hash values with named fields, a keyspace separate from `pairs`
+    hashes: LookupMap<String, UnorderedMap<String, String>>,
+    // Scored sets, a keyspace separate from `pairs`
+    sorted_sets: LookupMap<String, SortedSet>,
+    // Roles, writer allowlist, blacklist and multisig
+    acl: Lazy<Acl>,
+    // Contract every change of an entry is replicated to, once configured
+    mirror_id: Option<AccountId>,
+    // Contract whose replicated entries `mirror_apply` accepts, once configured
+    mirror_source_id: Option<AccountId>,
+    // Namespace only holders of an NFT may write to, once configured
+    nft_gate: Option<NftGate>,
+    // Contract asked by `read_through` for keys missing here, once configured
+    upstream_id: Option<AccountId>,
+    // Croncat manager allowed to call `croncat_tick`, once configured
+    croncat_manager_id: Option<AccountId>,
+    // Private keyspace of each calling account, addressed by (namespace, key), and the bytes
+    // each namespace uses
+    namespaces: LookupMap<(AccountId, String), String>,
+    namespace_usage: LookupMap<AccountId, StorageUsage>,
+    // Contracts notified of writes to keys starting with each prefix, and the number of
+    // subscribed prefixes of each byte length, so a write only looks up prefixes that may match
+    subscribers: LookupMap<String, Vec<AccountId>>,
+    subscribed_prefix_lens: BTreeMap<u64, u64>,
+    // Accounts allowed to push oracle feeds
+    oracles: UnorderedSet<AccountId>,
+    // Latest round of each oracle feed, a namespace separate from `pairs`
+    feeds: LookupMap<String, FeedRound>,
+    // Pending multisig proposals by id
+    proposals: LookupMap<u64, Proposal>,
+    next_proposal_id: u64,
+    // Proposals below this id have been executed or dropped by `croncat_tick`
+    proposal_sweep_from: u64,
+    // Timelocked administrative actions by id
+    queued: LookupMap<u64, QueuedAction>,
+    next_queued_id: u64,
+    // Storage bytes each account has paid for and can be refunded when freeing them
+    storage_paid: UnorderedMap<AccountId, StorageUsage>,
+    // Unspent NEP-145 storage deposit of each registered account, beyond the registration reserve
+    storage_deposits: LookupMap<AccountId, Balance>,
+    // Storage credit token balances, bought 1:1 with yoctoNEAR and burnt when spent on writes
+    credits: LookupMap<AccountId, Balance>,
+    credits_supply: Balance,
+    // Token that can be transferred in place of a NEAR storage deposit, once configured
+    storage_token: Option<StorageToken>,
+    // Write fees collected and not yet withdrawn
+    treasury: Balance,
+    // ed25519 key each author signs relayed writes with, and the last nonce relayed for them
+    relay_keys: LookupMap<AccountId, Vec<u8>>,
+    relay_nonces: LookupMap<AccountId, u64>,
+    // Values written by the current call, charged the flat write fee; never persisted
+    #[borsh_skip]
+    writes_in_call: u64,
+    // Author of the relayed or NFT-verified write being applied, who is treated as the caller;
+    // never persisted
+    #[borsh_skip]
+    acting_author: Option<AccountId>,
+    // Storage bytes the entries of each payer gained (positive) or freed in the current call,
+    // settled with the payer rather than the caller; never persisted
+    #[borsh_skip]
+    payer_deltas: BTreeMap<AccountId, i64>,
+    // Entries of the keys changed by the current call as they were before it, replicated to
+    // `mirror_id` once the call is done; never persisted
+    #[borsh_skip]
+    mirror_changes: BTreeMap<String, Option<MirroredEntry>>,
+    // Account allowed to call administrative methods
+    owner_id: AccountId,
+    // Account proposed as the next owner, until it accepts
+    pending_owner_id: Option<AccountId>,
+    // While set, every mutating method except administrative ones is rejected
+    paused: bool,
+    // While set, all data writes are rejected with the "maintenance" error code
+    maintenance: bool,
+    // Bitmask of PAUSE_* flags for method groups disabled individually
+    paused_methods: u64,
+    // Settings chosen at initialization and adjustable by the owner
+    config: Config,
+    // Pairs of the original layout that [KeyValue::migrate_batch] has not moved yet
+    legacy_pairs: Option<UnorderedMap<String, String>>,
+}
+
+/// Storage keys of the parts of the state in a [Lazy] of their own.
+const INDEXES_KEY: &[u8] = b"Cindexes";
+const AUDIT_LOG_KEY: &[u8] = b"Caudit";
+const ACL_KEY: &[u8] = b"Cacl";
+
+/// Part of the contract state kept under its own storage key rather than inline in the root
+/// struct, so calls that never touch it do not deserialize it. It is loaded on first access;
+/// once borrowed mutably it must be written back with [Lazy::flush] before the call ends,
+/// which [KeyValue::internal_flush] does ahead of settling storage.
+pub struct Lazy<T> {
+    storage_key: Vec<u8>,
+    value: std::cell::OnceCell<T>,
+    dirty: bool,
+}
+
+impl<T: BorshSerialize + BorshDeserialize> Lazy<T> {
+    fn new(storage_key: &[u8], value: T) -> Self {
+        Self {
+            storage_key: storage_key.to_vec(),
+            value: std::cell::OnceCell::from(value),
+            dirty: true,
+        }
+    }
+
+    fn get(&self) -> &T {
+        self.value.get_or_init(|| {
+            let raw = env::storage_read(&self.storage_key).unwrap_or_else(|| {
+                KvError::InvalidState("Lazy state is missing".to_string()).panic()
+            });
+            T::try_from_slice(&raw).unwrap()
+        })
+    }
+
+    fn get_mut(&mut self) -> &mut T {
+        self.get();
+        self.dirty = true;
+        self.value.get_mut().unwrap()
+    }
+
+    /// Writes the value back to its storage key if it was borrowed mutably since the last flush
+    fn flush(&mut self) {
+        if let Some(value) = self.value.get().filter(|_| self.dirty) {
+            env::storage_write(&self.storage_key, &value.try_to_vec().unwrap());
+            self.dirty = false;
+        }
+    }
+}
+
+impl<T: BorshSerialize> BorshSerialize for Lazy<T> {
+    fn serialize<W: std::io::Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        // Writing the value here would go unbilled, so a missed flush fails the call instead
+        if self.dirty {
+            return Err(std::io::Error::other("Lazy state was not flushed"));
+        }
+        BorshSerialize::serialize(&self.storage_key, writer)
+    }
+}
+
+impl<T> BorshDeserialize for Lazy<T> {
+    fn deserialize(buf: &mut &[u8]) -> std::io::Result<Self> {
+        Ok(Self {
+            storage_key: <Vec<u8> as BorshDeserialize>::deserialize(buf)?,
+            value: std::cell::OnceCell::new(),
+            dirty: false,
+        })
+    }
+}
+
+/// Indexes over the keys in [KeyValue::pairs]
+#[derive(BorshDeserialize, BorshSerialize)]
+struct Indexes {
+    // Enumeration indexes, maintained only while `config.listing` is set: sharded by key hash
+    // for paged listings, and ordered for range and prefix scans
+    key_list: LookupMap<u8, UnorderedSet<String>>,
+    index: TreeMap<String, ()>,
+    // Keys with a TTL ordered by expiry time, so purging never has to scan the whole store
+    expiry_index: TreeMap<(u64, String), ()>,
+    // Reverse index from the sha256 of a value's text form to the keys holding it
+    value_index: LookupMap<Vec<u8>, UnorderedSet<String>>,
+    // Keys carrying each tag
+    tag_index: LookupMap<String, UnorderedSet<String>>,
+}
+
+/// Record of the mutations applied to the store
+#[derive(BorshDeserialize, BorshSerialize)]
+struct AuditLog {
+    // Append-only log of the operations on each key, addressed by (key, sequence number); it
+    // outlives the key itself
+    audit: LookupMap<(String, u64), AuditEntry>,
+    audit_lengths: LookupMap<String, u64>,
+    // Every mutation in order; the change at position i has sequence number i + 1
+    changes: Vector<Change>,
+    // Operation counters exposed by `stats`, and the accounts already counted as writers
+    stats: Stats,
+    known_writers: LookupSet<AccountId>,
+}
+
+/// Who may do what, beyond key ownership
+#[derive(BorshDeserialize, BorshSerialize)]
+struct Acl {
+    // Roles granted to each account
+    roles: LookupMap<AccountId, Vec<Role>>,
+    // Accounts allowed to write while `config.allowlist_only` is set
+    writers: UnorderedSet<AccountId>,
+    // Accounts rejected from every mutating method
+    blacklist: UnorderedSet<AccountId>,
+    // Admin set that must confirm sensitive operations, once configured
+    multisig: Option<Multisig>,
+}
+
+/// Storage key under which near-bindgen persists the contract struct.
+const STATE_KEY: &[u8] = b"STATE";
+
+/// Storage key of the layout version of the state under [STATE_KEY]. The original deployment
+/// predates it, so state without a version is of layout v0.
+const STATE_VERSION_KEY: &[u8] = b"STATE_VERSION";
+
+/// Layout version of [KeyValue], bumped along with each new [VersionedState] variant.
+const STATE_VERSION: u32 = 1;
+
+/// Layout of the original deployment: bare string pairs under the same "r" prefix as
+/// [KeyValue::pairs].
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct KeyValueV0 {
     pairs: UnorderedMap<String, String>,
 }
 
-// 2. Default Implementation
-//
-// Default implementation for KeyValue struct
-impl Default for KeyValue {
+/// Every layout the contract state has had, oldest first, told apart by the version stored
+/// under [STATE_VERSION_KEY]. A new layout is added as a variant here, with a new
+/// [STATE_VERSION], and converted in [KeyValue::migrate].
+pub enum VersionedState {
+    V0(KeyValueV0),
+    Current(Box<KeyValue>),
+}
+
+impl VersionedState {
+    fn read() -> Self {
+        let raw = env::storage_read(STATE_KEY).unwrap_or_else(|| {
+            KvError::InvalidState("The contract is not initialized".to_string()).panic()
+        });
+        let version = env::storage_read(STATE_VERSION_KEY)
+            .map_or(0, |version| u32::try_from_slice(&version).unwrap());
+        let invalid = || -> ! {
+            KvError::InvalidState(format!("State does not match layout v{}", version)).panic()
+        };
+        match version {
+            0 => VersionedState::V0(KeyValueV0::try_from_slice(&raw).unwrap_or_else(|_| invalid())),
+            STATE_VERSION => VersionedState::Current(Box::new(
+                KeyValue::try_from_slice(&raw).unwrap_or_else(|_| invalid()),
+            )),
+            _ => KvError::InvalidState(format!("Unknown state layout v{}", version)).panic(),
+        }
+    }
+
+    /// Tags the state as of the current layout
+    fn write_version() {
+        env::storage_write(STATE_VERSION_KEY, &STATE_VERSION.try_to_vec().unwrap());
+    }
+}
+
+/// Permissions an account can be granted on top of managing its own keys
+///
+/// An `admin` may call administrative methods, a `writer` may update and delete keys created by
+/// other accounts, a `moderator` may manage the blacklist, and a `reader` is restricted to reads.
+#[derive(
+    BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, Debug, PartialEq,
+)]
+#[serde(crate = "near_sdk::serde", rename_all = "lowercase")]
+pub enum Role {
+    Admin,
+    Writer,
+    Reader,
+    Moderator,
+}
+
+/// Namespace whose writes require owning a token of `nft_contract_id`, see
+/// [KeyValue::gated_write]
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftGate {
+    pub prefix: String,
+    pub nft_contract_id: AccountId,
+}
+
+/// NEP-148 metadata of the storage credit token, returned by [KeyValue::ft_metadata]
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FungibleTokenMetadata {
+    pub spec: String,
+    pub name: String,
+    pub symbol: String,
+    pub icon: Option<String>,
+    pub reference: Option<String>,
+    pub reference_hash: Option<Base64VecU8>,
+    pub decimals: u8,
+}
+
+/// NEP-171 token standing for the ownership of a key, whose id is the key itself
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Token {
+    pub token_id: String,
+    pub owner_id: AccountId,
+    pub metadata: TokenMetadata,
+}
+
+/// NEP-177 metadata of a key token; fields this contract has no data for are omitted
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TokenMetadata {
+    pub title: String,
+    // Block times (ns) the key was created and last written
+    pub issued_at: U64,
+    pub updated_at: U64,
+}
+
+/// NEP-177 metadata of the key token contract, returned by [KeyValue::nft_metadata]
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftContractMetadata {
+    pub spec: String,
+    pub name: String,
+    pub symbol: String,
+    pub icon: Option<String>,
+    pub base_uri: Option<String>,
+    pub reference: Option<String>,
+    pub reference_hash: Option<Base64VecU8>,
+}
+
+/// Write signed off-chain by `author` and submitted by a relayer through
+/// [KeyValue::relay_write]; the signature covers the sha256 of its Borsh serialization.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RelayedWrite {
+    pub author: AccountId,
+    // Contract the write is meant for, so it cannot be replayed on another deployment
+    pub receiver_id: AccountId,
+    // Must be greater than the last nonce relayed for `author`
+    pub nonce: U64,
+    // Block timestamp (ns) from which the write is rejected
+    pub expires_at: U64,
+    pub k: String,
+    pub v: String,
+}
+
+/// Fungible token accepted through [KeyValue::ft_on_transfer] as storage balance, each of its
+/// smallest units crediting `yocto_per_unit` yoctoNEAR
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StorageToken {
+    pub token_id: AccountId,
+    pub yocto_per_unit: U128,
+}
+
+/// Admins of which `threshold` must confirm a proposal before it can be executed
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Multisig {
+    pub members: Vec<AccountId>,
+    pub threshold: u64,
+}
+
+impl Multisig {
+    fn assert_valid(&self) {
+        require!(
+            self.threshold >= 1 && self.threshold <= self.members.len() as u64,
+            KvError::InvalidArgument(
+                "Threshold must be between 1 and the number of members".to_string()
+            )
+        );
+        for (i, member) in self.members.iter().enumerate() {
+            require!(
+                env::is_valid_account_id(member.as_bytes()),
+                KvError::InvalidArgument(format!("Account id '{}' is invalid", member))
+            );
+            require!(
+                !self.members[..i].contains(member),
+                KvError::InvalidArgument(format!("Account '{}' is listed twice", member))
+            );
+        }
+    }
+}
+
+/// Sensitive operation carried out by [KeyValue::execute] once confirmed
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde", tag = "type", rename_all = "snake_case")]
+pub enum AdminAction {
+    ClearAll { limit: u64 },
+    SetConfig { config: Config },
+    SetMultisig { multisig: Multisig },
+    Upgrade { code: Base64VecU8 },
+}
+
+/// An [AdminAction] awaiting confirmations
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Proposal {
+    pub action: AdminAction,
+    pub proposer: AccountId,
+    pub confirmations: Vec<AccountId>,
+    pub expires_at: U64,
+}
+
+/// Fees paid into the treasury on top of storage costs, see [KeyValue::treasury]
+#[derive(
+    BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, Debug, PartialEq,
+)]
+#[serde(crate = "near_sdk::serde", default)]
+pub struct FeeSchedule {
+    /// Charged for every byte of storage a write adds
+    pub per_byte: U128,
+    /// Charged for every value written
+    pub write_fee: U128,
+    /// Charged for every [KeyValue::read_paid], on top of the entry's price
+    pub read_fee: U128,
+}
+
+impl Default for FeeSchedule {
     fn default() -> Self {
         Self {
-            pairs: UnorderedMap::new(b"r".to_vec()),
+            per_byte: U128(0),
+            write_fee: U128(0),
+            read_fee: U128(0),
         }
     }
 }
 
-// 3. Core Logic
-//
-// Add methods to KeyValue struct
-#[near_bindgen]
-impl KeyValue {
-    pub fn create_update(&mut self, k: String, v: String) {
-        env::log(b"created or update"); // log fn from near-sdk
-        self.pairs.insert(&k, &v); // insert into UnorderedMap
+/// Operation counters since deployment, see [KeyValue::stats]
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Stats {
+    pub creates: U64,
+    pub updates: U64,
+    pub deletes: U64,
+    // Distinct accounts that have created, updated or deleted a key
+    pub writers: U64,
+}
+
+/// Contract storage usage, see [KeyValue::storage_report]
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StorageReport {
+    pub total_bytes: U64,
+    pub total_cost: U128,
+    pub byte_cost: U128,
+    pub accounts: Vec<AccountStorage>,
+}
+
+/// Storage bytes an account pays for and their cost
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AccountStorage {
+    pub account_id: AccountId,
+    pub bytes: U64,
+    pub cost: U128,
+}
+
+/// NEP-145 storage balance of an account
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StorageBalance {
+    pub total: U128,
+    pub available: U128,
+}
+
+/// NEP-145 bounds on the storage balance of an account
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StorageBalanceBounds {
+    pub min: U128,
+    pub max: Option<U128>,
+}
+
+/// An [AdminAction] waiting for the timelock delay to pass
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct QueuedAction {
+    pub action: AdminAction,
+    pub queued_by: AccountId,
+    pub executable_at: U64,
+}
+
+/// Members of a scored set, indexed both by member and by (score, member)
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct SortedSet {
+    scores: LookupMap<String, i64>,
+    order: TreeMap<(i64, String), ()>,
+}
+
+impl SortedSet {
+    fn new(k: &str) -> Self {
+        let mut prefix = nested_prefix(SORTED_SET_MEMBERS_PREFIX, k);
+        prefix.push(b's');
+        let scores = LookupMap::new(prefix.clone());
+        prefix.pop();
+        prefix.push(b'o');
+        Self {
+            scores,
+            order: TreeMap::new(prefix),
+        }
     }
+}
 
-    pub fn read(&self, k: String) -> Option<String> {
-        env::log(b"read");
-        return self.pairs.get(&k); //get value from pairs from key: &k
+/// Maximum key and value byte lengths accepted on writes
+#[derive(
+    BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, Debug, PartialEq,
+)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SizeLimits {
+    pub max_key_len: u64,
+    pub max_value_len: u64,
+}
+
+impl Default for SizeLimits {
+    fn default() -> Self {
+        Self {
+            max_key_len: DEFAULT_MAX_KEY_LEN,
+            max_value_len: DEFAULT_MAX_VALUE_LEN,
+        }
     }
+}
 
-    pub fn delete(&mut self, k: String) {
-        env::log(b"delete");
-        self.pairs.remove(&k); // remove from pairs key: &k
+/// Normalizations applied to keys before they are written or looked up, so that keys
+/// differing only in these respects address the same entry. Prefixes and range bounds of the
+/// scanning methods are matched against the normalized keys as given.
+#[derive(
+    BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq,
+)]
+#[serde(crate = "near_sdk::serde", default)]
+pub struct KeyNormalization {
+    /// Strip leading and trailing whitespace
+    pub trim: bool,
+    /// Lowercase, by Unicode rules
+    pub lowercase: bool,
+    /// Unicode normalization form C, so precomposed and combining forms of a character match
+    pub nfc: bool,
+}
+
+impl KeyNormalization {
+    /// `k` with the enabled normalizations applied, NFC last so the result is always in NFC.
+    pub fn apply(&self, k: String) -> String {
+        let mut k = if self.trim { k.trim().to_string() } else { k };
+        if self.lowercase {
+            k = k.to_lowercase();
+        }
+        if self.nfc && !is_nfc(&k) {
+            k = k.nfc().collect();
+        }
+        k
     }
 }
 
-// 4. Tests
-//
-// Writing unit test are imprtant because smart contracts are often
-// immutable and sometimes responsible for managing sums of money.
-//
-// Writing good unit tests is a key component of secure and reliable
-// smart contract development.
-#[cfg(not(target_arch = "wasm32"))]
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use near_sdk::MockedBlockchain;
-    use near_sdk::{testing_env, VMContext};
+/// Contract settings passed to [KeyValue::new]; omitted fields take their defaults
+#[derive(
+    BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, Debug, PartialEq,
+)]
+#[serde(crate = "near_sdk::serde", default)]
+pub struct Config {
+    /// Number of previous values retained per key
+    pub history_depth: u64,
+    /// When set, deletes leave a tombstone restorable for this many nanoseconds
+    pub tombstone_retention: Option<U64>,
+    /// Maximum key and value sizes accepted on writes
+    pub size_limits: SizeLimits,
+    /// When set, a value may only be stored under one key at a time
+    pub unique_values: bool,
+    /// When set, only the owner and accounts added with [KeyValue::add_writer] may write
+    pub allowlist_only: bool,
+    /// When non-zero, sensitive operations must be queued and wait this many nanoseconds
+    pub timelock_delay: U64,
+    /// When set, the most storage bytes a single account may pay for
+    pub storage_quota: Option<U64>,
+    /// Fees paid into the treasury
+    pub fees: FeeSchedule,
+    /// When set, the most storage bytes a single caller's namespace may use
+    pub namespace_quota: Option<U64>,
+    /// When set, keys are indexed for the listing and scanning methods; without it writes
+    /// only touch the entry itself. Can only be changed while the store is empty.
+    pub listing: bool,
+    /// Normalizations applied to keys on every write and read. Can only be changed while no
+    /// pair, collection or namespace key is stored.
+    pub key_normalization: KeyNormalization,
+}
 
-    fn get_context(input: Vec<u8>, is_view: bool) -> VMContext {
-        VMContext {
-            current_account_id: "alice_near".to_string(),
-            signer_account_id: "bob_near".to_string(),
-            signer_account_pk: vec![0, 1, 2],
-            predecessor_account_id: "carol_near".to_string(),
-            input,
-            block_index: 0,
-            block_timestamp: 0,
-            account_balance: 0,
-            account_locked_balance: 0,
-            storage_usage: 0,
-            attached_deposit: 0,
-            prepaid_gas: 10u64.pow(18),
-            random_seed: vec![0, 1, 2],
-            is_view,
-            output_data_receivers: vec![],
-            epoch_height: 0,
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            history_depth: DEFAULT_HISTORY_DEPTH,
+            tombstone_retention: None,
+            size_limits: SizeLimits::default(),
+            unique_values: false,
+            allowlist_only: false,
+            timelock_delay: U64(0),
+            storage_quota: None,
+            fees: FeeSchedule::default(),
+            namespace_quota: None,
+            listing: true,
+            key_normalization: KeyNormalization::default(),
         }
     }
+}
 
-    // Test 1
-    //
-    /// Test for [create_update] and [read]
-    #[test]
-    fn create_read_pair() {
-        let context = get_context(vec![], false);
-        testing_env!(context);
-        let mut contract = KeyValue::default();
-        contract.create_update("first_key".to_string(), "hello".to_string());
+/// A stored value together with its type tag
+///
+/// Serialized to JSON as `{"type": "number", "value": "42"}`. The plain string API renders
+/// typed values as text, see [Value::text].
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(
+    crate = "near_sdk::serde",
+    tag = "type",
+    content = "value",
+    rename_all = "lowercase"
+)]
+pub enum Value {
+    String(String),
+    Number(I128),
+    Bool(bool),
+    // JSON document kept as validated text
+    Json(String),
+    // Arbitrary bytes, base64 encoded in JSON
+    Bytes(Base64VecU8),
+}
 
-        assert_eq!(
-            "hello".to_string(),
-            contract.read("first_key".to_string()).unwrap()
-        ); // expect("hello", "hello")
+impl Value {
+    /// Text form of the value, as returned by [read]
+    pub fn text(&self) -> Cow<'_, str> {
+        match self {
+            Value::String(s) | Value::Json(s) => Cow::Borrowed(s),
+            Value::Number(n) => Cow::Owned(n.0.to_string()),
+            Value::Bool(b) => Cow::Owned(b.to_string()),
+            Value::Bytes(bytes) => Cow::Owned(near_sdk::base64::encode(&bytes.0)),
+        }
     }
 
-    // Test 2
-    //
-    // Test for read non-existent pair
-    #[test]
-    fn read_nonexistent_pair() {
-        let context = get_context(vec![], true);
-        testing_env!(context);
-        let contract = KeyValue::default();
-        assert_eq!(None, contract.read("first_key".to_string()));
+    /// Size checked against [SizeLimits::max_value_len]: the payload length of [Value::Bytes],
+    /// the length of the text form otherwise
+    pub fn byte_len(&self) -> usize {
+        match self {
+            Value::Bytes(bytes) => bytes.0.len(),
+            other => other.text().len(),
+        }
+    }
+
+    /// Raw bytes of the value: the payload of [Value::Bytes], the UTF-8 text otherwise
+    pub fn into_bytes(self) -> Vec<u8> {
+        match self {
+            Value::Bytes(bytes) => bytes.0,
+            other => other.into_text().into_bytes(),
+        }
+    }
+
+    pub fn into_text(self) -> String {
+        match self {
+            Value::String(s) | Value::Json(s) => s,
+            other => other.text().into_owned(),
+        }
+    }
+}
+
+/// A value as kept in [KeyValue::pairs]: inline, or behind its own storage entry when longer
+/// than [LARGE_VALUE_THRESHOLD], so loading or evicting the map entry does not deserialize a
+/// large value unless it is asked for.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub enum StoredValue {
+    Inline(Value),
+    Large(LazyOption<Value>),
+}
+
+impl StoredValue {
+    /// Wraps `v`, writing it to its own storage entry if it is large
+    fn new(k: &str, v: &Value) -> Self {
+        if v.byte_len() > LARGE_VALUE_THRESHOLD {
+            StoredValue::Large(LazyOption::new(
+                nested_prefix(LARGE_VALUES_PREFIX, k),
+                Some(v),
+            ))
+        } else {
+            StoredValue::Inline(v.clone())
+        }
+    }
+
+    fn get(self) -> Value {
+        match self {
+            StoredValue::Inline(v) => v,
+            StoredValue::Large(lazy) => lazy.get().unwrap(),
+        }
+    }
+
+    /// The value, removing the separate storage entry of a large one
+    fn take(self) -> Value {
+        match self {
+            StoredValue::Inline(v) => v,
+            StoredValue::Large(mut lazy) => lazy.take().unwrap(),
+        }
+    }
+}
+
+/// Metadata stored alongside a key
+#[derive(BorshDeserialize, BorshSerialize, Clone, Debug, PartialEq)]
+pub struct EntryMeta {
+    pub created_at: u64,
+    pub created_height: u64,
+    // Account that owns the key: its first writer, or whoever it was transferred to
+    pub created_by: AccountId,
+    pub updated_at: u64,
+    pub updated_height: u64,
+    // Account that last wrote the value
+    pub updated_by: AccountId,
+    // Incremented on every write; the first write of a key is version 1
+    pub version: u64,
+    // Oldest version still retained in `history`
+    pub oldest_version: u64,
+    // Block timestamp (ns) from which the entry is treated as absent
+    pub expires_at: Option<u64>,
+    // Tags attached to the key, see [KeyValue::keys_by_tag]
+    pub tags: Vec<String>,
+    // Set while the entry is soft deleted
+    pub deleted_at: Option<u64>,
+    // Block timestamp (ns) until which a soft-deleted entry can be restored
+    pub restorable_until: Option<u64>,
+    // Account offered ownership by a two-step [KeyValue::transfer_key], until it accepts
+    pub pending_owner: Option<AccountId>,
+    // Accounts the key owner allowed to update the value, see [KeyValue::grant_write]
+    pub write_grants: Vec<AccountId>,
+    // Set when the key is left out of listings and only readable by its designated readers
+    pub unlisted: bool,
+    // Accounts the key owner allowed to read the value of an unlisted key
+    pub readers: Vec<AccountId>,
+    // When set, the value is only served through [KeyValue::read_paid] for this price (yocto)
+    pub price: Option<Balance>,
+    // Account billed for the entry's storage, and refunded as it shrinks or is removed, whoever
+    // makes the change
+    pub payer: AccountId,
+}
+
+impl EntryMeta {
+    /// Metadata for an entry `author` created in the current block, with storage paid by `payer`
+    fn new(author: AccountId, payer: AccountId) -> Self {
+        Self {
+            created_at: env::block_timestamp(),
+            created_height: env::block_index(),
+            created_by: author.clone(),
+            updated_at: env::block_timestamp(),
+            updated_height: env::block_index(),
+            updated_by: author,
+            version: 0,
+            oldest_version: 1,
+            expires_at: None,
+            tags: vec![],
+            deleted_at: None,
+            restorable_until: None,
+            pending_owner: None,
+            write_grants: vec![],
+            unlisted: false,
+            readers: vec![],
+            price: None,
+            payer,
+        }
+    }
+
+    /// False once the entry has expired or been soft deleted
+    fn is_live(&self) -> bool {
+        let expired = match self.expires_at {
+            Some(expires_at) => env::block_timestamp() >= expires_at,
+            None => false,
+        };
+        !expired && self.deleted_at.is_none()
+    }
+
+    /// Time from which [purge_expired] may physically remove the entry
+    fn sweep_at(&self) -> Option<u64> {
+        match (self.expires_at, self.restorable_until) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+}
+
+/// A stored value with all of its metadata, as replicated to the mirror contract
+#[derive(BorshDeserialize, BorshSerialize, Clone, Debug, PartialEq)]
+pub struct MirroredEntry {
+    pub value: Value,
+    pub meta: EntryMeta,
+}
+
+/// Change of one key in a batch replicated to the mirror contract: its entry before and after
+/// the call that made it, `None` while nothing was stored
+#[derive(BorshDeserialize, BorshSerialize, Debug, PartialEq)]
+pub struct MirrorChange {
+    pub key: String,
+    pub previous: Option<MirroredEntry>,
+    pub entry: Option<MirroredEntry>,
+}
+
+/// Value together with its metadata, returned by [read_with_meta]
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct EntryWithMeta {
+    pub value: String,
+    pub created_at: U64,
+    pub created_height: U64,
+    pub created_by: AccountId,
+    pub updated_at: U64,
+    pub updated_height: U64,
+    pub updated_by: AccountId,
+    pub expires_at: Option<U64>,
+}
+
+/// A previous value of a key, returned by [history]
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Revision {
+    pub version: U64,
+    pub value: Value,
+    pub updated_at: U64,
+    pub updated_by: AccountId,
+}
+
+/// One operation on a key, returned by [audit]. Values are recorded by their sha256 only.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AuditEntry {
+    // "create", "update" or "delete"
+    pub operation: String,
+    pub actor: AccountId,
+    pub timestamp: U64,
+    pub block_height: U64,
+    pub old_hash: Option<Base64VecU8>,
+    pub new_hash: Option<Base64VecU8>,
+}
+
+/// One mutation in the change feed, returned by [changes_since]
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Change {
+    pub seq: U64,
+    // "create", "update" or "delete"
+    pub operation: String,
+    pub key: String,
+    pub actor: AccountId,
+    pub timestamp: U64,
+    pub block_height: U64,
+}
+
+/// Net effect of a range of changes, returned by [diff]. Keys are sorted.
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StateDiff {
+    pub from_seq: U64,
+    pub to_seq: U64,
+    // Keys absent before the range and present after it
+    pub created: Vec<String>,
+    // Keys present on both ends whose value was written in between
+    pub updated: Vec<String>,
+    // Keys present before the range and absent after it
+    pub deleted: Vec<String>,
+}
+
+/// A live entry with the metadata a replacement contract needs, see [export_state]
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StateEntry {
+    pub key: String,
+    pub value: Value,
+    pub owner: AccountId,
+    pub tags: Vec<String>,
+    pub expires_at: Option<U64>,
+    pub write_grants: Vec<AccountId>,
+    pub unlisted: bool,
+    pub readers: Vec<AccountId>,
+    pub price: Option<U128>,
+}
+
+/// A listed entry with its metadata, returned by [export]
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ExportedEntry {
+    pub key: String,
+    pub value: Value,
+    pub created_at: U64,
+    pub created_height: U64,
+    pub created_by: AccountId,
+    pub updated_at: U64,
+    pub updated_height: U64,
+    pub updated_by: AccountId,
+    pub version: U64,
+    pub expires_at: Option<U64>,
+    pub tags: Vec<String>,
+}
+
+/// An entry written by [import] with preset metadata. Entries returned by [export] are
+/// accepted as they are; heights default to the current block and `updated_by` to the owner.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ImportEntry {
+    pub key: String,
+    pub value: Value,
+    // Account the key will belong to
+    pub created_by: AccountId,
+    pub created_at: U64,
+    pub created_height: Option<U64>,
+    pub updated_at: U64,
+    pub updated_height: Option<U64>,
+    pub updated_by: Option<AccountId>,
+    pub expires_at: Option<U64>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+/// Build the deployment runs, returned by [version]
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct BuildInfo {
+    pub version: String,
+    // Short git commit hash recorded at compile time, or "unknown"
+    pub commit: String,
+}
+
+/// NEP-330 source metadata, returned by [contract_source_metadata]
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ContractSourceMetadata {
+    pub version: Option<String>,
+    pub link: Option<String>,
+    pub standards: Vec<Standard>,
+}
+
+/// A NEP the contract implements, at the given version
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Standard {
+    pub standard: String,
+    pub version: String,
+}
+
+/// Latest round of an oracle feed, see [KeyValue::push_feed]
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FeedRound {
+    pub value: String,
+    // Strictly increasing per feed
+    pub round: U64,
+    // Registered oracle that pushed and signed this round
+    pub oracle: AccountId,
+    // Time (ns) the oracle observed the value, as it reported
+    pub observed_at: U64,
+    // Block time (ns) the round was pushed
+    pub submitted_at: U64,
+    // The oracle's signature of the round, see [KeyValue::push_feed]
+    pub signature: Base64VecU8,
+}
+
+/// A feed reading returned by [KeyValue::read_feed]
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FeedReading {
+    pub round: FeedRound,
+    // Whether the value was observed longer ago than the requested maximum age
+    pub stale: bool,
+}
+
+/// Work done by one [croncat_tick]
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TickSummary {
+    // Expired pairs and tombstones removed, see [purge_expired]
+    pub purged: u64,
+    // Expired multisig proposals dropped
+    pub proposals_dropped: u64,
+}
+
+/// Summary returned by [create_update_many]
+#[derive(BorshSerialize, Serialize, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct BatchWriteSummary {
+    pub created: u64,
+    pub updated: u64,
+}
+
+/// Result of [read_many_with_missing]: the pairs found and the keys that were not, each in
+/// request order
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ReadManyResult {
+    pub found: Vec<(String, String)>,
+    pub missing: Vec<String>,
+}
+
+/// Progress returned by [clear_all]; call again until `remaining` is zero
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ClearProgress {
+    pub removed: u64,
+    pub remaining: u64,
+}
+
+// 2. Core Logic
+//
+// Add methods to KeyValue struct
+#[near_bindgen]
+impl KeyValue {
+    /// Initializes the contract with `owner_id` as the account allowed to call administrative
+    /// methods. Must be called once, right after deployment.
+    #[init]
+    pub fn new(owner_id: ValidAccountId, config: Option<Config>) -> Self {
+        require!(
+            !env::state_exists(),
+            KvError::InvalidState("The contract is already initialized".to_string())
+        );
+        let config = config.unwrap_or_default();
+        assert_history_depth(config.history_depth);
+        VersionedState::write_version();
+        let mut state = Self::internal_new(owner_id.into(), config);
+        state.internal_flush();
+        state
+    }
+
+    /// Upgrades the stored state from any earlier layout to the current one; a no-op on
+    /// current state. Pairs of the original deployment are left in place for [migrate_batch]
+    /// to move over, owned by the contract account.
+    #[private]
+    #[init(ignore_state)]
+    pub fn migrate() -> Self {
+        let mut state = match VersionedState::read() {
+            VersionedState::Current(state) => *state,
+            VersionedState::V0(old) => {
+                let mut state = Self::internal_new(env::current_account_id(), Config::default());
+                env::log(format!("migrate: {} entries from layout v0", old.pairs.len()).as_bytes());
+                state.legacy_pairs = Some(old.pairs);
+                state
+            }
+        };
+        VersionedState::write_version();
+        state.internal_flush();
+        state
+    }
+
+    /// Moves up to `limit` pairs of the original layout left by [migrate] into the current
+    /// one and returns how many are left, so it can be called until it returns 0. Pairs are
+    /// taken from the end of the old map, whose remaining length is the cursor to resume from,
+    /// and written without the size limits and access checks of regular writes.
+    #[private]
+    pub fn migrate_batch(&mut self, limit: u64) -> U64 {
+        let mut legacy = match self.legacy_pairs.take() {
+            Some(legacy) => legacy,
+            None => return U64(0),
+        };
+        let mut moved = 0;
+        while moved < limit && !legacy.is_empty() {
+            let k = legacy.keys_as_vector().get(legacy.len() - 1).unwrap();
+            let v = legacy.remove(&k).unwrap();
+            self.internal_put(&k, &Value::String(v));
+            moved += 1;
+        }
+        let left = legacy.len();
+        env::log(format!("migrate batch: {} moved, {} left", moved, left).as_bytes());
+        if left > 0 {
+            self.legacy_pairs = Some(legacy);
+        }
+        self.internal_flush();
+        U64(left)
+    }
+
+    /// Owner only: a page of live entries, unlisted and premium ones included, in the format
+    /// [import_state] accepts. This is a change method because view calls cannot tell who is
+    /// reading.
+    pub fn export_state(&mut self, from_index: u64, limit: u64) -> Vec<StateEntry> {
+        self.assert_owner();
+        self.assert_listing();
+        self.internal_key_page(from_index, limit.min(MAX_PAGE_SIZE))
+            .into_iter()
+            .filter_map(|k| {
+                let meta = self.internal_live_meta(&k)?;
+                Some(StateEntry {
+                    value: self.internal_value(&k).unwrap(),
+                    key: k,
+                    owner: meta.created_by,
+                    tags: meta.tags,
+                    expires_at: meta.expires_at.map(U64),
+                    write_grants: meta.write_grants,
+                    unlisted: meta.unlisted,
+                    readers: meta.readers,
+                    price: meta.price.map(U128),
+                })
+            })
+            .collect()
+    }
+
+    /// Owner only: writes entries exported by [export_state], overwriting existing keys, and
+    /// returns how many were imported. Imported values start a fresh history.
+    #[payable]
+    pub fn import_state(&mut self, entries: Vec<StateEntry>) -> u64 {
+        let initial_storage = env::storage_usage();
+        self.assert_owner();
+        let entries: Vec<_> = entries
+            .into_iter()
+            .map(|mut entry| {
+                entry.key = self.normalize_key(entry.key);
+                entry
+            })
+            .collect();
+        assert_batch_size(entries.len());
+        env::log(format!("import: {} entries", entries.len()).as_bytes());
+        for entry in entries.iter() {
+            self.internal_insert(&entry.key, &entry.value);
+            self.internal_set_expiry(&entry.key, entry.expires_at.map(|at| at.0));
+            let mut meta = self.meta.get(&entry.key).unwrap();
+            meta.created_by = entry.owner.clone();
+            meta.write_grants = entry.write_grants.clone();
+            meta.unlisted = entry.unlisted;
+            meta.readers = entry.readers.clone();
+            meta.price = entry.price.map(|price| price.0);
+            self.internal_update_meta(&entry.key, &meta);
+            for tag in entry.tags.iter() {
+                self.internal_add_tag(&entry.key, tag);
+            }
+        }
+        self.internal_settle_storage(initial_storage);
+        entries.len() as u64
+    }
+
+    /// Owner only: writes `entries` with the owner and timestamps they carry, overwriting
+    /// existing keys, to seed the store or move entries over from another deployment. Returns
+    /// how many were imported.
+    #[payable]
+    pub fn import(&mut self, entries: Vec<ImportEntry>) -> u64 {
+        let initial_storage = env::storage_usage();
+        self.assert_owner();
+        let entries: Vec<_> = entries
+            .into_iter()
+            .map(|mut entry| {
+                entry.key = self.normalize_key(entry.key);
+                entry
+            })
+            .collect();
+        assert_batch_size(entries.len());
+        env::log(format!("import: {} entries", entries.len()).as_bytes());
+        for entry in entries.iter() {
+            require!(
+                entry.created_at.0 <= entry.updated_at.0,
+                KvError::InvalidArgument(format!(
+                    "Entry '{}' is updated before it is created",
+                    entry.key
+                ))
+            );
+            self.internal_insert(&entry.key, &entry.value);
+            self.internal_set_expiry(&entry.key, entry.expires_at.map(|at| at.0));
+            let mut meta = self.meta.get(&entry.key).unwrap();
+            meta.created_by = entry.created_by.clone();
+            meta.created_at = entry.created_at.0;
+            meta.created_height = entry.created_height.map_or(env::block_index(), |at| at.0);
+            meta.updated_at = entry.updated_at.0;
+            meta.updated_height = entry.updated_height.map_or(env::block_index(), |at| at.0);
+            meta.updated_by = entry.updated_by.clone().unwrap_or(entry.created_by.clone());
+            self.internal_update_meta(&entry.key, &meta);
+            for tag in entry.tags.iter() {
+                self.internal_add_tag(&entry.key, tag);
+            }
+        }
+        self.internal_settle_storage(initial_storage);
+        entries.len() as u64
+    }
+
+    pub fn version(&self) -> BuildInfo {
+        BuildInfo {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            commit: env!("GIT_COMMIT").to_string(),
+        }
+    }
+
+    /// NEP-330: crate version, source repository and the standards this contract implements.
+    pub fn contract_source_metadata(&self) -> ContractSourceMetadata {
+        let standard = |standard: &str, version: &str| Standard {
+            standard: standard.to_string(),
+            version: version.to_string(),
+        };
+        ContractSourceMetadata {
+            version: Some(env!("CARGO_PKG_VERSION").to_string()),
+            link: Some(env!("CARGO_PKG_REPOSITORY").to_string()),
+            standards: vec![
+                standard("nep141", "1.0.0"),
+                standard("nep145", "1.0.0"),
+                standard("nep148", "1.0.0"),
+                standard("nep171", "1.0.0"),
+                standard("nep177", "2.0.0"),
+                standard("nep181", "1.0.0"),
+                standard("nep297", "1.0.0"),
+                standard("nep330", "1.1.0"),
+            ],
+        }
+    }
+
+    pub fn owner(&self) -> AccountId {
+        self.owner_id.clone()
+    }
+
+    pub fn config(&self) -> Config {
+        self.config
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Blocks all mutating methods until [unpause]; reads and administrative methods stay
+    /// available.
+    pub fn pause(&mut self) {
+        self.assert_owner();
+        env::log(format!("pause: paused by {}", env::predecessor_account_id()).as_bytes());
+        self.paused = true;
+    }
+
+    pub fn unpause(&mut self) {
+        self.assert_owner();
+        env::log(format!("pause: unpaused by {}", env::predecessor_account_id()).as_bytes());
+        self.paused = false;
+    }
+
+    pub fn in_maintenance(&self) -> bool {
+        self.maintenance
+    }
+
+    /// Switches read-only maintenance mode. Unlike [pause], it rejects every data write
+    /// including the owner's, while reads and administrative methods stay available.
+    pub fn set_maintenance(&mut self, enabled: bool) {
+        self.assert_owner();
+        env::log(format!("maintenance: {}", if enabled { "on" } else { "off" }).as_bytes());
+        self.maintenance = enabled;
+    }
+
+    pub fn paused_methods(&self) -> u64 {
+        self.paused_methods
+    }
+
+    /// Disables the method groups whose PAUSE_* flags are set in `mask`, re-enabling all others.
+    pub fn set_paused_methods(&mut self, mask: u64) {
+        self.assert_owner();
+        env::log(format!("pause: paused methods set to {:#b}", mask).as_bytes());
+        self.paused_methods = mask;
+    }
+
+    pub fn pending_owner(&self) -> Option<AccountId> {
+        self.pending_owner_id.clone()
+    }
+
+    /// Offers ownership of the contract to `new_owner`, which takes over once it calls
+    /// [accept_ownership]. A new proposal replaces the previous one.
+    pub fn propose_owner(&mut self, new_owner: ValidAccountId) {
+        self.assert_only_owner();
+        let new_owner: AccountId = new_owner.into();
+        emit_kv_control_event(
+            "kv_owner_proposed",
+            near_sdk::serde_json::json!({
+                "owner_id": self.owner_id,
+                "new_owner_id": new_owner,
+            }),
+        );
+        self.pending_owner_id = Some(new_owner);
+    }
+
+    pub fn cancel_owner_proposal(&mut self) {
+        self.assert_only_owner();
+        if let Some(pending) = self.pending_owner_id.take() {
+            emit_kv_control_event(
+                "kv_owner_proposal_cancelled",
+                near_sdk::serde_json::json!({
+                    "owner_id": self.owner_id,
+                    "new_owner_id": pending,
+                }),
+            );
+        }
+    }
+
+    /// Makes the caller the owner, if it is the proposed one.
+    pub fn accept_ownership(&mut self) {
+        let caller = env::predecessor_account_id();
+        require!(
+            self.pending_owner_id.as_ref() == Some(&caller),
+            KvError::Unauthorized("Only the proposed owner can accept ownership".to_string())
+        );
+        emit_kv_control_event(
+            "kv_owner_transferred",
+            near_sdk::serde_json::json!({
+                "old_owner_id": self.owner_id,
+                "new_owner_id": caller,
+            }),
+        );
+        self.owner_id = caller;
+        self.pending_owner_id = None;
+    }
+
+    /// Returns the value previously stored under `k`, if any. The attached deposit must cover
+    /// the storage the write adds at the current storage price, otherwise the call panics and
+    /// the deposit is refunded.
+    #[payable]
+    pub fn create_update(&mut self, k: String, v: String) -> Option<String> {
+        let k = self.normalize_key(k);
+        let initial_storage = env::storage_usage();
+        let previous = self.internal_insert(&k, &Value::String(v));
+        self.internal_set_expiry(&k, None);
+        self.internal_settle_storage(initial_storage);
+        previous.map(Value::into_text)
+    }
+
+    /// Typed counterpart of [create_update]; JSON values must be well-formed.
+    #[payable]
+    pub fn create_update_typed(&mut self, k: String, v: Value) -> Option<Value> {
+        let k = self.normalize_key(k);
+        let initial_storage = env::storage_usage();
+        if let Value::Json(text) = &v {
+            require!(
+                near_sdk::serde_json::from_str::<near_sdk::serde_json::Value>(text).is_ok(),
+                KvError::InvalidArgument(format!("Value of '{}' is not valid JSON", k))
+            );
+        }
+        let previous = self.internal_insert(&k, &v);
+        self.internal_set_expiry(&k, None);
+        self.internal_settle_storage(initial_storage);
+        previous
+    }
+
+    /// Like [create_update], but the entry is treated as absent `ttl_ns` nanoseconds from now.
+    #[payable]
+    pub fn create_update_with_ttl(&mut self, k: String, v: String, ttl_ns: U64) -> Option<String> {
+        let k = self.normalize_key(k);
+        let initial_storage = env::storage_usage();
+        let expires_at = env::block_timestamp()
+            .checked_add(ttl_ns.0)
+            .unwrap_or_else(|| KvError::Overflow("TTL overflow".to_string()).panic());
+        let previous = self.internal_insert(&k, &Value::String(v));
+        self.internal_set_expiry(&k, Some(expires_at));
+        self.internal_settle_storage(initial_storage);
+        previous.map(Value::into_text)
+    }
+
+    /// Like [read], but a key missing here is looked up with `read_through` on the configured
+    /// upstream contract, so stores can be layered. Forwarding needs a cross-contract call, so
+    /// this is a change method, to be called as a transaction.
+    pub fn read_through(&mut self, k: String) -> PromiseOrValue<Option<String>> {
+        let k = self.normalize_key(k);
+        if let Some(value) = self.internal_get_listed(&k) {
+            return PromiseOrValue::Value(Some(value.into_text()));
+        }
+        match &self.upstream_id {
+            Some(upstream_id) => Promise::new(upstream_id.clone())
+                .function_call(
+                    b"read_through".to_vec(),
+                    near_sdk::serde_json::json!({ "k": k })
+                        .to_string()
+                        .into_bytes(),
+                    0,
+                    UPSTREAM_GAS,
+                )
+                .then(
+                    Promise::new(env::current_account_id()).function_call(
+                        b"on_upstream_read".to_vec(),
+                        near_sdk::serde_json::json!({ "k": k })
+                            .to_string()
+                            .into_bytes(),
+                        0,
+                        UPSTREAM_CALLBACK_GAS,
+                    ),
+                )
+                .into(),
+            None => PromiseOrValue::Value(None),
+        }
+    }
+
+    /// Callback of [read_through]: the upstream answer, or `None` if the lookup failed.
+    #[private]
+    pub fn on_upstream_read(&self, k: String) -> Option<String> {
+        match env::promise_result(0) {
+            PromiseResult::Successful(answer) => {
+                near_sdk::serde_json::from_slice(&answer).unwrap_or(None)
+            }
+            _ => {
+                env::log(format!("upstream: lookup of '{}' failed", k).as_bytes());
+                None
+            }
+        }
+    }
+
+    pub fn read(&self, k: String) -> Option<String> {
+        let k = self.normalize_key(k);
+        env::log(b"read");
+        self.internal_get_listed(&k).map(Value::into_text) //get value from pairs from key: &k
+    }
+
+    /// Like [read], but returns `default` instead of None when `k` is missing.
+    pub fn read_or_default(&self, k: String, default: String) -> String {
+        let k = self.normalize_key(k);
+        env::log(b"read or default");
+        self.internal_get_listed(&k)
+            .map_or(default, Value::into_text)
+    }
+
+    /// Binary counterpart of [create_update], returning the previous value as bytes.
+    #[payable]
+    pub fn create_update_bytes(&mut self, k: String, v: Base64VecU8) -> Option<Base64VecU8> {
+        let k = self.normalize_key(k);
+        let initial_storage = env::storage_usage();
+        let previous = self.internal_insert(&k, &Value::Bytes(v));
+        self.internal_set_expiry(&k, None);
+        self.internal_settle_storage(initial_storage);
+        previous.map(|value| Base64VecU8(value.into_bytes()))
+    }
+
+    /// Value under `k` as bytes; non-binary values are returned as their UTF-8 text.
+    pub fn read_bytes(&self, k: String) -> Option<Base64VecU8> {
+        let k = self.normalize_key(k);
+        env::log(b"read bytes");
+        self.internal_get_listed(&k)
+            .map(|value| Base64VecU8(value.into_bytes()))
+    }
+
+    /// Returns the payload of `k` as the raw call result, with no JSON encoding: the bytes of
+    /// binary values, the UTF-8 text of the others. Panics if `k` is absent, unlisted or
+    /// premium, so that an empty result always means an empty value; see [contains_key].
+    pub fn read_raw(&self, k: String) {
+        let k = self.normalize_key(k);
+        env::log(b"read raw");
+        let value = self
+            .internal_get_listed(&k)
+            .unwrap_or_else(|| KvError::KeyNotFound(k.to_string()).panic());
+        env::value_return(&value.into_bytes());
+    }
+
+    /// Like [create_update], additionally attaching `tags` to the key.
+    #[payable]
+    pub fn create_update_with_tags(
+        &mut self,
+        k: String,
+        v: String,
+        tags: Vec<String>,
+    ) -> Option<String> {
+        let k = self.normalize_key(k);
+        let initial_storage = env::storage_usage();
+        let previous = self.internal_insert(&k, &Value::String(v));
+        self.internal_set_expiry(&k, None);
+        for tag in tags.iter() {
+            self.internal_add_tag(&k, tag);
+        }
+        self.internal_settle_storage(initial_storage);
+        previous.map(Value::into_text)
+    }
+
+    pub fn read_typed(&self, k: String) -> Option<Value> {
+        let k = self.normalize_key(k);
+        env::log(b"read typed");
+        self.internal_get_listed(&k)
+    }
+
+    pub fn read_with_meta(&self, k: String) -> Option<EntryWithMeta> {
+        let k = self.normalize_key(k);
+        env::log(b"read with meta");
+        let value = self.internal_get_listed(&k)?.into_text();
+        let meta = self.meta.get(&k).unwrap();
+        Some(EntryWithMeta {
+            value,
+            created_at: U64(meta.created_at),
+            created_height: U64(meta.created_height),
+            created_by: meta.created_by,
+            updated_at: U64(meta.updated_at),
+            updated_height: U64(meta.updated_height),
+            updated_by: meta.updated_by,
+            expires_at: meta.expires_at.map(U64),
+        })
+    }
+
+    #[payable]
+    pub fn delete(&mut self, k: String) {
+        let k = self.normalize_key(k);
+        let initial_storage = env::storage_usage();
+        self.internal_delete(&k); // remove from pairs key: &k
+        self.internal_settle_storage(initial_storage);
+    }
+
+    /// Inserts a new pair, panicking if the key already exists.
+    #[payable]
+    pub fn create_only(&mut self, k: String, v: String) {
+        let k = self.normalize_key(k);
+        let initial_storage = env::storage_usage();
+        require!(
+            !self.internal_contains_key(&k),
+            KvError::KeyExists(k.to_string())
+        );
+        self.internal_insert(&k, &Value::String(v));
+        self.internal_settle_storage(initial_storage);
+    }
+
+    /// Updates an existing pair and returns its previous value, panicking if the key is missing.
+    #[payable]
+    pub fn update_only(&mut self, k: String, v: String) -> String {
+        let k = self.normalize_key(k);
+        let initial_storage = env::storage_usage();
+        require!(
+            self.internal_contains_key(&k),
+            KvError::KeyNotFound(k.to_string())
+        );
+        let previous = self.internal_insert(&k, &Value::String(v)).unwrap();
+        self.internal_settle_storage(initial_storage);
+        previous.into_text()
+    }
+
+    /// Writes `new` only if the key currently holds `expected`; returns whether the swap happened.
+    #[payable]
+    pub fn update_if(&mut self, k: String, expected: String, new: String) -> bool {
+        let k = self.normalize_key(k);
+        let initial_storage = env::storage_usage();
+        self.assert_can_update(&k);
+        let swapped = match self.internal_read(&k) {
+            Some(current) if current.text() == expected => {
+                self.internal_insert(&k, &Value::String(new));
+                true
+            }
+            _ => false,
+        };
+        self.internal_settle_storage(initial_storage);
+        swapped
+    }
+
+    /// Adds `by` to the counter stored under `k` (missing keys start at zero).
+    #[payable]
+    pub fn increment(&mut self, k: String, by: U128) -> U128 {
+        let k = self.normalize_key(k);
+        let initial_storage = env::storage_usage();
+        self.assert_can_update(&k);
+        let current = self.internal_counter(&k);
+        let next = current
+            .checked_add(by.0)
+            .unwrap_or_else(|| KvError::Overflow("Counter overflow".to_string()).panic());
+        self.internal_insert(&k, &Value::String(next.to_string()));
+        self.internal_settle_storage(initial_storage);
+        U128(next)
+    }
+
+    /// Subtracts `by` from the counter stored under `k` (missing keys start at zero).
+    #[payable]
+    pub fn decrement(&mut self, k: String, by: U128) -> U128 {
+        let k = self.normalize_key(k);
+        let initial_storage = env::storage_usage();
+        self.assert_can_update(&k);
+        let current = self.internal_counter(&k);
+        let next = current
+            .checked_sub(by.0)
+            .unwrap_or_else(|| KvError::Overflow("Counter underflow".to_string()).panic());
+        self.internal_insert(&k, &Value::String(next.to_string()));
+        self.internal_settle_storage(initial_storage);
+        U128(next)
+    }
+
+    /// Appends `suffix` to the value under `k` (creating it if absent) and returns the new byte
+    /// length, panicking if it would exceed `max_len` bytes.
+    #[payable]
+    pub fn append(&mut self, k: String, suffix: String, max_len: u64) -> u64 {
+        let k = self.normalize_key(k);
+        let initial_storage = env::storage_usage();
+        self.assert_can_update(&k);
+        let mut value = self
+            .internal_read(&k)
+            .map(Value::into_text)
+            .unwrap_or_default();
+        value.push_str(&suffix);
+        require!(
+            value.len() as u64 <= max_len,
+            KvError::ValueTooLarge(format!(
+                "Appended value would be {} bytes, exceeding max_len of {}",
+                value.len(),
+                max_len
+            ))
+        );
+        let len = value.len() as u64;
+        self.internal_insert(&k, &Value::String(value));
+        self.internal_settle_storage(initial_storage);
+        len
+    }
+
+    #[payable]
+    pub fn create_update_many(&mut self, pairs: Vec<(String, String)>) -> BatchWriteSummary {
+        let pairs: Vec<(String, String)> = pairs
+            .into_iter()
+            .map(|(k, v)| (self.normalize_key(k), v))
+            .collect();
+        let initial_storage = env::storage_usage();
+        assert_batch_size(pairs.len());
+        let mut summary = BatchWriteSummary {
+            created: 0,
+            updated: 0,
+        };
+        for (k, v) in pairs.iter() {
+            let previous = self.internal_insert(k, &Value::String(v.clone()));
+            self.internal_set_expiry(k, None);
+            match previous {
+                Some(_) => summary.updated += 1,
+                None => summary.created += 1,
+            }
+        }
+        self.internal_settle_storage(initial_storage);
+        summary
+    }
+
+    pub fn read_many(&self, keys: Vec<String>) -> Vec<Option<String>> {
+        let keys: Vec<String> = keys.into_iter().map(|k| self.normalize_key(k)).collect();
+        assert_batch_size(keys.len());
+        env::log(b"read many");
+        keys.iter()
+            .map(|k| self.internal_get_listed(k).map(Value::into_text))
+            .collect()
+    }
+
+    /// Like [read_many], but splits the result into the pairs found and the missing keys.
+    pub fn read_many_with_missing(&self, keys: Vec<String>) -> ReadManyResult {
+        let keys: Vec<String> = keys.into_iter().map(|k| self.normalize_key(k)).collect();
+        assert_batch_size(keys.len());
+        env::log(b"read many with missing");
+        let mut result = ReadManyResult {
+            found: vec![],
+            missing: vec![],
+        };
+        for k in keys {
+            match self.internal_get_listed(&k) {
+                Some(value) => result.found.push((k, value.into_text())),
+                None => result.missing.push(k),
+            }
+        }
+        result
+    }
+
+    #[payable]
+    pub fn delete_many(&mut self, keys: Vec<String>) -> u64 {
+        let keys: Vec<String> = keys.into_iter().map(|k| self.normalize_key(k)).collect();
+        let initial_storage = env::storage_usage();
+        assert_batch_size(keys.len());
+        let deleted = keys
+            .iter()
+            .filter(|k| self.internal_delete(k).is_some())
+            .count() as u64;
+        self.internal_settle_storage(initial_storage);
+        deleted
+    }
+
+    /// [create_update] taking and returning Borsh instead of JSON, which is smaller and
+    /// cheaper to decode for programmatic writers.
+    #[payable]
+    #[result_serializer(borsh)]
+    pub fn create_update_borsh(
+        &mut self,
+        #[serializer(borsh)] k: String,
+        #[serializer(borsh)] v: String,
+    ) -> Option<String> {
+        self.create_update(k, v)
+    }
+
+    /// Borsh counterpart of [create_update_many].
+    #[payable]
+    #[result_serializer(borsh)]
+    pub fn create_update_many_borsh(
+        &mut self,
+        #[serializer(borsh)] pairs: Vec<(String, String)>,
+    ) -> BatchWriteSummary {
+        self.create_update_many(pairs)
+    }
+
+    /// Borsh counterpart of [read].
+    #[result_serializer(borsh)]
+    pub fn read_borsh(&self, #[serializer(borsh)] k: String) -> Option<String> {
+        self.read(k)
+    }
+
+    /// Borsh counterpart of [read_many].
+    #[result_serializer(borsh)]
+    pub fn read_many_borsh(&self, #[serializer(borsh)] keys: Vec<String>) -> Vec<Option<String>> {
+        self.read_many(keys)
+    }
+
+    /// A page of keys, listed shard by shard as [KEY_LIST_SHARDS] describes; pages starting
+    /// where the previous one ended cover each key exactly once.
+    pub fn keys(&self, from_index: u64, limit: u64) -> Vec<String> {
+        self.assert_listing();
+        self.internal_key_page(from_index, limit.min(MAX_PAGE_SIZE))
+            .into_iter()
+            .filter(|k| self.is_listed(k))
+            .collect()
+    }
+
+    pub fn entries(&self, from_index: u64, limit: u64) -> Vec<(String, String)> {
+        self.keys(from_index, limit)
+            .into_iter()
+            .map(|k| {
+                let v = self.internal_value(&k).unwrap().into_text();
+                (k, v)
+            })
+            .collect()
+    }
+
+    /// Number of stored pairs, including expired or soft-deleted ones not purged yet.
+    pub fn len(&self) -> u64 {
+        self.pair_count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pair_count == 0
+    }
+
+    pub fn contains_key(&self, k: String) -> bool {
+        let k = self.normalize_key(k);
+        self.internal_contains_key(&k)
+    }
+
+    pub fn keys_with_prefix(&self, prefix: String, from_index: u64, limit: u64) -> Vec<String> {
+        self.assert_listing();
+        self.indexes
+            .get()
+            .index
+            .range((Bound::Included(prefix.clone()), Bound::Unbounded))
+            .map(|(k, _)| k)
+            .take_while(|k| k.starts_with(&prefix))
+            .filter(|k| self.is_listed(k))
+            .skip(from_index as usize)
+            .take(limit.min(MAX_PAGE_SIZE) as usize)
+            .collect()
+    }
+
+    pub fn entries_with_prefix(
+        &self,
+        prefix: String,
+        from_index: u64,
+        limit: u64,
+    ) -> Vec<(String, String)> {
+        self.keys_with_prefix(prefix, from_index, limit)
+            .into_iter()
+            .map(|k| {
+                let v = self.internal_value(&k).unwrap().into_text();
+                (k, v)
+            })
+            .collect()
+    }
+
+    /// Keys in lexicographic order strictly after `from_key` (from the start when `None`), so
+    /// the last key of a page is the cursor for the next one.
+    pub fn keys_sorted(&self, from_key: Option<String>, limit: u64) -> Vec<String> {
+        self.assert_listing();
+        let keys: Box<dyn Iterator<Item = (String, ())>> = match from_key {
+            Some(from_key) => Box::new(self.indexes.get().index.iter_from(from_key)),
+            None => Box::new(self.indexes.get().index.iter()),
+        };
+        keys.map(|(k, _)| k)
+            .filter(|k| self.is_listed(k))
+            .take(limit.min(MAX_PAGE_SIZE) as usize)
+            .collect()
+    }
+
+    /// Page of listed entries with their values and metadata, in lexicographic key order so
+    /// backups taken page by page are deterministic. Like [keys_sorted], the last key of a page
+    /// is the `from_key` of the next one. Unlisted and premium entries are left out, see
+    /// [export_state].
+    pub fn export(&self, from_key: Option<String>, limit: u64) -> Vec<ExportedEntry> {
+        self.keys_sorted(from_key, limit)
+            .into_iter()
+            .map(|k| {
+                let meta = self.meta.get(&k).unwrap();
+                ExportedEntry {
+                    value: self.internal_value(&k).unwrap(),
+                    key: k,
+                    created_at: U64(meta.created_at),
+                    created_height: U64(meta.created_height),
+                    created_by: meta.created_by,
+                    updated_at: U64(meta.updated_at),
+                    updated_height: U64(meta.updated_height),
+                    updated_by: meta.updated_by,
+                    version: U64(meta.version),
+                    expires_at: meta.expires_at.map(U64),
+                    tags: meta.tags,
+                }
+            })
+            .collect()
+    }
+
+    /// Attaches `tag` to an existing key; returns false if it was already attached.
+    #[payable]
+    pub fn add_tag(&mut self, k: String, tag: String) -> bool {
+        let k = self.normalize_key(k);
+        let initial_storage = env::storage_usage();
+        self.assert_enabled(PAUSE_TAGS, "tags");
+        let meta = self
+            .internal_live_meta(&k)
+            .unwrap_or_else(|| KvError::KeyNotFound(k.to_string()).panic());
+        self.assert_owner_or_key_owner(&meta, &k, "tag");
+        env::log(b"add tag");
+        let added = self.internal_add_tag(&k, &tag);
+        self.internal_settle_storage(initial_storage);
+        added
+    }
+
+    /// Detaches `tag` from `k`; returns false if it was not attached.
+    #[payable]
+    pub fn remove_tag(&mut self, k: String, tag: String) -> bool {
+        let k = self.normalize_key(k);
+        let initial_storage = env::storage_usage();
+        self.assert_enabled(PAUSE_TAGS, "tags");
+        env::log(b"remove tag");
+        let mut meta = match self.internal_live_meta(&k) {
+            Some(meta) => meta,
+            None => return false,
+        };
+        self.assert_owner_or_key_owner(&meta, &k, "tag");
+        let count = meta.tags.len();
+        meta.tags.retain(|attached| *attached != tag);
+        if meta.tags.len() == count {
+            return false;
+        }
+        self.internal_update_meta(&k, &meta);
+        self.internal_untag(&k, &tag);
+        self.internal_settle_storage(initial_storage);
+        true
+    }
+
+    pub fn tags(&self, k: String) -> Vec<String> {
+        let k = self.normalize_key(k);
+        self.internal_live_meta(&k)
+            .map(|meta| meta.tags)
+            .unwrap_or_default()
+    }
+
+    pub fn keys_by_tag(&self, tag: String, from_index: u64, limit: u64) -> Vec<String> {
+        match self.indexes.get().tag_index.get(&tag) {
+            Some(keys) => keys
+                .iter()
+                .filter(|k| self.is_listed(k))
+                .skip(from_index as usize)
+                .take(limit.min(MAX_PAGE_SIZE) as usize)
+                .collect(),
+            None => vec![],
+        }
+    }
+
+    /// Keys whose value renders as the text `v`, see [Value::text].
+    pub fn keys_with_value(&self, v: String, from_index: u64, limit: u64) -> Vec<String> {
+        match self
+            .indexes
+            .get()
+            .value_index
+            .get(&env::sha256(v.as_bytes()))
+        {
+            Some(keys) => keys
+                .iter()
+                .filter(|k| self.is_listed(k))
+                .skip(from_index as usize)
+                .take(limit.min(MAX_PAGE_SIZE) as usize)
+                .collect(),
+            None => vec![],
+        }
+    }
+
+    /// Returns up to `limit` pairs with `from_key <= key < to_key` in lexicographic order.
+    pub fn range(&self, from_key: String, to_key: String, limit: u64) -> Vec<(String, String)> {
+        require!(
+            from_key <= to_key,
+            KvError::InvalidArgument("from_key must not be greater than to_key".to_string())
+        );
+        self.assert_listing();
+        self.indexes
+            .get()
+            .index
+            .range((Bound::Included(from_key), Bound::Excluded(to_key)))
+            .filter(|(k, _)| self.is_listed(k))
+            .take(limit.min(MAX_PAGE_SIZE) as usize)
+            .map(|(k, _)| {
+                let v = self.internal_value(&k).unwrap().into_text();
+                (k, v)
+            })
+            .collect()
+    }
+
+    /// Physically removes up to `limit` expired pairs and tombstones past their retention window
+    /// (capped at [MAX_BATCH_SIZE]), oldest first, and returns how many were removed.
+    pub fn purge_expired(&mut self, limit: u64) -> u64 {
+        self.assert_not_paused();
+        self.assert_enabled(PAUSE_PURGE, "purge");
+        env::log(b"purge expired");
+        let now = env::block_timestamp();
+        let expired: Vec<String> = self
+            .indexes
+            .get()
+            .expiry_index
+            .iter()
+            .take_while(|((expires_at, _), _)| *expires_at <= now)
+            .take(limit.min(MAX_BATCH_SIZE as u64) as usize)
+            .map(|((_, k), _)| k)
+            .collect();
+        for k in expired.iter() {
+            self.internal_remove(k);
+        }
+        self.internal_settle_payers();
+        expired.len() as u64
+    }
+
+    pub fn croncat_manager(&self) -> Option<AccountId> {
+        self.croncat_manager_id.clone()
+    }
+
+    /// Lets the Croncat manager `manager_id` run [croncat_tick] on a schedule, or revokes it
+    /// when `None`.
+    pub fn set_croncat_manager(&mut self, manager_id: Option<ValidAccountId>) {
+        self.assert_sensitive();
+        env::log(b"set croncat manager");
+        self.croncat_manager_id = manager_id.map(Into::into);
+    }
+
+    /// Scheduled maintenance, callable only by the configured Croncat manager: purges expired
+    /// entries as [purge_expired] does, unless purging is paused, and drops expired multisig
+    /// proposals, each up to [MAX_BATCH_SIZE] per tick.
+    pub fn croncat_tick(&mut self) -> TickSummary {
+        require!(
+            self.croncat_manager_id.as_ref() == Some(&env::predecessor_account_id()),
+            KvError::Unauthorized("Only the Croncat manager can call this method".to_string())
+        );
+        let purge_paused =
+            self.paused || self.maintenance || self.paused_methods & PAUSE_PURGE != 0;
+        let purged = if purge_paused {
+            0
+        } else {
+            self.purge_expired(MAX_BATCH_SIZE as u64)
+        };
+        // Proposals expire in id order, so the sweep stops at the first one still open
+        let mut proposals_dropped = 0;
+        let now = env::block_timestamp();
+        while self.proposal_sweep_from < self.next_proposal_id
+            && proposals_dropped < MAX_BATCH_SIZE as u64
+        {
+            let id = self.proposal_sweep_from;
+            match self.proposals.get(&id) {
+                Some(proposal) if proposal.expires_at.0 > now => break,
+                Some(_) => {
+                    self.proposals.remove(&id);
+                    proposals_dropped += 1;
+                }
+                None => {}
+            }
+            self.proposal_sweep_from += 1;
+        }
+        env::log(
+            format!(
+                "croncat: purged {}, dropped {} proposals",
+                purged, proposals_dropped
+            )
+            .as_bytes(),
+        );
+        self.internal_flush();
+        TickSummary {
+            purged,
+            proposals_dropped,
+        }
+    }
+
+    /// Previous values of `k`, newest first, skipping `from_index` of them.
+    pub fn history(&self, k: String, from_index: u64, limit: u64) -> Vec<Revision> {
+        let k = self.normalize_key(k);
+        if self.internal_get_listed(&k).is_none() {
+            return vec![];
+        }
+        let meta = self.meta.get(&k).unwrap();
+        (meta.oldest_version..meta.version)
+            .rev()
+            .skip(from_index as usize)
+            .take(limit.min(MAX_PAGE_SIZE) as usize)
+            .filter_map(|version| self.history.get(&(k.clone(), version)))
+            .collect()
+    }
+
+    /// Operations recorded on `k`, oldest first, starting at the `from_index`-th one. Entries
+    /// remain after the key is deleted.
+    pub fn audit(&self, k: String, from_index: u64, limit: u64) -> Vec<AuditEntry> {
+        let k = self.normalize_key(k);
+        let len = self.audit_log.get().audit_lengths.get(&k).unwrap_or(0);
+        (from_index..len)
+            .take(limit.min(MAX_PAGE_SIZE) as usize)
+            .filter_map(|seq| self.audit_log.get().audit.get(&(k.clone(), seq)))
+            .collect()
+    }
+
+    /// Up to `limit` changes with a sequence number greater than `seq`, in order. Starting
+    /// from 0 replays every mutation since deployment.
+    pub fn changes_since(&self, seq: U64, limit: u64) -> Vec<Change> {
+        (seq.0..self.audit_log.get().changes.len())
+            .take(limit.min(MAX_PAGE_SIZE) as usize)
+            .filter_map(|index| self.audit_log.get().changes.get(index))
+            .collect()
+    }
+
+    /// Compacts the changes with sequence numbers in `(from_seq, to_seq]` into the net set of
+    /// created, updated and deleted keys; a key created and deleted within the range is left
+    /// out. `to_seq` is capped at [last_seq].
+    pub fn diff(&self, from_seq: U64, to_seq: U64) -> StateDiff {
+        let to_seq = to_seq.0.min(self.audit_log.get().changes.len());
+        let from_seq = from_seq.0.min(to_seq);
+        require!(
+            to_seq - from_seq <= MAX_DIFF_SPAN,
+            KvError::LimitExceeded(format!("Diff spans more than {} changes", MAX_DIFF_SPAN))
+        );
+        // First and last operation on each key within the range
+        let mut ops: BTreeMap<String, (String, String)> = BTreeMap::new();
+        for change in (from_seq..to_seq).filter_map(|index| self.audit_log.get().changes.get(index))
+        {
+            ops.entry(change.key)
+                .and_modify(|(_, last)| *last = change.operation.clone())
+                .or_insert_with(|| (change.operation.clone(), change.operation));
+        }
+        let mut diff = StateDiff {
+            from_seq: U64(from_seq),
+            to_seq: U64(to_seq),
+            created: vec![],
+            updated: vec![],
+            deleted: vec![],
+        };
+        for (k, (first, last)) in ops {
+            match (first.as_str(), last.as_str()) {
+                ("create", "delete") => {}
+                ("create", _) => diff.created.push(k),
+                (_, "delete") => diff.deleted.push(k),
+                _ => diff.updated.push(k),
+            }
+        }
+        diff
+    }
+
+    /// Sequence number of the latest change, 0 before the first one.
+    pub fn last_seq(&self) -> U64 {
+        U64(self.audit_log.get().changes.len())
+    }
+
+    pub fn stats(&self) -> Stats {
+        self.audit_log.get().stats
+    }
+
+    /// Restores the value `k` had at `version` as a new version, which is returned. Only the
+    /// owner or the account that created the key may roll it back.
+    #[payable]
+    pub fn rollback(&mut self, k: String, version: U64) -> U64 {
+        let k = self.normalize_key(k);
+        let initial_storage = env::storage_usage();
+        let meta = self
+            .internal_live_meta(&k)
+            .unwrap_or_else(|| KvError::KeyNotFound(k.to_string()).panic());
+        self.assert_owner_or_key_owner(&meta, &k, "roll back");
+        let revision = self
+            .history
+            .get(&(k.clone(), version.0))
+            .unwrap_or_else(|| {
+                KvError::NotFound(format!(
+                    "Version {} of key '{}' is not available",
+                    version.0, k
+                ))
+                .panic()
+            });
+        self.internal_insert(&k, &revision.value);
+        let restored = meta.version + 1;
+        emit_kv_control_event(
+            "kv_rollback",
+            near_sdk::serde_json::json!({
+                "key": k,
+                "actor": self.internal_actor(),
+                "from_version": version,
+                "version": U64(restored),
+            }),
+        );
+        self.internal_settle_storage(initial_storage);
+        U64(restored)
+    }
+
+    /// Moves the value of `old_key` together with its metadata and history to `new_key`,
+    /// panicking if `new_key` already exists.
+    #[payable]
+    pub fn rename(&mut self, old_key: String, new_key: String) {
+        let old_key = self.normalize_key(old_key);
+        let new_key = self.normalize_key(new_key);
+        let initial_storage = env::storage_usage();
+        self.assert_enabled(PAUSE_RENAME, "rename");
+        require!(
+            old_key != new_key,
+            KvError::InvalidArgument("Keys must differ".to_string())
+        );
+        let meta = self
+            .internal_live_meta(&old_key)
+            .unwrap_or_else(|| KvError::KeyNotFound(old_key.to_string()).panic());
+        self.assert_owner_or_key_owner(&meta, &old_key, "rename");
+        // A hidden destination may still be restored or hold history, so only its own owner
+        // may rename over it
+        if let Some(existing) = self.meta.get(&new_key) {
+            require!(
+                !existing.is_live() && existing.created_by == self.internal_actor(),
+                KvError::KeyExists(new_key.to_string())
+            );
+        }
+        let value = self.internal_value(&old_key).unwrap();
+        self.assert_not_gated(&new_key);
+        self.assert_can_insert(&new_key, &value, Some(&old_key));
+        // The caller's own hidden destination only lingers until purged, so drop it right away
+        self.internal_remove(&new_key);
+
+        self.internal_snapshot(&old_key);
+        self.internal_take_value(&old_key);
+        self.internal_unindex_value(&old_key, &value);
+        self.internal_index_value(&new_key, &value);
+        self.internal_unlist_key(&old_key);
+        self.meta.remove(&old_key);
+        if let Some(at) = meta.sweep_at() {
+            self.indexes
+                .get_mut()
+                .expiry_index
+                .remove(&(at, old_key.clone()));
+        }
+        for version in meta.oldest_version..meta.version {
+            if let Some(revision) = self.history.remove(&(old_key.clone(), version)) {
+                self.history.insert(&(new_key.clone(), version), &revision);
+            }
+        }
+        for tag in meta.tags.iter() {
+            self.internal_untag(&old_key, tag);
+            self.internal_tag(&new_key, tag);
+        }
+
+        self.pairs
+            .insert(&new_key, &StoredValue::new(&new_key, &value));
+        self.internal_list_key(&new_key);
+        self.internal_write_meta(&new_key, None, &meta);
+        let reveal = !meta.unlisted && meta.price.is_none();
+        self.internal_record("kv_delete", &old_key, Some(&value), None, reveal);
+        self.internal_record("kv_create", &new_key, None, Some(&value), reveal);
+        self.internal_settle_storage(initial_storage);
+    }
+
+    /// Writes the value of `src` under `dst` as a regular write by the caller, returning the
+    /// value `dst` held before. Panics if `dst` exists and `overwrite` is false.
+    #[payable]
+    pub fn copy(&mut self, src: String, dst: String, overwrite: bool) -> Option<String> {
+        let src = self.normalize_key(src);
+        let dst = self.normalize_key(dst);
+        let initial_storage = env::storage_usage();
+        let value = self
+            .internal_read(&src)
+            .unwrap_or_else(|| KvError::KeyNotFound(src.to_string()).panic());
+        require!(
+            overwrite || self.internal_live_meta(&dst).is_none(),
+            KvError::KeyExists(dst.to_string())
+        );
+        let previous = self.internal_insert(&dst, &value);
+        self.internal_set_expiry(&dst, None);
+        self.internal_settle_storage(initial_storage);
+        previous.map(Value::into_text)
+    }
+
+    /// Restores a soft-deleted key within its retention window. Only the owner or the account
+    /// that created the key may restore it.
+    #[payable]
+    pub fn undelete(&mut self, k: String) {
+        let k = self.normalize_key(k);
+        let initial_storage = env::storage_usage();
+        self.assert_enabled(PAUSE_RESTORE, "restore");
+        let mut meta = self
+            .meta
+            .get(&k)
+            .filter(|meta| meta.deleted_at.is_some())
+            .unwrap_or_else(|| KvError::NotFound(format!("Key '{}' is not deleted", k)).panic());
+        self.assert_owner_or_key_owner(&meta, &k, "restore");
+        require!(
+            env::block_timestamp() < meta.restorable_until.unwrap(),
+            KvError::Expired(format!("Retention window of '{}' has passed", k))
+        );
+        let sweep_before = meta.sweep_at();
+        meta.deleted_at = None;
+        meta.restorable_until = None;
+        self.internal_write_meta(&k, sweep_before, &meta);
+        let reveal = !meta.unlisted && meta.price.is_none();
+        self.internal_record(
+            "kv_create",
+            &k,
+            None,
+            self.internal_value(&k).as_ref(),
+            reveal,
+        );
+        self.internal_settle_storage(initial_storage);
+    }
+
+    /// Account currently owning `k`, if it exists.
+    pub fn key_owner(&self, k: String) -> Option<AccountId> {
+        let k = self.normalize_key(k);
+        self.internal_live_meta(&k).map(|meta| meta.created_by)
+    }
+
+    /// Hands ownership of `k` to `new_owner`. With `two_step`, `new_owner` only becomes the owner
+    /// once it calls [accept_key]; until then the current owner keeps control and may offer the
+    /// key to someone else.
+    #[payable]
+    pub fn transfer_key(&mut self, k: String, new_owner: ValidAccountId, two_step: bool) {
+        let k = self.normalize_key(k);
+        let initial_storage = env::storage_usage();
+        self.assert_enabled(PAUSE_ACCESS, "access control");
+        self.assert_can_write();
+        let mut meta = self
+            .internal_live_meta(&k)
+            .unwrap_or_else(|| KvError::KeyNotFound(k.to_string()).panic());
+        self.assert_key_owner(&meta, &k, "transfer");
+        let new_owner: AccountId = new_owner.into();
+        if two_step {
+            env::log(format!("transfer: key '{}' offered to {}", k, new_owner).as_bytes());
+            meta.pending_owner = Some(new_owner);
+        } else {
+            env::log(
+                format!(
+                    "transfer: key '{}' from {} to {}",
+                    k, meta.created_by, new_owner
+                )
+                .as_bytes(),
+            );
+            meta.created_by = new_owner;
+            meta.pending_owner = None;
+        }
+        self.internal_update_meta(&k, &meta);
+        self.internal_settle_storage(initial_storage);
+    }
+
+    /// NEP-171: transfers the token of key `token_id`, and with it the ownership of the key, to
+    /// `receiver_id`. Write grants of the previous owner are dropped. Approvals are not
+    /// supported, so only the owner may transfer.
+    #[payable]
+    pub fn nft_transfer(
+        &mut self,
+        receiver_id: ValidAccountId,
+        token_id: String,
+        approval_id: Option<u64>,
+        memo: Option<String>,
+    ) {
+        let token_id = self.normalize_key(token_id);
+        let initial_storage = env::storage_usage();
+        near_sdk::assert_one_yocto();
+        require!(
+            approval_id.is_none(),
+            KvError::Unsupported("Approvals are not supported".to_string())
+        );
+        let sender_id = env::predecessor_account_id();
+        self.internal_transfer_token(&sender_id, receiver_id.as_ref(), &token_id, memo);
+        self.internal_settle_storage(initial_storage);
+    }
+
+    /// NEP-171: transfers the token of key `token_id` to `receiver_id` and calls its
+    /// `nft_on_transfer`, which may hand it back by returning true.
+    #[payable]
+    pub fn nft_transfer_call(
+        &mut self,
+        receiver_id: ValidAccountId,
+        token_id: String,
+        approval_id: Option<u64>,
+        memo: Option<String>,
+        msg: String,
+    ) -> Promise {
+        let token_id = self.normalize_key(token_id);
+        let initial_storage = env::storage_usage();
+        near_sdk::assert_one_yocto();
+        require!(
+            approval_id.is_none(),
+            KvError::Unsupported("Approvals are not supported".to_string())
+        );
+        let sender_id = env::predecessor_account_id();
+        self.internal_transfer_token(&sender_id, receiver_id.as_ref(), &token_id, memo);
+        self.internal_settle_storage(initial_storage);
+        Promise::new(receiver_id.to_string())
+            .function_call(
+                b"nft_on_transfer".to_vec(),
+                near_sdk::serde_json::json!({
+                    "sender_id": sender_id,
+                    "previous_owner_id": sender_id,
+                    "token_id": token_id,
+                    "msg": msg,
+                })
+                .to_string()
+                .into_bytes(),
+                0,
+                NFT_TRANSFER_CALL_GAS,
+            )
+            .then(
+                Promise::new(env::current_account_id()).function_call(
+                    b"nft_resolve_transfer".to_vec(),
+                    near_sdk::serde_json::json!({
+                        "previous_owner_id": sender_id,
+                        "receiver_id": receiver_id,
+                        "token_id": token_id,
+                    })
+                    .to_string()
+                    .into_bytes(),
+                    0,
+                    NFT_RESOLVE_GAS,
+                ),
+            )
+    }
+
+    /// NEP-171: callback of [nft_transfer_call]. Returns the token to `previous_owner_id` if
+    /// the receiver asked for it or failed, as long as the receiver still owns it; returns
+    /// whether the transfer stands.
+    #[private]
+    pub fn nft_resolve_transfer(
+        &mut self,
+        previous_owner_id: AccountId,
+        receiver_id: AccountId,
+        token_id: String,
+    ) -> bool {
+        let give_back = match env::promise_result(0) {
+            PromiseResult::Successful(value) => {
+                near_sdk::serde_json::from_slice::<bool>(&value).unwrap_or(true)
+            }
+            _ => true,
+        };
+        if !give_back {
+            return true;
+        }
+        let still_owned = self
+            .internal_live_meta(&token_id)
+            .is_some_and(|meta| meta.created_by == receiver_id);
+        if !still_owned {
+            return true;
+        }
+        self.internal_transfer_token(&receiver_id, &previous_owner_id, &token_id, None);
+        self.internal_flush();
+        false
+    }
+
+    /// NEP-171: the token of key `token_id`, if the key exists and is listed.
+    pub fn nft_token(&self, token_id: String) -> Option<Token> {
+        let token_id = self.normalize_key(token_id);
+        if !self.is_listed(&token_id) {
+            return None;
+        }
+        let meta = self.internal_live_meta(&token_id)?;
+        Some(Token {
+            owner_id: meta.created_by,
+            metadata: TokenMetadata {
+                title: token_id.clone(),
+                issued_at: U64(meta.created_at),
+                updated_at: U64(meta.updated_at),
+            },
+            token_id,
+        })
+    }
+
+    /// NEP-181: a page of the tokens of listed keys.
+    pub fn nft_tokens(&self, from_index: Option<U128>, limit: Option<u64>) -> Vec<Token> {
+        let from_index = from_index.map_or(0, |index| index.0 as u64);
+        self.keys(from_index, limit.unwrap_or(MAX_PAGE_SIZE))
+            .into_iter()
+            .filter_map(|k| self.nft_token(k))
+            .collect()
+    }
+
+    pub fn nft_metadata(&self) -> NftContractMetadata {
+        NftContractMetadata {
+            spec: "nft-1.0.0".to_string(),
+            name: "Key Ownership".to_string(),
+            symbol: "KEY".to_string(),
+            icon: None,
+            base_uri: None,
+            reference: None,
+            reference_hash: None,
+        }
+    }
+
+    /// Registers the ed25519 `public_key` the caller signs relayed writes and oracle rounds
+    /// with, or removes it when `None`. Implicit accounts can sign with their own key without
+    /// one.
+    #[payable]
+    pub fn set_relay_key(&mut self, public_key: Option<Base64VecU8>) {
+        self.assert_not_paused();
+        self.assert_not_blacklisted();
+        let initial_storage = env::storage_usage();
+        let caller = env::predecessor_account_id();
+        match public_key {
+            Some(key) => {
+                require!(
+                    32 == key.0.len(),
+                    KvError::InvalidArgument(
+                        "Relay keys are 32 byte ed25519 public keys".to_string()
+                    )
+                );
+                self.relay_keys.insert(&caller, &key.0);
+            }
+            None => {
+                self.relay_keys.remove(&caller);
+            }
+        }
+        self.internal_settle_storage(initial_storage);
+    }
+
+    /// Key `account_id` signs relayed writes with, if registered.
+    pub fn relay_key(&self, account_id: ValidAccountId) -> Option<Base64VecU8> {
+        self.relay_keys.get(account_id.as_ref()).map(Base64VecU8)
+    }
+
+    /// Last nonce relayed for `account_id`; the next relayed write must use a greater one.
+    pub fn relay_nonce(&self, account_id: ValidAccountId) -> U64 {
+        U64(self.relay_nonces.get(account_id.as_ref()).unwrap_or(0))
+    }
+
+    /// Applies `write` as if its author had called [create_update], after checking
+    /// `signature` against the author's relay key, the nonce and the expiry. The relayer pays
+    /// for the gas and, as with [create_update], for the storage.
+    #[payable]
+    pub fn relay_write(&mut self, write: RelayedWrite, signature: Base64VecU8) -> Option<String> {
+        self.assert_not_blacklisted();
+        require!(
+            write.receiver_id == env::current_account_id(),
+            KvError::InvalidArgument(format!("Relayed write is meant for {}", write.receiver_id))
+        );
+        require!(
+            env::block_timestamp() < write.expires_at.0,
+            KvError::Expired("Relayed write has expired".to_string())
+        );
+        let last_nonce = self.relay_nonces.get(&write.author).unwrap_or(0);
+        require!(
+            write.nonce.0 > last_nonce,
+            KvError::Unauthorized(format!(
+                "Nonce {} of {} was already used",
+                write.nonce.0, write.author
+            ))
+        );
+        let message = env::sha256(&write.try_to_vec().unwrap());
+        self.assert_signed_by(&write.author, &message, &signature);
+
+        // The author signed the key as typed, so it is only normalized once the signature holds
+        let k = self.normalize_key(write.k);
+        let initial_storage = env::storage_usage();
+        self.relay_nonces.insert(&write.author, &write.nonce.0);
+        self.acting_author = Some(write.author);
+        let previous = self.internal_insert(&k, &Value::String(write.v));
+        self.internal_set_expiry(&k, None);
+        self.acting_author = None;
+        self.internal_settle_storage(initial_storage);
+        previous.map(Value::into_text)
+    }
+
+    /// Completes a two-step transfer of `k` to the caller.
+    #[payable]
+    pub fn accept_key(&mut self, k: String) {
+        let k = self.normalize_key(k);
+        let initial_storage = env::storage_usage();
+        self.assert_enabled(PAUSE_ACCESS, "access control");
+        self.assert_can_write();
+        let mut meta = self
+            .internal_live_meta(&k)
+            .unwrap_or_else(|| KvError::KeyNotFound(k.to_string()).panic());
+        let caller = env::predecessor_account_id();
+        require!(
+            meta.pending_owner.as_ref() == Some(&caller),
+            KvError::Unauthorized(format!("No transfer of '{}' is pending for {}", k, caller))
+        );
+        env::log(
+            format!(
+                "transfer: key '{}' from {} to {}",
+                k, meta.created_by, caller
+            )
+            .as_bytes(),
+        );
+        meta.created_by = caller;
+        meta.pending_owner = None;
+        self.internal_update_meta(&k, &meta);
+        self.internal_settle_storage(initial_storage);
+    }
+
+    /// Lets `account_id` update the value of `k` without owning it; returns false if it was
+    /// already granted. Only the key owner (or the contract owner) may grant.
+    #[payable]
+    pub fn grant_write(&mut self, k: String, account_id: ValidAccountId) -> bool {
+        let k = self.normalize_key(k);
+        let initial_storage = env::storage_usage();
+        self.assert_enabled(PAUSE_ACCESS, "access control");
+        self.assert_can_write();
+        let mut meta = self
+            .internal_live_meta(&k)
+            .unwrap_or_else(|| KvError::KeyNotFound(k.to_string()).panic());
+        self.assert_key_owner(&meta, &k, "grant write on");
+        let account_id: AccountId = account_id.into();
+        if meta.write_grants.contains(&account_id) {
+            return false;
+        }
+        require!(
+            meta.write_grants.len() < MAX_WRITE_GRANTS_PER_KEY,
+            KvError::LimitExceeded(format!(
+                "Key '{}' already has the maximum of {} write grants",
+                k, MAX_WRITE_GRANTS_PER_KEY
+            ))
+        );
+        env::log(format!("grant write on '{}' to {}", k, account_id).as_bytes());
+        meta.write_grants.push(account_id);
+        self.internal_update_meta(&k, &meta);
+        self.internal_settle_storage(initial_storage);
+        true
+    }
+
+    /// Withdraws a write grant on `k`; returns false if `account_id` was not granted.
+    #[payable]
+    pub fn revoke_write(&mut self, k: String, account_id: ValidAccountId) -> bool {
+        let k = self.normalize_key(k);
+        let initial_storage = env::storage_usage();
+        self.assert_enabled(PAUSE_ACCESS, "access control");
+        self.assert_can_write();
+        let mut meta = match self.internal_live_meta(&k) {
+            Some(meta) => meta,
+            None => return false,
+        };
+        self.assert_key_owner(&meta, &k, "revoke write on");
+        let count = meta.write_grants.len();
+        meta.write_grants
+            .retain(|granted| granted != account_id.as_ref());
+        if meta.write_grants.len() == count {
+            return false;
+        }
+        env::log(format!("revoke write on '{}' from {}", k, account_id.as_ref()).as_bytes());
+        self.internal_update_meta(&k, &meta);
+        self.internal_settle_storage(initial_storage);
+        true
+    }
+
+    pub fn write_grants(&self, k: String) -> Vec<AccountId> {
+        let k = self.normalize_key(k);
+        self.internal_live_meta(&k)
+            .map(|meta| meta.write_grants)
+            .unwrap_or_default()
+    }
+
+    /// Value of `k` for its owner and designated readers, including unlisted keys. This is a
+    /// change method because view calls cannot tell who is reading.
+    pub fn read_private(&mut self, k: String) -> Option<String> {
+        let k = self.normalize_key(k);
+        env::log(b"read private");
+        self.internal_read(&k).map(Value::into_text)
+    }
+
+    /// Serves a premium entry: the attached deposit must cover its price, which goes to the key
+    /// owner, plus the read fee, which goes to the treasury, and the rest is refunded. Unlisted
+    /// entries still require read access.
+    #[payable]
+    pub fn read_paid(&mut self, k: String) -> String {
+        let k = self.normalize_key(k);
+        self.assert_not_paused();
+        self.assert_not_blacklisted();
+        let meta = self
+            .internal_live_meta(&k)
+            .unwrap_or_else(|| KvError::KeyNotFound(k.to_string()).panic());
+        let price = meta.price.unwrap_or(0);
+        let read_fee = self.config.fees.read_fee.0;
+        let attached = env::attached_deposit();
+        let cost = price
+            .checked_add(read_fee)
+            .unwrap_or_else(|| KvError::Overflow("Read price overflow".to_string()).panic());
+        require!(
+            attached >= cost,
+            KvError::InsufficientDeposit(format!(
+                "Attached deposit of {} is less than the price of {} plus the read fee of {}",
+                attached, price, read_fee
+            ))
+        );
+        let caller = env::predecessor_account_id();
+        require!(
+            !meta.unlisted
+                || caller == meta.created_by
+                || caller == self.owner_id
+                || meta.readers.contains(&caller),
+            KvError::Unauthorized(format!("Only designated readers can read '{}'", k))
+        );
+        env::log(format!("read paid: '{}' for {}", k, price).as_bytes());
+        if price > 0 {
+            Promise::new(meta.created_by).transfer(price);
+        }
+        self.treasury += read_fee;
+        self.internal_refund(attached - cost);
+        self.internal_value(&k).unwrap().into_text()
+    }
+
+    /// Price of the premium entry `k`, if it is one.
+    pub fn price(&self, k: String) -> Option<U128> {
+        let k = self.normalize_key(k);
+        self.internal_live_meta(&k)
+            .and_then(|meta| meta.price)
+            .map(U128)
+    }
+
+    /// Makes `k` a premium entry served only through [read_paid] for `price`, or a regular
+    /// entry again when `None`.
+    #[payable]
+    pub fn set_premium(&mut self, k: String, price: Option<U128>) {
+        let k = self.normalize_key(k);
+        let initial_storage = env::storage_usage();
+        self.assert_can_write();
+        let mut meta = self
+            .internal_live_meta(&k)
+            .unwrap_or_else(|| KvError::KeyNotFound(k.to_string()).panic());
+        self.assert_key_owner(&meta, &k, "price");
+        env::log(b"set premium");
+        meta.price = price.map(|price| price.0);
+        self.internal_update_meta(&k, &meta);
+        self.internal_settle_storage(initial_storage);
+    }
+
+    /// Leaves `k` out of listings and plain reads, or lists it again.
+    #[payable]
+    pub fn set_unlisted(&mut self, k: String, unlisted: bool) {
+        let k = self.normalize_key(k);
+        let initial_storage = env::storage_usage();
+        self.assert_enabled(PAUSE_ACCESS, "access control");
+        self.assert_can_write();
+        let mut meta = self
+            .internal_live_meta(&k)
+            .unwrap_or_else(|| KvError::KeyNotFound(k.to_string()).panic());
+        self.assert_key_owner(&meta, &k, "unlist");
+        env::log(b"set unlisted");
+        meta.unlisted = unlisted;
+        self.internal_update_meta(&k, &meta);
+        self.internal_settle_storage(initial_storage);
+    }
+
+    /// Lets `account_id` read `k` with [read_private]; returns false if it already could.
+    #[payable]
+    pub fn add_reader(&mut self, k: String, account_id: ValidAccountId) -> bool {
+        let k = self.normalize_key(k);
+        let initial_storage = env::storage_usage();
+        self.assert_enabled(PAUSE_ACCESS, "access control");
+        self.assert_can_write();
+        let mut meta = self
+            .internal_live_meta(&k)
+            .unwrap_or_else(|| KvError::KeyNotFound(k.to_string()).panic());
+        self.assert_key_owner(&meta, &k, "add a reader to");
+        let account_id: AccountId = account_id.into();
+        if meta.readers.contains(&account_id) {
+            return false;
+        }
+        require!(
+            meta.readers.len() < MAX_READERS_PER_KEY,
+            KvError::LimitExceeded(format!(
+                "Key '{}' already has the maximum of {} readers",
+                k, MAX_READERS_PER_KEY
+            ))
+        );
+        env::log(format!("add reader of '{}': {}", k, account_id).as_bytes());
+        meta.readers.push(account_id);
+        self.internal_update_meta(&k, &meta);
+        self.internal_settle_storage(initial_storage);
+        true
+    }
+
+    /// Withdraws read access to `k`; returns false if `account_id` was not a reader.
+    #[payable]
+    pub fn remove_reader(&mut self, k: String, account_id: ValidAccountId) -> bool {
+        let k = self.normalize_key(k);
+        let initial_storage = env::storage_usage();
+        self.assert_enabled(PAUSE_ACCESS, "access control");
+        self.assert_can_write();
+        let mut meta = match self.internal_live_meta(&k) {
+            Some(meta) => meta,
+            None => return false,
+        };
+        self.assert_key_owner(&meta, &k, "remove a reader from");
+        let count = meta.readers.len();
+        meta.readers.retain(|reader| reader != account_id.as_ref());
+        if meta.readers.len() == count {
+            return false;
+        }
+        env::log(format!("remove reader of '{}': {}", k, account_id.as_ref()).as_bytes());
+        self.internal_update_meta(&k, &meta);
+        self.internal_settle_storage(initial_storage);
+        true
+    }
+
+    /// Storage bytes `account_id` has paid for with attached deposits.
+    pub fn storage_paid(&self, account_id: ValidAccountId) -> U64 {
+        U64(self.storage_paid.get(account_id.as_ref()).unwrap_or(0))
+    }
+
+    /// NEP-145: registers `account_id` (the caller by default) and adds the attached deposit to
+    /// its storage balance, which later writes draw from when their attached deposit falls
+    /// short. With `registration_only`, anything beyond the minimum balance is refunded.
+    #[payable]
+    pub fn storage_deposit(
+        &mut self,
+        account_id: Option<ValidAccountId>,
+        registration_only: Option<bool>,
+    ) -> StorageBalance {
+        self.assert_not_paused();
+        self.assert_enabled(PAUSE_STORAGE, "storage");
+        let account_id: AccountId = account_id
+            .map(|account_id| account_id.into())
+            .unwrap_or_else(env::predecessor_account_id);
+        let mut amount = env::attached_deposit();
+        let available = match self.storage_deposits.get(&account_id) {
+            Some(available) => {
+                if registration_only.unwrap_or(false) {
+                    self.internal_refund(amount);
+                    amount = 0;
+                }
+                available
+            }
+            None => {
+                let min = self.storage_balance_bounds().min.0;
+                require!(
+                    amount >= min,
+                    KvError::InsufficientDeposit(format!(
+                        "The attached deposit is less than the minimum storage balance of {}",
+                        min
+                    ))
+                );
+                amount -= min;
+                if registration_only.unwrap_or(false) {
+                    self.internal_refund(amount);
+                    amount = 0;
+                }
+                0
+            }
+        };
+        env::log(format!("storage deposit of {} for {}", amount, account_id).as_bytes());
+        self.storage_deposits
+            .insert(&account_id, &(available + amount));
+        self.internal_storage_balance(&account_id).unwrap()
+    }
+
+    /// Mints storage credits for the attached deposit, one per yoctoNEAR, to the caller, who
+    /// must be registered with [storage_deposit]. Writes spend credits once their attached
+    /// deposit falls short, before the NEP-145 balance. Returns the caller's new balance.
+    #[payable]
+    pub fn buy_credits(&mut self) -> U128 {
+        self.assert_not_paused();
+        self.assert_enabled(PAUSE_STORAGE, "storage");
+        let buyer = env::predecessor_account_id();
+        require!(
+            self.storage_deposits.contains_key(&buyer),
+            KvError::NotRegistered(buyer.to_string())
+        );
+        let amount = env::attached_deposit();
+        require!(
+            amount > 0,
+            KvError::InsufficientDeposit("Attach a deposit to buy credits".to_string())
+        );
+        let balance = self.credits.get(&buyer).unwrap_or(0) + amount;
+        self.credits.insert(&buyer, &balance);
+        self.credits_supply += amount;
+        emit_event(
+            "nep141",
+            "1.0.0",
+            "ft_mint",
+            near_sdk::serde_json::json!({ "owner_id": buyer, "amount": U128(amount) }),
+        );
+        U128(balance)
+    }
+
+    /// NEP-141: transfers `amount` credits to the registered `receiver_id`.
+    #[payable]
+    pub fn ft_transfer(&mut self, receiver_id: ValidAccountId, amount: U128, memo: Option<String>) {
+        near_sdk::assert_one_yocto();
+        self.assert_not_paused();
+        self.assert_enabled(PAUSE_STORAGE, "storage");
+        self.assert_not_blacklisted();
+        let sender_id = env::predecessor_account_id();
+        self.internal_transfer_credits(&sender_id, receiver_id.as_ref(), amount.0, memo);
+    }
+
+    /// NEP-141: transfers `amount` credits to `receiver_id` and calls its `ft_on_transfer`,
+    /// taking back whatever it reports as unused.
+    #[payable]
+    pub fn ft_transfer_call(
+        &mut self,
+        receiver_id: ValidAccountId,
+        amount: U128,
+        memo: Option<String>,
+        msg: String,
+    ) -> Promise {
+        near_sdk::assert_one_yocto();
+        self.assert_not_paused();
+        self.assert_enabled(PAUSE_STORAGE, "storage");
+        self.assert_not_blacklisted();
+        let sender_id = env::predecessor_account_id();
+        self.internal_transfer_credits(&sender_id, receiver_id.as_ref(), amount.0, memo);
+        Promise::new(receiver_id.to_string())
+            .function_call(
+                b"ft_on_transfer".to_vec(),
+                near_sdk::serde_json::json!({
+                    "sender_id": sender_id,
+                    "amount": amount,
+                    "msg": msg,
+                })
+                .to_string()
+                .into_bytes(),
+                0,
+                FT_TRANSFER_CALL_GAS,
+            )
+            .then(
+                Promise::new(env::current_account_id()).function_call(
+                    b"ft_resolve_transfer".to_vec(),
+                    near_sdk::serde_json::json!({
+                        "sender_id": sender_id,
+                        "receiver_id": receiver_id,
+                        "amount": amount,
+                    })
+                    .to_string()
+                    .into_bytes(),
+                    0,
+                    FT_RESOLVE_GAS,
+                ),
+            )
+    }
+
+    /// NEP-141: callback of [ft_transfer_call] returning the credits that stayed with the
+    /// receiver; the unused amount it reported, or all of them if the call failed, goes back to
+    /// the sender as far as the receiver still holds them.
+    #[private]
+    pub fn ft_resolve_transfer(
+        &mut self,
+        sender_id: AccountId,
+        receiver_id: AccountId,
+        amount: U128,
+    ) -> U128 {
+        let unused = match env::promise_result(0) {
+            PromiseResult::Successful(value) => near_sdk::serde_json::from_slice::<U128>(&value)
+                .map(|unused| unused.0.min(amount.0))
+                .unwrap_or(amount.0),
+            _ => amount.0,
+        };
+        let refund = unused.min(self.credits.get(&receiver_id).unwrap_or(0));
+        if refund > 0 {
+            self.internal_transfer_credits(&receiver_id, &sender_id, refund, None);
+        }
+        U128(amount.0 - refund)
+    }
+
+    pub fn ft_total_supply(&self) -> U128 {
+        U128(self.credits_supply)
+    }
+
+    pub fn ft_balance_of(&self, account_id: ValidAccountId) -> U128 {
+        U128(self.credits.get(account_id.as_ref()).unwrap_or(0))
+    }
+
+    pub fn ft_metadata(&self) -> FungibleTokenMetadata {
+        FungibleTokenMetadata {
+            spec: "ft-1.0.0".to_string(),
+            name: "Storage Credit".to_string(),
+            symbol: "CREDIT".to_string(),
+            icon: None,
+            reference: None,
+            reference_hash: None,
+            decimals: 24,
+        }
+    }
+
+    pub fn storage_token(&self) -> Option<StorageToken> {
+        self.storage_token.clone()
+    }
+
+    /// Accepts `token` in [ft_on_transfer] from now on, or stops accepting tokens when `None`.
+    /// Credits are ordinary storage balance and can be withdrawn as NEAR, so the rate should
+    /// make the exchange even, as with wrapped NEAR at one yoctoNEAR per unit.
+    pub fn set_storage_token(&mut self, token: Option<StorageToken>) {
+        self.assert_sensitive();
+        env::log(b"set storage token");
+        self.storage_token = token;
+    }
+
+    /// NEP-141 receiver: credits `amount` of the configured storage token to the storage
+    /// balance of `sender_id`, or of the account named in `msg`, registering it first if
+    /// needed. The whole amount is used; a panic makes the token contract refund it.
+    pub fn ft_on_transfer(
+        &mut self,
+        sender_id: ValidAccountId,
+        amount: U128,
+        msg: String,
+    ) -> PromiseOrValue<U128> {
+        self.assert_not_paused();
+        self.assert_enabled(PAUSE_STORAGE, "storage");
+        let token = self
+            .storage_token
+            .as_ref()
+            .filter(|token| token.token_id == env::predecessor_account_id())
+            .unwrap_or_else(|| {
+                KvError::InvalidArgument("This token is not accepted for storage".to_string())
+                    .panic()
+            });
+        let account_id: AccountId = if msg.is_empty() {
+            sender_id.into()
+        } else {
+            require!(
+                env::is_valid_account_id(msg.as_bytes()),
+                KvError::InvalidArgument(
+                    "Message must be empty or the account to credit".to_string()
+                )
+            );
+            msg
+        };
+        let mut credit = amount
+            .0
+            .checked_mul(token.yocto_per_unit.0)
+            .unwrap_or_else(|| KvError::Overflow("Storage credit overflow".to_string()).panic());
+        let available = match self.storage_deposits.get(&account_id) {
+            Some(available) => available,
+            None => {
+                let min = self.storage_balance_bounds().min.0;
+                require!(
+                    credit >= min,
+                    KvError::InsufficientDeposit(format!(
+                        "The transferred amount is worth less than the minimum storage balance of {}",
+                        min
+                    ))
+                );
+                credit -= min;
+                0
+            }
+        };
+        env::log(format!("storage credit of {} for {} in tokens", credit, account_id).as_bytes());
+        self.storage_deposits
+            .insert(&account_id, &(available + credit));
+        PromiseOrValue::Value(U128(0))
+    }
+
+    /// NEP-145: withdraws `amount` (all by default) of the caller's available storage balance.
+    #[payable]
+    pub fn storage_withdraw(&mut self, amount: Option<U128>) -> StorageBalance {
+        near_sdk::assert_one_yocto();
+        self.assert_not_paused();
+        self.assert_enabled(PAUSE_STORAGE, "storage");
+        let caller = env::predecessor_account_id();
+        let available = self
+            .storage_deposits
+            .get(&caller)
+            .unwrap_or_else(|| KvError::NotRegistered(caller.to_string()).panic());
+        let amount = amount.map(|amount| amount.0).unwrap_or(available);
+        require!(
+            amount <= available,
+            KvError::InsufficientBalance(
+                "The amount is greater than the available storage balance".to_string()
+            )
+        );
+        self.storage_deposits.insert(&caller, &(available - amount));
+        if amount > 0 {
+            Promise::new(caller.clone()).transfer(amount);
+        }
+        self.internal_storage_balance(&caller).unwrap()
+    }
+
+    /// NEP-145: closes the caller's registration and refunds its balance. Accounts still paying
+    /// for stored data must delete it first, as `force` is not supported.
+    #[payable]
+    pub fn storage_unregister(&mut self, force: Option<bool>) -> bool {
+        near_sdk::assert_one_yocto();
+        self.assert_not_paused();
+        self.assert_enabled(PAUSE_STORAGE, "storage");
+        require!(
+            !force.unwrap_or(false),
+            KvError::Unsupported("Forced unregistration is not supported".to_string())
+        );
+        let caller = env::predecessor_account_id();
+        let available = match self.storage_deposits.get(&caller) {
+            Some(available) => available,
+            None => return false,
+        };
+        require!(
+            self.storage_paid.get(&caller).is_none(),
+            KvError::InvalidArgument(
+                "Can't unregister while the account still pays for stored data".to_string()
+            )
+        );
+        self.storage_deposits.remove(&caller);
+        Promise::new(caller).transfer(available + self.storage_balance_bounds().min.0);
+        true
+    }
+
+    pub fn storage_balance_of(&self, account_id: ValidAccountId) -> Option<StorageBalance> {
+        self.internal_storage_balance(account_id.as_ref())
+    }
+
+    pub fn storage_balance_bounds(&self) -> StorageBalanceBounds {
+        StorageBalanceBounds {
+            min: U128(Balance::from(STORAGE_REGISTRATION_BYTES) * env::storage_byte_cost()),
+            max: None,
+        }
+    }
+
+    /// Total storage used by the contract and a page of the accounts paying for storage, with
+    /// their cost at the current byte price.
+    pub fn storage_report(&self, from_index: Option<u64>, limit: Option<u64>) -> StorageReport {
+        let byte_cost = env::storage_byte_cost();
+        let total_bytes = env::storage_usage();
+        let accounts = self.storage_paid.keys_as_vector();
+        let values = self.storage_paid.values_as_vector();
+        let from_index = from_index.unwrap_or(0);
+        let accounts = (from_index..accounts.len())
+            .take(limit.unwrap_or(MAX_PAGE_SIZE).min(MAX_PAGE_SIZE) as usize)
+            .map(|index| {
+                let bytes = values.get(index).unwrap();
+                AccountStorage {
+                    account_id: accounts.get(index).unwrap(),
+                    bytes: U64(bytes),
+                    cost: U128(Balance::from(bytes) * byte_cost),
+                }
+            })
+            .collect();
+        StorageReport {
+            total_bytes: U64(total_bytes),
+            total_cost: U128(Balance::from(total_bytes) * byte_cost),
+            byte_cost: U128(byte_cost),
+            accounts,
+        }
+    }
+
+    pub fn tombstone_retention(&self) -> Option<U64> {
+        self.config.tombstone_retention
+    }
+
+    /// Enables soft deletes kept restorable for `retention_ns`, or hard deletes when `None`.
+    pub fn set_tombstone_retention(&mut self, retention_ns: Option<U64>) {
+        self.assert_sensitive();
+        env::log(b"set tombstone retention");
+        self.config.tombstone_retention = retention_ns;
+    }
+
+    pub fn unique_values(&self) -> bool {
+        self.config.unique_values
+    }
+
+    /// Makes writes reject values already stored under another key.
+    pub fn set_unique_values(&mut self, enabled: bool) {
+        self.assert_sensitive();
+        env::log(b"set unique values");
+        self.config.unique_values = enabled;
+    }
+
+    pub fn size_limits(&self) -> SizeLimits {
+        self.config.size_limits
+    }
+
+    pub fn set_size_limits(&mut self, limits: SizeLimits) {
+        self.assert_sensitive();
+        env::log(b"set size limits");
+        self.config.size_limits = limits;
+    }
+
+    pub fn history_depth(&self) -> u64 {
+        self.config.history_depth
+    }
+
+    /// Sets how many previous values are kept per key; shrinking takes effect on each key's
+    /// next write.
+    pub fn set_history_depth(&mut self, depth: u64) {
+        self.assert_sensitive();
+        assert_history_depth(depth);
+        env::log(b"set history depth");
+        self.config.history_depth = depth;
+    }
+
+    /// Grants `role` to `account_id`; returns false if it was already granted. Admins may grant
+    /// any role except [Role::Admin], which only the owner may grant.
+    pub fn grant_role(&mut self, account_id: ValidAccountId, role: Role) -> bool {
+        self.assert_role_manager(role);
+        let account_id: AccountId = account_id.into();
+        let mut roles = self.acl.get().roles.get(&account_id).unwrap_or_default();
+        if roles.contains(&role) {
+            return false;
+        }
+        env::log(format!("grant role {:?} to {}", role, account_id).as_bytes());
+        roles.push(role);
+        self.acl.get_mut().roles.insert(&account_id, &roles);
+        self.internal_flush();
+        true
+    }
+
+    /// Revokes `role` from `account_id`; returns false if it was not granted.
+    pub fn revoke_role(&mut self, account_id: ValidAccountId, role: Role) -> bool {
+        self.assert_role_manager(role);
+        let account_id: AccountId = account_id.into();
+        let mut roles = self.acl.get().roles.get(&account_id).unwrap_or_default();
+        let count = roles.len();
+        roles.retain(|granted| *granted != role);
+        if roles.len() == count {
+            return false;
+        }
+        env::log(format!("revoke role {:?} from {}", role, account_id).as_bytes());
+        if roles.is_empty() {
+            self.acl.get_mut().roles.remove(&account_id);
+        } else {
+            self.acl.get_mut().roles.insert(&account_id, &roles);
+        }
+        self.internal_flush();
+        true
+    }
+
+    pub fn has_role(&self, account_id: ValidAccountId, role: Role) -> bool {
+        self.internal_has_role(account_id.as_ref(), role)
+    }
+
+    pub fn roles_of(&self, account_id: ValidAccountId) -> Vec<Role> {
+        self.acl
+            .get()
+            .roles
+            .get(account_id.as_ref())
+            .unwrap_or_default()
+    }
+
+    pub fn fee_schedule(&self) -> FeeSchedule {
+        self.config.fees
+    }
+
+    pub fn set_fee_schedule(&mut self, fees: FeeSchedule) {
+        self.assert_sensitive();
+        env::log(b"set fee schedule");
+        self.config.fees = fees;
+    }
+
+    pub fn set_per_byte_fee(&mut self, fee: U128) {
+        self.assert_sensitive();
+        env::log(b"set per byte fee");
+        self.config.fees.per_byte = fee;
+    }
+
+    pub fn set_write_fee(&mut self, fee: U128) {
+        self.assert_sensitive();
+        env::log(b"set write fee");
+        self.config.fees.write_fee = fee;
+    }
+
+    pub fn set_read_fee(&mut self, fee: U128) {
+        self.assert_sensitive();
+        env::log(b"set read fee");
+        self.config.fees.read_fee = fee;
+    }
+
+    /// Write fees collected and not yet withdrawn.
+    pub fn treasury(&self) -> U128 {
+        U128(self.treasury)
+    }
+
+    /// Transfers `amount` of the collected write fees to `to`.
+    pub fn withdraw_fees(&mut self, to: ValidAccountId, amount: U128) -> Promise {
+        self.assert_only_owner();
+        require!(
+            amount.0 <= self.treasury,
+            KvError::InsufficientBalance(format!(
+                "The amount exceeds the treasury balance of {}",
+                self.treasury
+            ))
+        );
+        env::log(format!("withdraw fees: {} to {}", amount.0, to.as_ref()).as_bytes());
+        self.treasury -= amount.0;
+        Promise::new(to.into()).transfer(amount.0)
+    }
+
+    /// Writes `v` under `k` in the caller's own namespace, which no other account can write
+    /// and which is separate from the shared keyspace, and returns the previous value. Storage
+    /// is billed to the caller as for [create_update], within [namespace_quota].
+    #[payable]
+    pub fn ns_set(&mut self, k: String, v: String) -> Option<String> {
+        let k = self.normalize_key(k);
+        let initial_storage = env::storage_usage();
+        self.assert_not_paused();
+        self.assert_not_blacklisted();
+        self.assert_key_size(&k);
+        let value = Value::String(v);
+        self.assert_value_size(&k, &value);
+        let namespace = env::predecessor_account_id();
+        let previous = self
+            .namespaces
+            .insert(&(namespace.clone(), k), &value.into_text());
+        if previous.is_none() {
+            self.collection_count += 1;
+        }
+        self.writes_in_call += 1;
+        self.internal_track_namespace(&namespace, initial_storage);
+        self.internal_settle_storage(initial_storage);
+        previous
+    }
+
+    /// Removes `k` from the caller's namespace and returns its value, refunding its storage.
+    #[payable]
+    pub fn ns_remove(&mut self, k: String) -> Option<String> {
+        let k = self.normalize_key(k);
+        let initial_storage = env::storage_usage();
+        self.assert_not_paused();
+        self.assert_not_blacklisted();
+        let namespace = env::predecessor_account_id();
+        let removed = self.namespaces.remove(&(namespace.clone(), k));
+        if removed.is_some() {
+            self.collection_count -= 1;
+        }
+        self.internal_track_namespace(&namespace, initial_storage);
+        self.internal_settle_storage(initial_storage);
+        removed
+    }
+
+    /// Value of `k` in the namespace of `namespace`.
+    pub fn ns_get(&self, namespace: ValidAccountId, k: String) -> Option<String> {
+        let k = self.normalize_key(k);
+        self.namespaces.get(&(namespace.into(), k))
+    }
+
+    /// Storage bytes used by the namespace of `namespace`.
+    pub fn ns_usage(&self, namespace: ValidAccountId) -> U64 {
+        U64(self.namespace_usage.get(namespace.as_ref()).unwrap_or(0))
+    }
+
+    pub fn namespace_quota(&self) -> Option<U64> {
+        self.config.namespace_quota
+    }
+
+    /// Caps the storage bytes each namespace may use, or lifts the cap when `None`.
+    pub fn set_namespace_quota(&mut self, quota: Option<U64>) {
+        self.assert_sensitive();
+        env::log(b"set namespace quota");
+        self.config.namespace_quota = quota;
+    }
+
+    pub fn storage_quota(&self) -> Option<U64> {
+        self.config.storage_quota
+    }
+
+    /// Caps the storage bytes each account may pay for, or lifts the cap when `None`.
+    pub fn set_storage_quota(&mut self, quota: Option<U64>) {
+        self.assert_sensitive();
+        env::log(b"set storage quota");
+        self.config.storage_quota = quota;
+    }
+
+    pub fn timelock_delay(&self) -> U64 {
+        self.config.timelock_delay
+    }
+
+    /// Makes sensitive operations wait `delay_ns` after being queued; once set, changing it is
+    /// itself a queued [AdminAction::SetConfig].
+    pub fn set_timelock_delay(&mut self, delay_ns: U64) {
+        self.assert_sensitive();
+        env::log(b"set timelock delay");
+        self.config.timelock_delay = delay_ns;
+    }
+
+    pub fn allowlist_only(&self) -> bool {
+        self.config.allowlist_only
+    }
+
+    /// Restricts writes to the owner and the writer allowlist.
+    pub fn set_allowlist_only(&mut self, enabled: bool) {
+        self.assert_sensitive();
+        env::log(b"set allowlist only");
+        self.config.allowlist_only = enabled;
+    }
+
+    /// Adds `account_id` to the writer allowlist; returns false if it was already listed.
+    pub fn add_writer(&mut self, account_id: ValidAccountId) -> bool {
+        self.assert_owner();
+        env::log(format!("add writer {}", account_id.as_ref()).as_bytes());
+        let added = self.acl.get_mut().writers.insert(account_id.as_ref());
+        self.internal_flush();
+        added
+    }
+
+    /// Removes `account_id` from the writer allowlist; returns false if it was not listed.
+    pub fn remove_writer(&mut self, account_id: ValidAccountId) -> bool {
+        self.assert_owner();
+        env::log(format!("remove writer {}", account_id.as_ref()).as_bytes());
+        let removed = self.acl.get_mut().writers.remove(account_id.as_ref());
+        self.internal_flush();
+        removed
+    }
+
+    /// Subscribes the calling contract to writes of keys starting with `prefix` (all keys for
+    /// ""): each one calls its `on_kv_change(key, value)`, with `value` null for deletions.
+    /// Returns false if it was already subscribed.
+    #[payable]
+    pub fn subscribe(&mut self, prefix: String) -> bool {
+        let initial_storage = env::storage_usage();
+        self.assert_not_paused();
+        self.assert_not_blacklisted();
+        self.assert_key_size(&prefix);
+        let subscriber = env::predecessor_account_id();
+        let mut subscribers = self.subscribers.get(&prefix).unwrap_or_default();
+        if subscribers.contains(&subscriber) {
+            return false;
+        }
+        require!(
+            subscribers.len() < MAX_SUBSCRIBERS_PER_PREFIX,
+            KvError::LimitExceeded(format!(
+                "Prefix '{}' already has the maximum of {} subscribers",
+                prefix, MAX_SUBSCRIBERS_PER_PREFIX
+            ))
+        );
+        env::log(format!("subscribe: {} to '{}'", subscriber, prefix).as_bytes());
+        if subscribers.is_empty() {
+            *self
+                .subscribed_prefix_lens
+                .entry(prefix.len() as u64)
+                .or_default() += 1;
+        }
+        subscribers.push(subscriber);
+        self.subscribers.insert(&prefix, &subscribers);
+        self.internal_settle_storage(initial_storage);
+        true
+    }
+
+    /// Ends the calling contract's subscription to `prefix`; returns false if it had none.
+    #[payable]
+    pub fn unsubscribe(&mut self, prefix: String) -> bool {
+        let initial_storage = env::storage_usage();
+        self.assert_not_paused();
+        let subscriber = env::predecessor_account_id();
+        let mut subscribers = self.subscribers.get(&prefix).unwrap_or_default();
+        if !subscribers.contains(&subscriber) {
+            return false;
+        }
+        env::log(format!("unsubscribe: {} from '{}'", subscriber, prefix).as_bytes());
+        subscribers.retain(|account_id| *account_id != subscriber);
+        if subscribers.is_empty() {
+            self.subscribers.remove(&prefix);
+            let len = prefix.len() as u64;
+            match self.subscribed_prefix_lens.get_mut(&len) {
+                Some(count) if *count > 1 => *count -= 1,
+                _ => {
+                    self.subscribed_prefix_lens.remove(&len);
+                }
+            }
+        } else {
+            self.subscribers.insert(&prefix, &subscribers);
+        }
+        self.internal_settle_storage(initial_storage);
+        true
+    }
+
+    pub fn subscribers(&self, prefix: String) -> Vec<AccountId> {
+        self.subscribers.get(&prefix).unwrap_or_default()
+    }
+
+    /// Registers `account_id` as an oracle allowed to [push_feed]; returns false if it already
+    /// was one.
+    pub fn add_oracle(&mut self, account_id: ValidAccountId) -> bool {
+        self.assert_owner();
+        env::log(format!("add oracle {}", account_id.as_ref()).as_bytes());
+        self.oracles.insert(account_id.as_ref())
+    }
+
+    /// Deregisters the oracle `account_id`; returns false if it was not one. Its past rounds
+    /// stay readable.
+    pub fn remove_oracle(&mut self, account_id: ValidAccountId) -> bool {
+        self.assert_owner();
+        env::log(format!("remove oracle {}", account_id.as_ref()).as_bytes());
+        self.oracles.remove(account_id.as_ref())
+    }
+
+    pub fn list_oracles(&self, from_index: u64, limit: u64) -> Vec<AccountId> {
+        self.oracles
+            .iter()
+            .skip(from_index as usize)
+            .take(limit.min(MAX_PAGE_SIZE) as usize)
+            .collect()
+    }
+
+    /// Publishes round `round` of `feed`, observed by the calling oracle at `observed_at`.
+    /// `signature` is the oracle's ed25519 signature, with its [set_relay_key] key, of the
+    /// sha256 hash of the Borsh encoded `(feed, value, round, observed_at)`. Rounds must
+    /// increase and observations cannot lie in the future.
+    #[payable]
+    pub fn push_feed(
+        &mut self,
+        feed: String,
+        value: String,
+        round: U64,
+        observed_at: U64,
+        signature: Base64VecU8,
+    ) {
+        let initial_storage = env::storage_usage();
+        self.assert_not_paused();
+        self.assert_not_blacklisted();
+        let oracle = env::predecessor_account_id();
+        require!(
+            self.oracles.contains(&oracle),
+            KvError::Unauthorized(format!("Account '{}' is not a registered oracle", oracle))
+        );
+        let message = env::sha256(
+            &(&feed, &value, round.0, observed_at.0)
+                .try_to_vec()
+                .unwrap(),
+        );
+        self.assert_signed_by(&oracle, &message, &signature);
+        self.assert_key_size(&feed);
+        self.assert_value_size(&feed, &Value::String(value.clone()));
+        if let Some(latest) = self.feeds.get(&feed) {
+            require!(
+                round.0 > latest.round.0,
+                KvError::InvalidArgument(format!(
+                    "Round {} of feed '{}' is not newer than round {}",
+                    round.0, feed, latest.round.0
+                ))
+            );
+        }
+        let now = env::block_timestamp();
+        require!(
+            observed_at.0 <= now,
+            KvError::InvalidArgument("Observation time lies in the future".to_string())
+        );
+        env::log(format!("oracle: {} pushed round {} of '{}'", oracle, round.0, feed).as_bytes());
+        self.feeds.insert(
+            &feed,
+            &FeedRound {
+                value,
+                round,
+                oracle,
+                observed_at,
+                submitted_at: U64(now),
+                signature,
+            },
+        );
+        self.internal_settle_storage(initial_storage);
+    }
+
+    /// Latest round of `feed`, flagged stale if it was observed more than `max_age_ns` ago.
+    pub fn read_feed(&self, feed: String, max_age_ns: Option<U64>) -> Option<FeedReading> {
+        let round = self.feeds.get(&feed)?;
+        let age = env::block_timestamp().saturating_sub(round.observed_at.0);
+        Some(FeedReading {
+            stale: max_age_ns.is_some_and(|max_age| age > max_age.0),
+            round,
+        })
+    }
+
+    /// Blacklists `account_id`, rejecting it from every mutating method; returns false if it was
+    /// already blacklisted. Callable by the owner, admins and moderators.
+    pub fn blacklist_add(&mut self, account_id: ValidAccountId) -> bool {
+        self.assert_moderator();
+        let added = self.acl.get_mut().blacklist.insert(account_id.as_ref());
+        if added {
+            emit_kv_control_event(
+                "kv_blacklist_add",
+                near_sdk::serde_json::json!({
+                    "account_id": account_id,
+                    "actor": env::predecessor_account_id(),
+                }),
+            );
+        }
+        self.internal_flush();
+        added
+    }
+
+    /// Lifts the blacklisting of `account_id`; returns false if it was not blacklisted.
+    pub fn blacklist_remove(&mut self, account_id: ValidAccountId) -> bool {
+        self.assert_moderator();
+        let removed = self.acl.get_mut().blacklist.remove(account_id.as_ref());
+        if removed {
+            emit_kv_control_event(
+                "kv_blacklist_remove",
+                near_sdk::serde_json::json!({
+                    "account_id": account_id,
+                    "actor": env::predecessor_account_id(),
+                }),
+            );
+        }
+        self.internal_flush();
+        removed
+    }
+
+    pub fn is_blacklisted(&self, account_id: ValidAccountId) -> bool {
+        self.acl.get().blacklist.contains(account_id.as_ref())
+    }
+
+    pub fn list_blacklisted(&self, from_index: u64, limit: u64) -> Vec<AccountId> {
+        self.acl
+            .get()
+            .blacklist
+            .iter()
+            .skip(from_index as usize)
+            .take(limit.min(MAX_PAGE_SIZE) as usize)
+            .collect()
+    }
+
+    pub fn list_writers(&self, from_index: u64, limit: u64) -> Vec<AccountId> {
+        self.acl
+            .get()
+            .writers
+            .iter()
+            .skip(from_index as usize)
+            .take(limit.min(MAX_PAGE_SIZE) as usize)
+            .collect()
+    }
+
+    /// Appends `v` to the list under `k`, creating it if absent, and returns the new length.
+    #[payable]
+    pub fn list_push(&mut self, k: String, v: String) -> u64 {
+        let k = self.normalize_key(k);
+        let initial_storage = env::storage_usage();
+        self.assert_enabled(PAUSE_COLLECTIONS, "collections");
+        self.assert_can_write();
+        self.assert_key_size(&k);
+        let value = Value::String(v);
+        self.assert_value_size(&k, &value);
+        env::log(b"list push");
+        let mut list = self
+            .lists
+            .get(&k)
+            .unwrap_or_else(|| Vector::new(nested_prefix(LIST_ELEMENTS_PREFIX, &k)));
+        list.push(&value.into_text());
+        if self.lists.insert(&k, &list).is_none() {
+            self.collection_count += 1;
+        }
+        self.internal_settle_storage(initial_storage);
+        list.len()
+    }
+
+    /// Removes and returns the last element of the list under `k`; empty lists are removed.
+    #[payable]
+    pub fn list_pop(&mut self, k: String) -> Option<String> {
+        let k = self.normalize_key(k);
+        let initial_storage = env::storage_usage();
+        self.assert_enabled(PAUSE_COLLECTIONS, "collections");
+        self.assert_can_write();
+        env::log(b"list pop");
+        let mut list = self.lists.get(&k)?;
+        let popped = list.pop();
+        if list.is_empty() {
+            self.lists.remove(&k);
+            self.collection_count -= 1;
+        } else {
+            self.lists.insert(&k, &list);
+        }
+        self.internal_settle_storage(initial_storage);
+        popped
+    }
+
+    /// Elements `from_index..from_index + limit` of the list under `k`.
+    pub fn list_get(&self, k: String, from_index: u64, limit: u64) -> Vec<String> {
+        let k = self.normalize_key(k);
+        match self.lists.get(&k) {
+            Some(list) => (from_index..list.len())
+                .take(limit.min(MAX_PAGE_SIZE) as usize)
+                .map(|index| list.get(index).unwrap())
+                .collect(),
+            None => vec![],
+        }
+    }
+
+    pub fn list_len(&self, k: String) -> u64 {
+        let k = self.normalize_key(k);
+        self.lists.get(&k).map_or(0, |list| list.len())
+    }
+
+    /// Adds `member` to the set under `k`, creating it if absent; returns false if it was
+    /// already a member.
+    #[payable]
+    pub fn set_add(&mut self, k: String, member: String) -> bool {
+        let k = self.normalize_key(k);
+        let initial_storage = env::storage_usage();
+        self.assert_enabled(PAUSE_COLLECTIONS, "collections");
+        self.assert_can_write();
+        self.assert_key_size(&k);
+        self.assert_value_size(&k, &Value::String(member.clone()));
+        env::log(b"set add");
+        let mut set = self
+            .sets
+            .get(&k)
+            .unwrap_or_else(|| UnorderedSet::new(nested_prefix(SET_MEMBERS_PREFIX, &k)));
+        let added = set.insert(&member);
+        if self.sets.insert(&k, &set).is_none() {
+            self.collection_count += 1;
+        }
+        self.internal_settle_storage(initial_storage);
+        added
+    }
+
+    /// Removes `member` from the set under `k`; empty sets are removed.
+    #[payable]
+    pub fn set_remove(&mut self, k: String, member: String) -> bool {
+        let k = self.normalize_key(k);
+        let initial_storage = env::storage_usage();
+        self.assert_enabled(PAUSE_COLLECTIONS, "collections");
+        self.assert_can_write();
+        env::log(b"set remove");
+        let mut set = match self.sets.get(&k) {
+            Some(set) => set,
+            None => return false,
+        };
+        let removed = set.remove(&member);
+        if set.is_empty() {
+            self.sets.remove(&k);
+            self.collection_count -= 1;
+        } else {
+            self.sets.insert(&k, &set);
+        }
+        self.internal_settle_storage(initial_storage);
+        removed
+    }
+
+    pub fn set_contains(&self, k: String, member: String) -> bool {
+        let k = self.normalize_key(k);
+        self.sets.get(&k).is_some_and(|set| set.contains(&member))
+    }
+
+    pub fn set_members(&self, k: String, from_index: u64, limit: u64) -> Vec<String> {
+        let k = self.normalize_key(k);
+        match self.sets.get(&k) {
+            Some(set) => {
+                let members = set.as_vector();
+                (from_index..members.len())
+                    .take(limit.min(MAX_PAGE_SIZE) as usize)
+                    .map(|index| members.get(index).unwrap())
+                    .collect()
+            }
+            None => vec![],
+        }
+    }
+
+    pub fn set_len(&self, k: String) -> u64 {
+        let k = self.normalize_key(k);
+        self.sets.get(&k).map_or(0, |set| set.len())
+    }
+
+    /// Sets `field` of the hash under `k`, creating it if absent, and returns the previous value
+    /// of the field.
+    #[payable]
+    pub fn hset(&mut self, k: String, field: String, v: String) -> Option<String> {
+        let k = self.normalize_key(k);
+        let initial_storage = env::storage_usage();
+        self.assert_enabled(PAUSE_COLLECTIONS, "collections");
+        self.assert_can_write();
+        self.assert_key_size(&k);
+        self.assert_key_size(&field);
+        self.assert_value_size(&k, &Value::String(v.clone()));
+        env::log(b"hset");
+        let mut hash = self
+            .hashes
+            .get(&k)
+            .unwrap_or_else(|| UnorderedMap::new(nested_prefix(HASH_FIELDS_PREFIX, &k)));
+        let previous = hash.insert(&field, &v);
+        if self.hashes.insert(&k, &hash).is_none() {
+            self.collection_count += 1;
+        }
+        self.internal_settle_storage(initial_storage);
+        previous
+    }
+
+    pub fn hget(&self, k: String, field: String) -> Option<String> {
+        let k = self.normalize_key(k);
+        self.hashes.get(&k).and_then(|hash| hash.get(&field))
+    }
+
+    /// Removes `field` from the hash under `k`; empty hashes are removed.
+    #[payable]
+    pub fn hdel(&mut self, k: String, field: String) -> bool {
+        let k = self.normalize_key(k);
+        let initial_storage = env::storage_usage();
+        self.assert_enabled(PAUSE_COLLECTIONS, "collections");
+        self.assert_can_write();
+        env::log(b"hdel");
+        let mut hash = match self.hashes.get(&k) {
+            Some(hash) => hash,
+            None => return false,
+        };
+        let removed = hash.remove(&field).is_some();
+        if hash.is_empty() {
+            self.hashes.remove(&k);
+            self.collection_count -= 1;
+        } else {
+            self.hashes.insert(&k, &hash);
+        }
+        self.internal_settle_storage(initial_storage);
+        removed
+    }
+
+    pub fn hgetall(&self, k: String, from_index: u64, limit: u64) -> Vec<(String, String)> {
+        let k = self.normalize_key(k);
+        match self.hashes.get(&k) {
+            Some(hash) => {
+                let fields = hash.keys_as_vector();
+                let values = hash.values_as_vector();
+                (from_index..fields.len())
+                    .take(limit.min(MAX_PAGE_SIZE) as usize)
+                    .map(|index| (fields.get(index).unwrap(), values.get(index).unwrap()))
+                    .collect()
+            }
+            None => vec![],
+        }
+    }
+
+    pub fn hlen(&self, k: String) -> u64 {
+        let k = self.normalize_key(k);
+        self.hashes.get(&k).map_or(0, |hash| hash.len())
+    }
+
+    /// Adds `member` with `score` to the sorted set under `k`, or updates its score; returns
+    /// true if the member is new.
+    #[payable]
+    pub fn zadd(&mut self, k: String, member: String, score: I64) -> bool {
+        let k = self.normalize_key(k);
+        let initial_storage = env::storage_usage();
+        self.assert_enabled(PAUSE_COLLECTIONS, "collections");
+        self.assert_can_write();
+        self.assert_key_size(&k);
+        self.assert_value_size(&k, &Value::String(member.clone()));
+        env::log(b"zadd");
+        let mut set = self
+            .sorted_sets
+            .get(&k)
+            .unwrap_or_else(|| SortedSet::new(&k));
+        let previous = set.scores.insert(&member, &score.0);
+        if let Some(previous) = previous {
+            set.order.remove(&(previous, member.clone()));
+        }
+        set.order.insert(&(score.0, member), &());
+        if self.sorted_sets.insert(&k, &set).is_none() {
+            self.collection_count += 1;
+        }
+        self.internal_settle_storage(initial_storage);
+        previous.is_none()
+    }
+
+    /// Removes `member` from the sorted set under `k`; empty sets are removed.
+    #[payable]
+    pub fn zrem(&mut self, k: String, member: String) -> bool {
+        let k = self.normalize_key(k);
+        let initial_storage = env::storage_usage();
+        self.assert_enabled(PAUSE_COLLECTIONS, "collections");
+        self.assert_can_write();
+        env::log(b"zrem");
+        let mut set = match self.sorted_sets.get(&k) {
+            Some(set) => set,
+            None => return false,
+        };
+        let removed = match set.scores.remove(&member) {
+            Some(score) => {
+                set.order.remove(&(score, member));
+                true
+            }
+            None => false,
+        };
+        if set.order.len() == 0 {
+            self.sorted_sets.remove(&k);
+            self.collection_count -= 1;
+        } else {
+            self.sorted_sets.insert(&k, &set);
+        }
+        self.internal_settle_storage(initial_storage);
+        removed
+    }
+
+    /// Members with `min <= score <= max`, lowest score first.
+    pub fn zrange_by_score(&self, k: String, min: I64, max: I64, limit: u64) -> Vec<(String, I64)> {
+        let k = self.normalize_key(k);
+        require!(
+            min.0 <= max.0,
+            KvError::InvalidArgument("min must not be greater than max".to_string())
+        );
+        let set = match self.sorted_sets.get(&k) {
+            Some(set) => set,
+            None => return vec![],
+        };
+        let upper = match max.0.checked_add(1) {
+            Some(next) => Bound::Excluded((next, String::new())),
+            None => Bound::Unbounded,
+        };
+        set.order
+            .range((Bound::Included((min.0, String::new())), upper))
+            .take(limit.min(MAX_PAGE_SIZE) as usize)
+            .map(|((score, member), _)| (member, I64(score)))
+            .collect()
+    }
+
+    /// Zero-based position of `member` in ascending score order. Counts the members ranked
+    /// before it, so the cost grows with the rank.
+    pub fn zrank(&self, k: String, member: String) -> Option<u64> {
+        let k = self.normalize_key(k);
+        let set = self.sorted_sets.get(&k)?;
+        let score = set.scores.get(&member)?;
+        let target = (score, member);
+        let rank = set
+            .order
+            .iter()
+            .take_while(|(entry, _)| *entry < target)
+            .count();
+        Some(rank as u64)
+    }
+
+    /// Removes up to `limit` pairs (capped at [MAX_BATCH_SIZE]) starting from the end of the
+    /// map, so repeated calls resume where the previous one stopped.
+    pub fn clear_all(&mut self, limit: u64) -> ClearProgress {
+        self.assert_sensitive();
+        env::log(b"clear all");
+        let progress = self.internal_clear(limit);
+        self.internal_flush();
+        progress
+    }
+
+    /// Replaces the contract code with `code` and migrates the state in the same transaction
+    /// chain, so no full-access key is needed on the contract account. A failing `migrate`
+    /// leaves the new code deployed on the old state.
+    pub fn upgrade(&mut self, code: Base64VecU8) -> Promise {
+        self.assert_sensitive();
+        internal_upgrade(code)
+    }
+
+    pub fn nft_gate(&self) -> Option<NftGate> {
+        self.nft_gate.clone()
+    }
+
+    /// Restricts writes to keys starting with `gate.prefix` to holders of a token of
+    /// `gate.nft_contract_id`, or lifts the restriction when `None`.
+    pub fn set_nft_gate(&mut self, gate: Option<NftGate>) {
+        self.assert_sensitive();
+        env::log(b"set nft gate");
+        self.nft_gate = gate;
+    }
+
+    /// Writes `v` under the gated key `k` once the NFT contract confirms the caller owns
+    /// `token_id`. The attached deposit travels with the callback, which charges storage as
+    /// [create_update] does or refunds it if the check fails.
+    #[payable]
+    pub fn gated_write(&mut self, k: String, v: String, token_id: String) -> Promise {
+        let k = self.normalize_key(k);
+        let gate = self
+            .nft_gate
+            .clone()
+            .filter(|gate| k.starts_with(&gate.prefix))
+            .unwrap_or_else(|| KvError::NotFound(format!("Key '{}' is not NFT gated", k)).panic());
+        self.assert_can_insert(&k, &Value::String(v.clone()), None);
+        Promise::new(gate.nft_contract_id)
+            .function_call(
+                b"nft_token".to_vec(),
+                near_sdk::serde_json::json!({ "token_id": token_id })
+                    .to_string()
+                    .into_bytes(),
+                0,
+                NFT_LOOKUP_GAS,
+            )
+            .then(
+                Promise::new(env::current_account_id()).function_call(
+                    b"on_nft_verified".to_vec(),
+                    near_sdk::serde_json::json!({
+                        "k": k,
+                        "v": v,
+                        "writer": env::predecessor_account_id(),
+                    })
+                    .to_string()
+                    .into_bytes(),
+                    env::attached_deposit(),
+                    NFT_CALLBACK_GAS,
+                ),
+            )
+    }
+
+    /// Callback of [gated_write]: commits the write on behalf of `writer` if the looked up
+    /// token is owned by them and they may still write `k`, otherwise refunds the deposit.
+    /// Returns whether it was written.
+    #[private]
+    #[payable]
+    pub fn on_nft_verified(&mut self, k: String, v: String, writer: AccountId) -> bool {
+        let owner_id = match env::promise_result(0) {
+            PromiseResult::Successful(token) => {
+                near_sdk::serde_json::from_slice::<near_sdk::serde_json::Value>(&token)
+                    .ok()
+                    .and_then(|token| token["owner_id"].as_str().map(str::to_string))
+            }
+            _ => None,
+        };
+        // The predecessor here is the contract itself, so the write is checked again, as state
+        // may have changed since `gated_write`, and recorded as made by `writer`
+        self.acting_author = Some(writer.clone());
+        let value = Value::String(v);
+        let checked = match owner_id {
+            Some(owner_id) if owner_id == writer => self.check_can_insert(&k, &value, None),
+            _ => Err(KvError::Unauthorized(format!(
+                "{} does not own the token",
+                writer
+            ))),
+        };
+        if let Err(error) = checked {
+            self.acting_author = None;
+            env::log(format!("nft gate: {}", error).as_bytes());
+            if env::attached_deposit() > 0 {
+                Promise::new(writer).transfer(env::attached_deposit());
+            }
+            return false;
+        }
+        let initial_storage = env::storage_usage();
+        self.writes_in_call += 1;
+        self.internal_put(&k, &value);
+        self.internal_set_expiry(&k, None);
+        self.acting_author = None;
+        self.internal_settle_storage_for(writer, env::attached_deposit(), initial_storage);
+        true
+    }
+
+    pub fn upstream(&self) -> Option<AccountId> {
+        self.upstream_id.clone()
+    }
+
+    /// Makes [read_through] forward lookups of missing keys to `upstream_id`, or stops
+    /// forwarding when `None`.
+    pub fn set_upstream(&mut self, upstream_id: Option<ValidAccountId>) {
+        self.assert_sensitive();
+        env::log(b"set upstream");
+        self.upstream_id = upstream_id.map(Into::into);
+    }
+
+    pub fn mirror(&self) -> Option<AccountId> {
+        self.mirror_id.clone()
+    }
+
+    /// Replicates every change of an entry from now on to `mirror_id`, another instance of this
+    /// contract that accepts them through [mirror_apply], or stops replicating when `None`.
+    /// The entries a call changes go to the mirror in one batch, value and metadata as a whole,
+    /// including removals by [purge_expired]. The mirror charges this contract for storage, so
+    /// it must be registered there with [storage_deposit] first.
+    pub fn set_mirror(&mut self, mirror_id: Option<ValidAccountId>) {
+        self.assert_sensitive();
+        env::log(b"set mirror");
+        self.mirror_id = mirror_id.map(Into::into);
+    }
+
+    pub fn mirror_source(&self) -> Option<AccountId> {
+        self.mirror_source_id.clone()
+    }
+
+    /// Accepts the entries `source_id` replicates with [set_mirror], or stops accepting them
+    /// when `None`.
+    pub fn set_mirror_source(&mut self, source_id: Option<ValidAccountId>) {
+        self.assert_sensitive();
+        env::log(b"set mirror source");
+        self.mirror_source_id = source_id.map(Into::into);
+    }
+
+    /// Applies a batch replicated by the mirror source: each key takes the entry it has there,
+    /// value and metadata as they are, or is removed when `None`. Storage is charged to the
+    /// source, and history is not replicated.
+    pub fn mirror_apply(
+        &mut self,
+        #[serializer(borsh)] changes: Vec<(String, Option<MirroredEntry>)>,
+    ) {
+        let initial_storage = env::storage_usage();
+        self.assert_not_paused();
+        let source = env::predecessor_account_id();
+        require!(
+            self.mirror_source_id.as_ref() == Some(&source),
+            KvError::Unauthorized(format!("Account '{}' is not the mirror source", source))
+        );
+        env::log(format!("mirror: applying {} changes from {}", changes.len(), source).as_bytes());
+        for (k, entry) in changes {
+            let entry = entry.map(|mut entry| {
+                entry.meta.payer = source.clone();
+                entry.meta.oldest_version = entry.meta.version;
+                entry
+            });
+            self.internal_set_entry(&k, entry);
+        }
+        self.internal_settle_storage(initial_storage);
+    }
+
+    /// Callback of a batch replicated to the mirror: if the mirror rejected it, each key is
+    /// restored to its entry from before the batch, unless a later call has changed it since.
+    /// Returns whether the mirror accepted the batch. Rollbacks are not replicated, and the
+    /// storage they free or use is not settled with the payers.
+    #[private]
+    pub fn on_mirror(&mut self, #[serializer(borsh)] changes: Vec<MirrorChange>) -> bool {
+        if let PromiseResult::Successful(_) = env::promise_result(0) {
+            return true;
+        }
+        for change in changes {
+            if self.internal_entry(&change.key) != change.entry {
+                env::log(
+                    format!(
+                        "mirror: change of '{}' failed and was superseded",
+                        change.key
+                    )
+                    .as_bytes(),
+                );
+                continue;
+            }
+            env::log(
+                format!(
+                    "mirror: change of '{}' failed and was rolled back",
+                    change.key
+                )
+                .as_bytes(),
+            );
+            self.internal_set_entry(&change.key, change.previous);
+        }
+        self.mirror_changes.clear();
+        self.payer_deltas.clear();
+        self.internal_flush();
+        false
+    }
+
+    pub fn multisig(&self) -> Option<Multisig> {
+        self.acl.get().multisig.clone()
+    }
+
+    /// Sets up the admin set whose confirmations sensitive operations require from then on.
+    /// Once configured it can only be changed through a [AdminAction::SetMultisig] proposal.
+    pub fn set_multisig(&mut self, multisig: Multisig) {
+        self.assert_sensitive();
+        multisig.assert_valid();
+        env::log(b"set multisig");
+        self.acl.get_mut().multisig = Some(multisig);
+        self.internal_flush();
+    }
+
+    /// Proposes `action` on behalf of the calling admin, who confirms it right away, and
+    /// returns the proposal id.
+    pub fn propose(&mut self, action: AdminAction) -> U64 {
+        self.assert_multisig_member();
+        let id = self.next_proposal_id;
+        self.next_proposal_id += 1;
+        let proposal = Proposal {
+            action,
+            proposer: env::predecessor_account_id(),
+            confirmations: vec![env::predecessor_account_id()],
+            expires_at: U64(env::block_timestamp().saturating_add(PROPOSAL_LIFETIME_NS)),
+        };
+        env::log(format!("multisig: proposal {} by {}", id, proposal.proposer).as_bytes());
+        self.proposals.insert(&id, &proposal);
+        U64(id)
+    }
+
+    pub fn proposal(&self, id: U64) -> Option<Proposal> {
+        self.proposals.get(&id.0)
+    }
+
+    /// Adds the calling admin's confirmation to proposal `id`; returns false if it had already
+    /// confirmed.
+    pub fn confirm(&mut self, id: U64) -> bool {
+        self.assert_multisig_member();
+        let mut proposal = self.internal_open_proposal(id.0);
+        let caller = env::predecessor_account_id();
+        if proposal.confirmations.contains(&caller) {
+            return false;
+        }
+        env::log(format!("multisig: proposal {} confirmed by {}", id.0, caller).as_bytes());
+        proposal.confirmations.push(caller);
+        self.proposals.insert(&id.0, &proposal);
+        true
+    }
+
+    /// Carries out proposal `id` once it has enough confirmations from current admins.
+    pub fn execute(&mut self, id: U64) {
+        self.assert_multisig_member();
+        let proposal = self.internal_open_proposal(id.0);
+        let multisig = self.acl.get().multisig.as_ref().unwrap();
+        let confirmed = proposal
+            .confirmations
+            .iter()
+            .filter(|account_id| multisig.members.contains(account_id))
+            .count() as u64;
+        require!(
+            confirmed >= multisig.threshold,
+            KvError::ApprovalRequired(format!(
+                "Proposal {} has {} of the {} required confirmations",
+                id.0, confirmed, multisig.threshold
+            ))
+        );
+        env::log(format!("multisig: proposal {} executed", id.0).as_bytes());
+        self.proposals.remove(&id.0);
+        self.internal_apply(proposal.action);
+        self.internal_flush();
+    }
+
+    /// Queues `action` to become executable once the configured timelock delay has passed,
+    /// and returns its id. Used instead of calling sensitive methods directly while a delay is
+    /// configured.
+    pub fn queue(&mut self, action: AdminAction) -> U64 {
+        require!(
+            self.acl.get().multisig.is_none(),
+            KvError::ApprovalRequired("This operation requires a multisig proposal".to_string())
+        );
+        self.assert_owner();
+        let id = self.next_queued_id;
+        self.next_queued_id += 1;
+        let queued = QueuedAction {
+            action,
+            queued_by: env::predecessor_account_id(),
+            executable_at: U64(env::block_timestamp().saturating_add(self.config.timelock_delay.0)),
+        };
+        env::log(
+            format!(
+                "timelock: action {} queued, executable at {}",
+                id, queued.executable_at.0
+            )
+            .as_bytes(),
+        );
+        self.queued.insert(&id, &queued);
+        U64(id)
+    }
+
+    pub fn queued_action(&self, id: U64) -> Option<QueuedAction> {
+        self.queued.get(&id.0)
+    }
+
+    pub fn cancel_queued(&mut self, id: U64) {
+        self.assert_owner();
+        self.queued
+            .remove(&id.0)
+            .unwrap_or_else(|| KvError::NotFound(format!("Action {} is not queued", id.0)).panic());
+        env::log(format!("timelock: action {} cancelled", id.0).as_bytes());
+    }
+
+    /// Carries out queued action `id` once its delay has passed.
+    pub fn execute_queued(&mut self, id: U64) {
+        self.assert_owner();
+        let queued = self
+            .queued
+            .get(&id.0)
+            .unwrap_or_else(|| KvError::NotFound(format!("Action {} is not queued", id.0)).panic());
+        require!(
+            env::block_timestamp() >= queued.executable_at.0,
+            KvError::ApprovalRequired(format!(
+                "Action {} is timelocked until {}",
+                id.0, queued.executable_at.0
+            ))
+        );
+        env::log(format!("timelock: action {} executed", id.0).as_bytes());
+        self.queued.remove(&id.0);
+        self.internal_apply(queued.action);
+        self.internal_flush();
+    }
+}
+
+// Internal helpers
+//
+// Every write path goes through these so secondary structures stay in sync with `pairs`
+impl KeyValue {
+    /// Fresh state with empty collections, shared by [KeyValue::new] and [KeyValue::migrate].
+    fn internal_new(owner_id: AccountId, config: Config) -> Self {
+        Self {
+            pairs: LookupMap::new(PAIRS_PREFIX.to_vec()),
+            pair_count: 0,
+            collection_count: 0,
+            indexes: Lazy::new(
+                INDEXES_KEY,
+                Indexes {
+                    key_list: LookupMap::new(KEY_LIST_PREFIX.to_vec()),
+                    index: TreeMap::new(INDEX_PREFIX.to_vec()),
+                    expiry_index: TreeMap::new(EXPIRY_INDEX_PREFIX.to_vec()),
+                    value_index: LookupMap::new(VALUE_INDEX_PREFIX.to_vec()),
+                    tag_index: LookupMap::new(TAG_INDEX_PREFIX.to_vec()),
+                },
+            ),
+            meta: LookupMap::new(META_PREFIX.to_vec()),
+            history: LookupMap::new(HISTORY_PREFIX.to_vec()),
+            audit_log: Lazy::new(
+                AUDIT_LOG_KEY,
+                AuditLog {
+                    audit: LookupMap::new(AUDIT_PREFIX.to_vec()),
+                    audit_lengths: LookupMap::new(AUDIT_LENGTHS_PREFIX.to_vec()),
+                    changes: Vector::new(CHANGES_PREFIX.to_vec()),
+                    stats: Stats {
+                        creates: U64(0),
+                        updates: U64(0),
+                        deletes: U64(0),
+                        writers: U64(0),
+                    },
+                    known_writers: LookupSet::new(KNOWN_WRITERS_PREFIX.to_vec()),
+                },
+            ),
+            lists: LookupMap::new(LISTS_PREFIX.to_vec()),
+            sets: LookupMap::new(SETS_PREFIX.to_vec()),
+            hashes: LookupMap::new(HASHES_PREFIX.to_vec()),
+            sorted_sets: LookupMap::new(SORTED_SETS_PREFIX.to_vec()),
+            acl: Lazy::new(
+                ACL_KEY,
+                Acl {
+                    roles: LookupMap::new(ROLES_PREFIX.to_vec()),
+                    writers: UnorderedSet::new(WRITERS_PREFIX.to_vec()),
+                    blacklist: UnorderedSet::new(BLACKLIST_PREFIX.to_vec()),
+                    multisig: None,
+                },
+            ),
+            namespaces: LookupMap::new(NAMESPACES_PREFIX.to_vec()),
+            namespace_usage: LookupMap::new(NAMESPACE_USAGE_PREFIX.to_vec()),
+            subscribers: LookupMap::new(SUBSCRIBERS_PREFIX.to_vec()),
+            subscribed_prefix_lens: BTreeMap::new(),
+            oracles: UnorderedSet::new(ORACLES_PREFIX.to_vec()),
+            feeds: LookupMap::new(FEEDS_PREFIX.to_vec()),
+            mirror_id: None,
+            mirror_source_id: None,
+            nft_gate: None,
+            upstream_id: None,
+            croncat_manager_id: None,
+            proposals: LookupMap::new(PROPOSALS_PREFIX.to_vec()),
+            next_proposal_id: 0,
+            proposal_sweep_from: 0,
+            queued: LookupMap::new(QUEUED_PREFIX.to_vec()),
+            next_queued_id: 0,
+            storage_paid: UnorderedMap::new(STORAGE_PAID_PREFIX.to_vec()),
+            storage_deposits: LookupMap::new(STORAGE_DEPOSITS_PREFIX.to_vec()),
+            storage_token: None,
+            credits: LookupMap::new(CREDITS_PREFIX.to_vec()),
+            credits_supply: 0,
+            treasury: 0,
+            relay_keys: LookupMap::new(RELAY_KEYS_PREFIX.to_vec()),
+            relay_nonces: LookupMap::new(RELAY_NONCES_PREFIX.to_vec()),
+            writes_in_call: 0,
+            acting_author: None,
+            payer_deltas: BTreeMap::new(),
+            mirror_changes: BTreeMap::new(),
+            owner_id,
+            pending_owner_id: None,
+            paused: false,
+            paused_methods: 0,
+            maintenance: false,
+            config,
+            legacy_pairs: None,
+        }
+    }
+
+    /// Writes back the [Lazy] parts of the state changed by the current call and replicates
+    /// the entries it changed to the mirror
+    fn internal_flush(&mut self) {
+        self.internal_mirror();
+        self.indexes.flush();
+        self.audit_log.flush();
+        self.acl.flush();
+    }
+
+    /// Charges the caller for the storage the current call added since `initial_storage` and
+    /// for its write fees, from the attached deposit and then its NEP-145 balance, or refunds
+    /// the cost of the storage it freed, up to the bytes the caller has paid for. Storage of
+    /// written or removed entries is instead settled with their payers, see [EntryMeta::payer].
+    /// Freed storage is credited to the NEP-145 balance of registered accounts; it and any
+    /// unspent attached deposit are otherwise transferred back.
+    fn internal_settle_storage(&mut self, initial_storage: StorageUsage) {
+        let caller = env::predecessor_account_id();
+        self.internal_settle_storage_for(caller, env::attached_deposit(), initial_storage);
+    }
+
+    /// [internal_settle_storage] on behalf of `caller`, who attached `attached`; used when
+    /// the account that pays is not the predecessor of the current call.
+    fn internal_settle_storage_for(
+        &mut self,
+        caller: AccountId,
+        attached: Balance,
+        initial_storage: StorageUsage,
+    ) {
+        self.internal_flush();
+        let writes = std::mem::take(&mut self.writes_in_call);
+        let mut deltas = std::mem::take(&mut self.payer_deltas);
+        let attributed: i64 = deltas.values().sum();
+        let total = env::storage_usage() as i64 - initial_storage as i64;
+        let caller_delta = deltas.remove(&caller).unwrap_or(0) + total - attributed;
+        let grown = deltas
+            .values()
+            .chain(Some(&caller_delta))
+            .filter(|delta| **delta > 0)
+            .map(|delta| delta.unsigned_abs())
+            .sum();
+        let fee = self.internal_write_fee(writes, grown);
+        // The attached deposit covers growth of other payers' entries first, and whatever the
+        // caller frees without a NEP-145 balance is refunded along with what is left of it
+        let mut available = attached;
+        for (payer, delta) in deltas {
+            let freed = self.internal_settle_account(&payer, delta, 0, &mut available, attached);
+            if freed > 0 {
+                Promise::new(payer).transfer(freed);
+            }
+        }
+        available +=
+            self.internal_settle_account(&caller, caller_delta, fee, &mut available, attached);
+        self.treasury += fee;
+        if available > 0 {
+            Promise::new(caller).transfer(available);
+        }
+    }
+
+    /// Settles the storage of entries written or removed by a call that is not billed, such as
+    /// purging expired keys: payers are refunded what their entries freed.
+    fn internal_settle_payers(&mut self) {
+        self.internal_flush();
+        for (payer, delta) in std::mem::take(&mut self.payer_deltas) {
+            let freed = self.internal_settle_account(&payer, delta, 0, &mut 0, 0);
+            if freed > 0 {
+                Promise::new(payer).transfer(freed);
+            }
+        }
+    }
+
+    /// Settles `delta` bytes of storage and `fee` with `account`. Growth and the fee are paid
+    /// from `available`, what is left of the `attached` deposit, then from the account's
+    /// credits and NEP-145 balance. The cost of freed bytes the account paid for is credited to
+    /// its NEP-145 balance, or returned to be transferred if it has none.
+    fn internal_settle_account(
+        &mut self,
+        account: &AccountId,
+        delta: i64,
+        fee: Balance,
+        available: &mut Balance,
+        attached: Balance,
+    ) -> Balance {
+        let paid = self.storage_paid.get(account).unwrap_or(0);
+        let mut freed = 0;
+        let mut grown = 0;
+        if delta > 0 {
+            grown = delta.unsigned_abs();
+            if let Some(quota) = self.config.storage_quota {
+                require!(
+                    paid + grown <= quota.0,
+                    KvError::QuotaExceeded(format!(
+                        "Storage quota of {} bytes exceeded: {} uses {} bytes and the write needs {} more",
+                        quota.0, account, paid, grown
+                    ))
+                );
+            }
+            self.storage_paid.insert(account, &(paid + grown));
+        } else {
+            let bytes = delta.unsigned_abs().min(paid);
+            if bytes > 0 {
+                if paid == bytes {
+                    self.storage_paid.remove(account);
+                } else {
+                    self.storage_paid.insert(account, &(paid - bytes));
+                }
+                freed = Balance::from(bytes) * env::storage_byte_cost();
+            }
+        }
+
+        let cost = Balance::from(grown) * env::storage_byte_cost();
+        let charge = cost + fee;
+        let mut shortfall = charge.saturating_sub(*available);
+        *available -= charge - shortfall;
+        let credits = self.credits.get(account).unwrap_or(0).min(shortfall);
+        if credits > 0 {
+            self.internal_burn_credits(account, credits);
+            shortfall -= credits;
+        }
+        let deposit = self.storage_deposits.get(account);
+        if shortfall > 0 {
+            require!(
+                deposit.unwrap_or(0) >= shortfall,
+                KvError::InsufficientDeposit(format!(
+                    "Attached deposit of {} does not cover the storage cost of {} for {} bytes plus write fees of {}",
+                    attached, cost, grown, fee
+                ))
+            );
+            self.storage_deposits
+                .insert(account, &(deposit.unwrap() - shortfall));
+        }
+        match deposit {
+            Some(balance) if freed > 0 => {
+                self.storage_deposits.insert(account, &(balance + freed));
+                0
+            }
+            _ => freed,
+        }
+    }
+
+    /// Attributes the storage change of an entry since `initial_storage` to its `payer`, who
+    /// settles it at the end of the call
+    fn internal_track_payer(&mut self, payer: &AccountId, initial_storage: StorageUsage) {
+        let delta = env::storage_usage() as i64 - initial_storage as i64;
+        *self.payer_deltas.entry(payer.clone()).or_default() += delta;
+    }
+
+    /// Account billed for the storage of entries the current call creates: the predecessor,
+    /// or the writer a callback of the contract itself acts for
+    fn internal_payer(&self) -> AccountId {
+        let predecessor = env::predecessor_account_id();
+        match &self.acting_author {
+            Some(author) if predecessor == env::current_account_id() => author.clone(),
+            _ => predecessor,
+        }
+    }
+
+    /// Updates the bytes used by `namespace` with the storage change since `initial_storage`,
+    /// enforcing the namespace quota.
+    fn internal_track_namespace(&mut self, namespace: &AccountId, initial_storage: StorageUsage) {
+        let used = self.namespace_usage.get(namespace).unwrap_or(0);
+        let current_storage = env::storage_usage();
+        let used = if current_storage >= initial_storage {
+            let used = used + (current_storage - initial_storage);
+            if let Some(quota) = self.config.namespace_quota {
+                require!(
+                    used <= quota.0,
+                    KvError::QuotaExceeded(format!(
+                        "Namespace quota of {} bytes exceeded: {} would use {} bytes",
+                        quota.0, namespace, used
+                    ))
+                );
+            }
+            used
+        } else {
+            used.saturating_sub(initial_storage - current_storage)
+        };
+        if used == 0 {
+            self.namespace_usage.remove(namespace);
+        } else {
+            self.namespace_usage.insert(namespace, &used);
+        }
+    }
+
+    fn internal_burn_credits(&mut self, account_id: &AccountId, amount: Balance) {
+        let balance = self.credits.get(account_id).unwrap_or(0);
+        require!(
+            balance >= amount,
+            KvError::InsufficientBalance("Not enough storage credits".to_string())
+        );
+        self.credits.insert(account_id, &(balance - amount));
+        self.credits_supply -= amount;
+        emit_event(
+            "nep141",
+            "1.0.0",
+            "ft_burn",
+            near_sdk::serde_json::json!({ "owner_id": account_id, "amount": U128(amount) }),
+        );
+    }
+
+    /// Hands the key `token_id` from its owner `sender_id` to `receiver_id`.
+    fn internal_transfer_token(
+        &mut self,
+        sender_id: &AccountId,
+        receiver_id: &AccountId,
+        token_id: &String,
+        memo: Option<String>,
+    ) {
+        self.assert_enabled(PAUSE_ACCESS, "access control");
+        self.assert_not_paused();
+        self.assert_not_blacklisted();
+        let mut meta = self
+            .internal_live_meta(token_id)
+            .unwrap_or_else(|| KvError::KeyNotFound(token_id.to_string()).panic());
+        require!(
+            meta.created_by == *sender_id,
+            KvError::Unauthorized(format!("Only the owner of '{}' can transfer it", token_id))
+        );
+        require!(
+            sender_id != receiver_id,
+            KvError::InvalidArgument(format!("The key already belongs to {}", receiver_id))
+        );
+        meta.created_by = receiver_id.clone();
+        meta.pending_owner = None;
+        meta.write_grants.clear();
+        self.internal_update_meta(token_id, &meta);
+        emit_event(
+            "nep171",
+            "1.0.0",
+            "nft_transfer",
+            near_sdk::serde_json::json!({
+                "old_owner_id": sender_id,
+                "new_owner_id": receiver_id,
+                "token_ids": [token_id],
+                "memo": memo,
+            }),
+        );
+    }
+
+    /// Moves `amount` credits between registered accounts.
+    fn internal_transfer_credits(
+        &mut self,
+        sender_id: &AccountId,
+        receiver_id: &AccountId,
+        amount: Balance,
+        memo: Option<String>,
+    ) {
+        require!(
+            sender_id != receiver_id,
+            KvError::InvalidArgument("Sender and receiver must differ".to_string())
+        );
+        require!(
+            amount > 0,
+            KvError::InvalidArgument("The amount must be positive".to_string())
+        );
+        require!(
+            self.storage_deposits.contains_key(receiver_id),
+            KvError::NotRegistered(receiver_id.to_string())
+        );
+        let balance = self.credits.get(sender_id).unwrap_or(0);
+        require!(
+            balance >= amount,
+            KvError::InsufficientBalance("Not enough storage credits".to_string())
+        );
+        self.credits.insert(sender_id, &(balance - amount));
+        let received = self.credits.get(receiver_id).unwrap_or(0);
+        self.credits.insert(receiver_id, &(received + amount));
+        emit_event(
+            "nep141",
+            "1.0.0",
+            "ft_transfer",
+            near_sdk::serde_json::json!({
+                "old_owner_id": sender_id,
+                "new_owner_id": receiver_id,
+                "amount": U128(amount),
+                "memo": memo,
+            }),
+        );
+    }
+
+    /// Fee owed for `writes` value writes that together added `bytes` of storage
+    fn internal_write_fee(&self, writes: u64, bytes: StorageUsage) -> Balance {
+        let fees = self.config.fees;
+        Balance::from(writes) * fees.write_fee.0 + Balance::from(bytes) * fees.per_byte.0
+    }
+
+    /// Transfers `amount` back to the caller, if non-zero.
+    fn internal_refund(&self, amount: Balance) {
+        if amount > 0 {
+            Promise::new(env::predecessor_account_id()).transfer(amount);
+        }
+    }
+
+    fn internal_storage_balance(&self, account_id: &str) -> Option<StorageBalance> {
+        let available = self.storage_deposits.get(&account_id.to_string())?;
+        let paid = self.storage_paid.get(&account_id.to_string()).unwrap_or(0);
+        let total = self.storage_balance_bounds().min.0
+            + Balance::from(paid) * env::storage_byte_cost()
+            + available;
+        Some(StorageBalance {
+            total: U128(total),
+            available: U128(available),
+        })
+    }
+
+    /// Removes up to `limit` pairs (capped at [MAX_BATCH_SIZE]) from the end of the map.
+    fn internal_clear(&mut self, limit: u64) -> ClearProgress {
+        self.assert_not_in_maintenance();
+        self.assert_listing();
+        let batch = limit.min(MAX_BATCH_SIZE as u64);
+        let mut removed = 0;
+        while removed < batch {
+            let last = (0..KEY_LIST_SHARDS).rev().find_map(|shard| {
+                let keys = self.indexes.get().key_list.get(&shard)?;
+                keys.as_vector().get(keys.len().checked_sub(1)?)
+            });
+            match last {
+                Some(k) => {
+                    self.internal_remove(&k);
+                    removed += 1;
+                }
+                None => break,
+            }
+        }
+        self.internal_settle_payers();
+        ClearProgress {
+            removed,
+            remaining: self.pair_count,
+        }
+    }
+
+    /// Panics unless the caller may run a sensitive operation directly, which is only the case
+    /// for the owner and admins while neither a multisig nor a timelock delay is configured.
+    fn assert_sensitive(&self) {
+        require!(
+            self.acl.get().multisig.is_none(),
+            KvError::ApprovalRequired("This operation requires a multisig proposal".to_string())
+        );
+        require!(
+            self.config.timelock_delay.0 == 0,
+            KvError::ApprovalRequired(
+                "This operation must be queued behind the timelock".to_string()
+            )
+        );
+        self.assert_owner();
+    }
+
+    /// Carries out a confirmed or timelocked [AdminAction]
+    fn internal_apply(&mut self, action: AdminAction) {
+        match action {
+            AdminAction::ClearAll { limit } => {
+                self.internal_clear(limit);
+            }
+            AdminAction::SetConfig { config } => {
+                assert_history_depth(config.history_depth);
+                require!(
+                    config.listing == self.config.listing || self.pair_count == 0,
+                    KvError::InvalidArgument(
+                        "Listing can only be changed while the store is empty".to_string()
+                    )
+                );
+                require!(
+                    config.key_normalization == self.config.key_normalization
+                        || (self.pair_count == 0 && self.collection_count == 0),
+                    KvError::InvalidArgument(
+                        "Key normalization can only be changed while no keys are stored"
+                            .to_string()
+                    )
+                );
+                self.config = config;
+            }
+            AdminAction::SetMultisig { multisig } => {
+                multisig.assert_valid();
+                self.acl.get_mut().multisig = Some(multisig);
+            }
+            AdminAction::Upgrade { code } => {
+                internal_upgrade(code);
+            }
+        }
+    }
+
+    fn assert_multisig_member(&self) {
+        let caller = env::predecessor_account_id();
+        require!(
+            self.acl
+                .get()
+                .multisig
+                .as_ref()
+                .is_some_and(|multisig| multisig.members.contains(&caller)),
+            KvError::Unauthorized("Only multisig admins can call this method".to_string())
+        );
+    }
+
+    /// Proposal `id`, panicking if it does not exist or has expired
+    fn internal_open_proposal(&self, id: u64) -> Proposal {
+        let proposal = self.proposals.get(&id).unwrap_or_else(|| {
+            KvError::NotFound(format!("Proposal {} does not exist", id)).panic()
+        });
+        require!(
+            env::block_timestamp() < proposal.expires_at.0,
+            KvError::Expired(format!("Proposal {} has expired", id))
+        );
+        proposal
+    }
+    /// Panics unless the caller is the owner or holds [Role::Admin].
+    fn assert_owner(&self) {
+        let caller = env::predecessor_account_id();
+        require!(
+            caller == self.owner_id || self.internal_has_role(&caller, Role::Admin),
+            KvError::Unauthorized("Only the owner can call this method".to_string())
+        );
+    }
+
+    /// Panics unless the caller is the owner itself, without accepting admins.
+    fn assert_only_owner(&self) {
+        require!(
+            env::predecessor_account_id() == self.owner_id,
+            KvError::Unauthorized("Only the owner can call this method".to_string())
+        );
+    }
+
+    /// Only the owner manages admins; admins manage the other roles.
+    fn assert_role_manager(&self, role: Role) {
+        if role == Role::Admin {
+            require!(
+                env::predecessor_account_id() == self.owner_id,
+                KvError::Unauthorized("Only the owner can manage admins".to_string())
+            );
+        } else {
+            self.assert_owner();
+        }
+    }
+
+    fn assert_not_paused(&self) {
+        self.check_not_paused()
+            .unwrap_or_else(|error| error.panic());
+    }
+
+    fn check_not_paused(&self) -> Result<(), KvError> {
+        ensure!(!self.maintenance, KvError::Maintenance);
+        ensure!(!self.paused, KvError::Paused("The contract".to_string()));
+        Ok(())
+    }
+
+    fn assert_not_in_maintenance(&self) {
+        require!(!self.maintenance, KvError::Maintenance);
+    }
+
+    /// Panics if the method group `flag`, described by `name`, is paused.
+    fn assert_enabled(&self, flag: u64, name: &str) {
+        self.check_enabled(flag, name)
+            .unwrap_or_else(|error| error.panic());
+    }
+
+    fn check_enabled(&self, flag: u64, name: &str) -> Result<(), KvError> {
+        ensure!(
+            self.paused_methods & flag == 0,
+            KvError::Paused(name.to_string())
+        );
+        Ok(())
+    }
+
+    /// Panics unless `signature` is `account`'s ed25519 signature of `message`, made with its
+    /// [set_relay_key] key or, for an implicit account, its own key.
+    fn assert_signed_by(&self, account: &AccountId, message: &[u8], signature: &Base64VecU8) {
+        let key = self
+            .relay_keys
+            .get(account)
+            .or_else(|| implicit_account_key(account))
+            .unwrap_or_else(|| KvError::NotFound(format!("{} has no relay key", account)).panic());
+        require!(
+            verify_ed25519(&key, message, &signature.0),
+            KvError::Unauthorized(format!("Invalid signature for {}", account))
+        );
+    }
+
+    fn assert_moderator(&self) {
+        let caller = env::predecessor_account_id();
+        require!(
+            caller == self.owner_id
+                || self.internal_has_role(&caller, Role::Admin)
+                || self.internal_has_role(&caller, Role::Moderator),
+            KvError::Unauthorized("Only the owner or a moderator can call this method".to_string())
+        );
+    }
+
+    /// Panics if the caller is blacklisted; guards every mutating method and reads of
+    /// restricted data.
+    fn assert_not_blacklisted(&self) {
+        self.check_not_blacklisted()
+            .unwrap_or_else(|error| error.panic());
+    }
+
+    fn check_not_blacklisted(&self) -> Result<(), KvError> {
+        let caller = self.internal_actor();
+        ensure!(
+            !self.acl.get().blacklist.contains(&caller),
+            KvError::Unauthorized(format!("Account '{}' is blacklisted", caller))
+        );
+        Ok(())
+    }
+
+    /// Panics if the contract is paused, the caller is blacklisted, holds [Role::Reader], which
+    /// restricts an account to reads, or is missing from the writer allowlist while it is
+    /// enforced.
+    fn assert_can_write(&self) {
+        self.check_can_write().unwrap_or_else(|error| error.panic());
+    }
+
+    fn check_can_write(&self) -> Result<(), KvError> {
+        self.check_not_paused()?;
+        self.check_not_blacklisted()?;
+        let caller = self.internal_actor();
+        ensure!(
+            !self.internal_has_role(&caller, Role::Reader),
+            KvError::Unauthorized(format!("Account '{}' is restricted to reads", caller))
+        );
+        ensure!(
+            !self.config.allowlist_only
+                || caller == self.owner_id
+                || self.acl.get().writers.contains(&caller),
+            KvError::Unauthorized(format!(
+                "Account '{}' is not on the writer allowlist",
+                caller
+            ))
+        );
+        Ok(())
+    }
+
+    /// Account the current write is attributed to: the author of a relayed write, otherwise
+    /// the predecessor.
+    fn internal_actor(&self) -> AccountId {
+        self.acting_author
+            .clone()
+            .unwrap_or_else(env::predecessor_account_id)
+    }
+
+    fn internal_has_role(&self, account_id: &str, role: Role) -> bool {
+        self.acl
+            .get()
+            .roles
+            .get(&account_id.to_string())
+            .is_some_and(|roles| roles.contains(&role))
+    }
+
+    /// Panics unless the caller is the contract owner or the owner of the key described by `meta`.
+    fn assert_key_owner(&self, meta: &EntryMeta, k: &str, action: &str) {
+        let caller = env::predecessor_account_id();
+        require!(
+            caller == meta.created_by || caller == self.owner_id,
+            KvError::Unauthorized(format!(
+                "Only the owner or the key owner can {} '{}'",
+                action, k
+            ))
+        );
+    }
+
+    /// Panics unless the caller is the owner, the account that created the key described by
+    /// `meta`, or holds [Role::Admin] or [Role::Writer].
+    fn assert_owner_or_key_owner(&self, meta: &EntryMeta, k: &str, action: &str) {
+        self.check_owner_or_key_owner(meta, k, action)
+            .unwrap_or_else(|error| error.panic());
+    }
+
+    fn check_owner_or_key_owner(
+        &self,
+        meta: &EntryMeta,
+        k: &str,
+        action: &str,
+    ) -> Result<(), KvError> {
+        self.check_can_write()?;
+        let caller = self.internal_actor();
+        ensure!(
+            caller == self.owner_id
+                || caller == meta.created_by
+                || self.internal_has_role(&caller, Role::Admin)
+                || self.internal_has_role(&caller, Role::Writer),
+            KvError::Unauthorized(format!(
+                "Only the owner or the key owner can {} '{}'",
+                action, k
+            ))
+        );
+        Ok(())
+    }
+
+    /// `k` as the configured [KeyNormalization] stores it
+    fn normalize_key(&self, k: String) -> String {
+        self.config.key_normalization.apply(k)
+    }
+
+    fn assert_key_size(&self, k: &str) {
+        self.check_key_size(k).unwrap_or_else(|error| error.panic());
+    }
+
+    fn check_key_size(&self, k: &str) -> Result<(), KvError> {
+        ensure!(
+            k.len() as u64 <= self.config.size_limits.max_key_len,
+            KvError::KeyTooLarge {
+                len: k.len() as u64,
+                max: self.config.size_limits.max_key_len,
+            }
+        );
+        Ok(())
+    }
+
+    fn assert_value_size(&self, k: &str, v: &Value) {
+        self.check_value_size(k, v)
+            .unwrap_or_else(|error| error.panic());
+    }
+
+    fn check_value_size(&self, k: &str, v: &Value) -> Result<(), KvError> {
+        let len = v.byte_len();
+        ensure!(
+            len as u64 <= self.config.size_limits.max_value_len,
+            KvError::ValueTooLarge(format!(
+                "Value of '{}' is {} bytes, exceeding the limit of {}",
+                k, len, self.config.size_limits.max_value_len
+            ))
+        );
+        Ok(())
+    }
+
+    fn check_value_unique(
+        &self,
+        k: &String,
+        v: &Value,
+        moved_from: Option<&String>,
+    ) -> Result<(), KvError> {
+        if let Some(keys) = self
+            .indexes
+            .get()
+            .value_index
+            .get(&env::sha256(v.text().as_bytes()))
+        {
+            // The other key is not named, as it may be unlisted or premium
+            ensure!(
+                !keys.iter().any(|other| {
+                    other != *k && Some(&other) != moved_from && !self.is_hidden(&other)
+                }),
+                KvError::InvalidArgument("Value already exists".to_string())
+            );
+        }
+        Ok(())
+    }
+
+    /// True if `k` is stored but expired or soft deleted
+    fn is_hidden(&self, k: &String) -> bool {
+        match self.meta.get(k) {
+            Some(meta) => !meta.is_live(),
+            None => false,
+        }
+    }
+
+    /// True if a live pair is stored under the already normalized `k`
+    fn internal_contains_key(&self, k: &String) -> bool {
+        // Checks for the storage entry without reading (and deserializing) the value itself
+        self.pairs.contains_key(k) && !self.is_hidden(k)
+    }
+
+    /// Metadata of `k`, treating expired and soft-deleted entries as absent
+    fn internal_live_meta(&self, k: &String) -> Option<EntryMeta> {
+        self.meta.get(k).filter(|meta| meta.is_live())
+    }
+
+    /// False for hidden, unlisted and premium entries, which listings leave out
+    fn is_listed(&self, k: &String) -> bool {
+        match self.meta.get(k) {
+            Some(meta) => meta.is_live() && !meta.unlisted && meta.price.is_none(),
+            None => true,
+        }
+    }
+
+    /// Value under `k` as served to anonymous readers, treating unlisted and premium entries as
+    /// absent
+    fn internal_get_listed(&self, k: &String) -> Option<Value> {
+        if !self.is_listed(k) {
+            return None;
+        }
+        self.internal_value(k)
+    }
+
+    /// Value under `k` as served to the caller, who must be allowed to read it if it is
+    /// unlisted or premium. Blacklisted callers are always refused.
+    fn internal_read(&self, k: &String) -> Option<Value> {
+        self.assert_not_blacklisted();
+        let meta = self.internal_live_meta(k)?;
+        if meta.unlisted || meta.price.is_some() {
+            let caller = env::predecessor_account_id();
+            let allowed = caller == meta.created_by
+                || caller == self.owner_id
+                || meta.readers.contains(&caller);
+            require!(
+                allowed || meta.unlisted,
+                KvError::Unauthorized(format!(
+                    "'{}' is a premium entry, read it with read_paid",
+                    k
+                ))
+            );
+            require!(
+                allowed,
+                KvError::Unauthorized(format!("Only designated readers can read '{}'", k))
+            );
+        }
+        self.internal_value(k)
+    }
+
+    /// Writes `v` under `k` and returns the previous live value. Metadata of live entries is
+    /// updated in place, while a hidden entry is replaced by a fresh one.
+    fn internal_insert(&mut self, k: &String, v: &Value) -> Option<Value> {
+        self.assert_can_insert(k, v, None);
+        self.assert_not_gated(k);
+        self.writes_in_call += 1;
+        self.internal_put(k, v)
+    }
+
+    /// Checks of [internal_insert] that the caller may write `v` under `k`; `moved_from` is the
+    /// key a renamed value still sits under, which the unique-values check ignores.
+    fn assert_can_insert(&self, k: &String, v: &Value, moved_from: Option<&String>) {
+        self.check_can_insert(k, v, moved_from)
+            .unwrap_or_else(|error| error.panic());
+    }
+
+    fn check_can_insert(
+        &self,
+        k: &String,
+        v: &Value,
+        moved_from: Option<&String>,
+    ) -> Result<(), KvError> {
+        self.check_can_update(k)?;
+        self.check_key_size(k)?;
+        self.check_value_size(k, v)?;
+        if self.config.unique_values {
+            self.check_value_unique(k, v, moved_from)?;
+        }
+        Ok(())
+    }
+
+    /// Checks of [assert_can_insert] that do not depend on the value: writes are enabled and
+    /// the caller may write `k`. Run before a write inspects the current value of `k`, so the
+    /// outcome cannot reveal it to accounts that may not change it.
+    fn assert_can_update(&self, k: &String) {
+        self.check_can_update(k)
+            .unwrap_or_else(|error| error.panic());
+    }
+
+    fn check_can_update(&self, k: &String) -> Result<(), KvError> {
+        self.check_enabled(PAUSE_WRITE, "write")?;
+        self.check_can_write()?;
+        match self.meta.get(k) {
+            Some(meta) if meta.is_live() && meta.write_grants.contains(&self.internal_actor()) => {
+                Ok(())
+            }
+            Some(meta) if meta.is_live() => self.check_owner_or_key_owner(&meta, k, "update"),
+            // A soft-deleted key stays with its owner, who may still undelete it, until purged
+            Some(meta) if meta.deleted_at.is_some() => {
+                self.check_owner_or_key_owner(&meta, k, "update")
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Panics if `k` is in the NFT-gated namespace, which only [gated_write] may write to
+    fn assert_not_gated(&self, k: &str) {
+        if let Some(gate) = &self.nft_gate {
+            require!(
+                !k.starts_with(&gate.prefix),
+                KvError::Unauthorized(format!(
+                    "Writes under '{}' require an NFT of {}, use gated_write",
+                    gate.prefix, gate.nft_contract_id
+                ))
+            );
+        }
+    }
+
+    /// Unchecked part of [internal_insert], also used to roll back failed mirror writes.
+    fn internal_put(&mut self, k: &String, v: &Value) -> Option<Value> {
+        self.internal_snapshot(k);
+        // A hidden entry is replaced by a fresh one, whose storage may have another payer
+        if self.is_hidden(k) {
+            self.internal_remove(k);
+        }
+        let initial_storage = env::storage_usage();
+        let old_meta = self.meta.get(k);
+        let sweep_before = old_meta.as_ref().and_then(|meta| meta.sweep_at());
+        let previous = self.internal_take_value(k);
+        self.pairs.insert(k, &StoredValue::new(k, v));
+        match &previous {
+            Some(old) => self.internal_unindex_value(k, old),
+            None => self.internal_list_key(k),
+        }
+        self.internal_index_value(k, v);
+        let mut meta = match (old_meta, &previous) {
+            (Some(mut meta), Some(old)) => {
+                self.internal_push_revision(k, &mut meta, old);
+                meta
+            }
+            _ => EntryMeta::new(self.internal_actor(), self.internal_payer()),
+        };
+        meta.version += 1;
+        meta.updated_at = env::block_timestamp();
+        meta.updated_height = env::block_index();
+        meta.updated_by = self.internal_actor();
+        self.internal_write_meta(k, sweep_before, &meta);
+        self.internal_record(
+            if previous.is_some() {
+                "kv_update"
+            } else {
+                "kv_create"
+            },
+            k,
+            previous.as_ref(),
+            Some(v),
+            !meta.unlisted && meta.price.is_none(),
+        );
+        self.internal_track_payer(&meta.payer, initial_storage);
+        previous
+    }
+
+    /// Emits the `event` for `k` and appends it to the key's audit log.
+    fn internal_record(
+        &mut self,
+        event: &str,
+        k: &String,
+        old: Option<&Value>,
+        new: Option<&Value>,
+        reveal: bool,
+    ) {
+        let actor = self.internal_actor();
+        emit_kv_event(event, k, &actor, old, new, reveal);
+        let log = self.audit_log.get_mut();
+        let counter = match event {
+            "kv_create" => &mut log.stats.creates,
+            "kv_update" => &mut log.stats.updates,
+            _ => &mut log.stats.deletes,
+        };
+        counter.0 += 1;
+        if log.known_writers.insert(&actor) {
+            log.stats.writers.0 += 1;
+        }
+        let hash = |value: &Value| Base64VecU8(env::sha256(value.text().as_bytes()));
+        let entry = AuditEntry {
+            operation: event.trim_start_matches("kv_").to_string(),
+            actor: actor.clone(),
+            timestamp: U64(env::block_timestamp()),
+            block_height: U64(env::block_index()),
+            old_hash: old.map(hash),
+            new_hash: new.map(hash),
+        };
+        let seq = log.audit_lengths.get(k).unwrap_or(0);
+        log.changes.push(&Change {
+            seq: U64(log.changes.len() + 1),
+            operation: entry.operation.clone(),
+            key: k.clone(),
+            actor: entry.actor.clone(),
+            timestamp: entry.timestamp,
+            block_height: entry.block_height,
+        });
+        log.audit.insert(&(k.clone(), seq), &entry);
+        log.audit_lengths.insert(k, &(seq + 1));
+        if reveal {
+            self.internal_notify(k, new);
+        }
+    }
+
+    /// Calls `on_kv_change` on the subscribers of every prefix of `k`, up to
+    /// [MAX_NOTIFICATIONS_PER_WRITE]. Notifications have no callback, so a failing subscriber
+    /// cannot affect the write.
+    fn internal_notify(&self, k: &str, new: Option<&Value>) {
+        if self.subscribed_prefix_lens.is_empty() {
+            return;
+        }
+        let args = near_sdk::serde_json::json!({
+            "key": k,
+            "value": new.map(|value| value.text()),
+        })
+        .to_string()
+        .into_bytes();
+        self.subscribed_prefix_lens
+            .keys()
+            .map(|len| *len as usize)
+            .take_while(|len| *len <= k.len())
+            .filter(|len| k.is_char_boundary(*len))
+            .filter_map(|len| self.subscribers.get(&k[..len].to_string()))
+            .flatten()
+            .take(MAX_NOTIFICATIONS_PER_WRITE)
+            .for_each(|subscriber| {
+                Promise::new(subscriber).function_call(
+                    b"on_kv_change".to_vec(),
+                    args.clone(),
+                    0,
+                    SUBSCRIBER_GAS,
+                );
+            });
+    }
+
+    /// Value and metadata stored under `k`, hidden or not
+    fn internal_entry(&self, k: &String) -> Option<MirroredEntry> {
+        Some(MirroredEntry {
+            value: self.internal_value(k)?,
+            meta: self.meta.get(k)?,
+        })
+    }
+
+    /// Keeps the entry of `k` as it was before the current call changed it, for the batch
+    /// [internal_mirror] replicates; a no-op without a mirror or once `k` is kept.
+    fn internal_snapshot(&mut self, k: &String) {
+        if self.mirror_id.is_some() && !self.mirror_changes.contains_key(k) {
+            let entry = self.internal_entry(k);
+            self.mirror_changes.insert(k.clone(), entry);
+        }
+    }
+
+    /// Stores `meta` for `k` after a change that leaves its sweep time as it is.
+    fn internal_update_meta(&mut self, k: &String, meta: &EntryMeta) {
+        self.internal_snapshot(k);
+        self.meta.insert(k, meta);
+    }
+
+    /// Replaces whatever is stored under `k`, hidden or not, with `entry`, value and metadata
+    /// as they are, keeping the indexes in step; removes it when `None`. Revisions not stored
+    /// here are left out of the history `entry` retains. Used to apply replicated entries and
+    /// to roll back the ones the mirror rejected.
+    fn internal_set_entry(&mut self, k: &String, entry: Option<MirroredEntry>) {
+        self.internal_snapshot(k);
+        let initial_storage = env::storage_usage();
+        let old_meta = self.meta.get(k);
+        let old = self.internal_take_value(k);
+        if let Some(value) = &old {
+            self.internal_unindex_value(k, value);
+        }
+        let sweep_before = old_meta.as_ref().and_then(|meta| meta.sweep_at());
+        if let Some(meta) = &old_meta {
+            for tag in meta.tags.iter() {
+                self.internal_untag(k, tag);
+            }
+        }
+        let (new, meta) = match entry {
+            Some(MirroredEntry { value, mut meta }) => {
+                let mut oldest = meta.version;
+                while oldest > meta.oldest_version
+                    && self.history.get(&(k.clone(), oldest - 1)).is_some()
+                {
+                    oldest -= 1;
+                }
+                meta.oldest_version = oldest;
+                if let Some(current) = &old_meta {
+                    for version in current.oldest_version..current.version {
+                        if version < meta.oldest_version || version >= meta.version {
+                            self.history.remove(&(k.clone(), version));
+                        }
+                    }
+                }
+                self.pairs.insert(k, &StoredValue::new(k, &value));
+                if old.is_none() {
+                    self.internal_list_key(k);
+                }
+                self.internal_index_value(k, &value);
+                for tag in meta.tags.iter() {
+                    self.internal_tag(k, tag);
+                }
+                self.internal_write_meta(k, sweep_before, &meta);
+                (Some(value), meta)
+            }
+            None => {
+                let Some(meta) = old_meta else {
+                    return;
+                };
+                self.internal_unlist_key(k);
+                self.meta.remove(k);
+                if let Some(at) = sweep_before {
+                    self.indexes.get_mut().expiry_index.remove(&(at, k.clone()));
+                }
+                self.internal_drop_history(k, &meta);
+                (None, meta)
+            }
+        };
+        if old != new {
+            let event = match (&old, &new) {
+                (None, _) => "kv_create",
+                (_, None) => "kv_delete",
+                _ => "kv_update",
+            };
+            let reveal = !meta.unlisted && meta.price.is_none();
+            self.internal_record(event, k, old.as_ref(), new.as_ref(), reveal);
+        }
+        self.internal_track_payer(&meta.payer, initial_storage);
+    }
+
+    /// Replicates the entries the current call changed to the mirror contract in one
+    /// [mirror_apply] call, with [on_mirror] as the callback. Panics if the gas left does not
+    /// cover the batch.
+    fn internal_mirror(&mut self) {
+        let changes: Vec<MirrorChange> = std::mem::take(&mut self.mirror_changes)
+            .into_iter()
+            .map(|(key, previous)| MirrorChange {
+                entry: self.internal_entry(&key),
+                key,
+                previous,
+            })
+            .filter(|change| change.previous != change.entry)
+            .collect();
+        let Some(mirror_id) = self.mirror_id.clone().filter(|_| !changes.is_empty()) else {
+            return;
+        };
+        let entry_gas = MIRROR_GAS_PER_ENTRY * changes.len() as Gas;
+        let gas = MIRROR_GAS + entry_gas;
+        let callback_gas = MIRROR_CALLBACK_GAS + entry_gas;
+        let left = env::prepaid_gas() - env::used_gas();
+        require!(
+            gas + callback_gas <= left,
+            KvError::LimitExceeded(format!(
+                "Mirroring {} changed keys needs {} gas, but only {} is left",
+                changes.len(),
+                gas + callback_gas,
+                left
+            ))
+        );
+        let entries: Vec<(&String, &Option<MirroredEntry>)> = changes
+            .iter()
+            .map(|change| (&change.key, &change.entry))
+            .collect();
+        Promise::new(mirror_id)
+            .function_call(
+                b"mirror_apply".to_vec(),
+                entries.try_to_vec().unwrap(),
+                0,
+                gas,
+            )
+            .then(Promise::new(env::current_account_id()).function_call(
+                b"on_mirror".to_vec(),
+                changes.try_to_vec().unwrap(),
+                0,
+                callback_gas,
+            ));
+    }
+
+    fn internal_index_value(&mut self, k: &String, v: &Value) {
+        let text = v.text();
+        let hash = env::sha256(text.as_bytes());
+        let mut keys = self
+            .indexes
+            .get()
+            .value_index
+            .get(&hash)
+            .unwrap_or_else(|| UnorderedSet::new(nested_prefix(VALUE_KEYS_PREFIX, &text)));
+        keys.insert(k);
+        self.indexes.get_mut().value_index.insert(&hash, &keys);
+    }
+
+    fn internal_unindex_value(&mut self, k: &String, v: &Value) {
+        let hash = env::sha256(v.text().as_bytes());
+        if let Some(mut keys) = self.indexes.get().value_index.get(&hash) {
+            keys.remove(k);
+            if keys.is_empty() {
+                self.indexes.get_mut().value_index.remove(&hash);
+            } else {
+                self.indexes.get_mut().value_index.insert(&hash, &keys);
+            }
+        }
+    }
+
+    /// Attaches `tag` to the live key `k`; returns false if it was already attached.
+    fn internal_add_tag(&mut self, k: &String, tag: &String) -> bool {
+        self.assert_key_size(tag);
+        let mut meta = self.internal_live_meta(k).unwrap();
+        if meta.tags.contains(tag) {
+            return false;
+        }
+        require!(
+            meta.tags.len() < MAX_TAGS_PER_KEY,
+            KvError::LimitExceeded(format!(
+                "Key '{}' already has the maximum of {} tags",
+                k, MAX_TAGS_PER_KEY
+            ))
+        );
+        meta.tags.push(tag.clone());
+        self.internal_update_meta(k, &meta);
+        self.internal_tag(k, tag);
+        true
+    }
+
+    fn internal_tag(&mut self, k: &String, tag: &String) {
+        let mut keys = self
+            .indexes
+            .get()
+            .tag_index
+            .get(tag)
+            .unwrap_or_else(|| UnorderedSet::new(nested_prefix(TAG_KEYS_PREFIX, tag)));
+        keys.insert(k);
+        self.indexes.get_mut().tag_index.insert(tag, &keys);
+    }
+
+    fn internal_untag(&mut self, k: &String, tag: &String) {
+        if let Some(mut keys) = self.indexes.get().tag_index.get(tag) {
+            keys.remove(k);
+            if keys.is_empty() {
+                self.indexes.get_mut().tag_index.remove(tag);
+            } else {
+                self.indexes.get_mut().tag_index.insert(tag, &keys);
+            }
+        }
+    }
+
+    /// Stores `meta` for `k`, moving its entry in `expiry_index` if the sweep time changed.
+    fn internal_write_meta(&mut self, k: &String, sweep_before: Option<u64>, meta: &EntryMeta) {
+        self.internal_snapshot(k);
+        let sweep_after = meta.sweep_at();
+        if sweep_before != sweep_after {
+            if let Some(at) = sweep_before {
+                self.indexes.get_mut().expiry_index.remove(&(at, k.clone()));
+            }
+            if let Some(at) = sweep_after {
+                self.indexes
+                    .get_mut()
+                    .expiry_index
+                    .insert(&(at, k.clone()), &());
+            }
+        }
+        self.meta.insert(k, meta);
+    }
+
+    /// Records `old` as the current version of `k` in the history and trims it to the
+    /// configured depth.
+    fn internal_push_revision(&mut self, k: &str, meta: &mut EntryMeta, old: &Value) {
+        if self.config.history_depth > 0 {
+            let revision = Revision {
+                version: U64(meta.version),
+                value: old.clone(),
+                updated_at: U64(meta.updated_at),
+                updated_by: meta.updated_by.clone(),
+            };
+            self.history
+                .insert(&(k.to_string(), meta.version), &revision);
+        }
+        while meta.oldest_version + self.config.history_depth <= meta.version {
+            self.history.remove(&(k.to_string(), meta.oldest_version));
+            meta.oldest_version += 1;
+        }
+    }
+
+    fn internal_drop_history(&mut self, k: &str, meta: &EntryMeta) {
+        for version in meta.oldest_version..meta.version {
+            self.history.remove(&(k.to_string(), version));
+        }
+    }
+
+    fn internal_set_expiry(&mut self, k: &String, expires_at: Option<u64>) {
+        let mut meta = self.meta.get(k).unwrap();
+        let sweep_before = meta.sweep_at();
+        meta.expires_at = expires_at;
+        self.internal_write_meta(k, sweep_before, &meta);
+    }
+
+    fn internal_counter(&self, k: &String) -> u128 {
+        match self.internal_read(k) {
+            Some(v) => v.text().parse().unwrap_or_else(|_| {
+                KvError::InvalidArgument(format!("Value of '{}' is not a counter", k)).panic()
+            }),
+            None => 0,
+        }
+    }
+
+    /// Deletes `k` as configured: a tombstone when a retention window is set, otherwise a
+    /// hard removal. Returns the value if it was live.
+    fn internal_delete(&mut self, k: &String) -> Option<Value> {
+        self.assert_enabled(PAUSE_DELETE, "delete");
+        self.assert_can_write();
+        let meta = self.internal_live_meta(k);
+        if let Some(meta) = &meta {
+            self.assert_owner_or_key_owner(meta, k, "delete");
+        }
+        let deleted = match self.config.tombstone_retention {
+            Some(retention) => self.internal_tombstone(k, retention.0),
+            None => self.internal_remove(k),
+        };
+        if let (Some(meta), Some(value)) = (&meta, &deleted) {
+            let initial_storage = env::storage_usage();
+            let reveal = !meta.unlisted && meta.price.is_none();
+            self.internal_record("kv_delete", k, Some(value), None, reveal);
+            self.internal_track_payer(&meta.payer, initial_storage);
+        }
+        deleted
+    }
+
+    /// Soft-deletes the live key `k`, keeping it restorable for `retention` nanoseconds.
+    fn internal_tombstone(&mut self, k: &String, retention: u64) -> Option<Value> {
+        let mut meta = self.internal_live_meta(k)?;
+        let sweep_before = meta.sweep_at();
+        let now = env::block_timestamp();
+        meta.deleted_at = Some(now);
+        meta.restorable_until = Some(now.saturating_add(retention));
+        self.internal_write_meta(k, sweep_before, &meta);
+        self.internal_value(k)
+    }
+
+    /// Value stored under `k`, hidden or not
+    fn internal_value(&self, k: &String) -> Option<Value> {
+        self.pairs.get(k).map(StoredValue::get)
+    }
+
+    /// Removes the value stored under `k` and returns it
+    fn internal_take_value(&mut self, k: &String) -> Option<Value> {
+        self.pairs.remove(k).map(StoredValue::take)
+    }
+
+    /// Counts the new key `k` and adds it to the enumeration indexes while listing is enabled.
+    fn internal_list_key(&mut self, k: &String) {
+        self.pair_count += 1;
+        if self.config.listing {
+            let shard = key_shard(k);
+            let mut keys = self.indexes.get().key_list.get(&shard).unwrap_or_else(|| {
+                UnorderedSet::new(nested_prefix(KEY_SHARD_PREFIX, &shard.to_string()))
+            });
+            keys.insert(k);
+            self.indexes.get_mut().key_list.insert(&shard, &keys);
+            self.indexes.get_mut().index.insert(k, &());
+        }
+    }
+
+    /// Reverse of [internal_list_key] for a key removed from `pairs`.
+    fn internal_unlist_key(&mut self, k: &String) {
+        self.pair_count -= 1;
+        if self.config.listing {
+            let shard = key_shard(k);
+            let mut keys = self.indexes.get().key_list.get(&shard).unwrap();
+            keys.remove(k);
+            if keys.is_empty() {
+                self.indexes.get_mut().key_list.remove(&shard);
+            } else {
+                self.indexes.get_mut().key_list.insert(&shard, &keys);
+            }
+            self.indexes.get_mut().index.remove(k);
+        }
+    }
+
+    /// Up to `limit` keys from position `from_index` of [KeyValue::key_list], counting through
+    /// the shards in order.
+    fn internal_key_page(&self, from_index: u64, limit: u64) -> Vec<String> {
+        let mut skip = from_index;
+        let mut page = vec![];
+        for shard in 0..KEY_LIST_SHARDS {
+            if page.len() as u64 >= limit {
+                break;
+            }
+            let Some(keys) = self.indexes.get().key_list.get(&shard) else {
+                continue;
+            };
+            if skip >= keys.len() {
+                skip -= keys.len();
+                continue;
+            }
+            let elements = keys.as_vector();
+            let wanted = limit - page.len() as u64;
+            page.extend(
+                (skip..keys.len())
+                    .take(wanted as usize)
+                    .map(|i| elements.get(i).unwrap()),
+            );
+            skip = 0;
+        }
+        page
+    }
+
+    fn assert_listing(&self) {
+        require!(
+            self.config.listing,
+            KvError::Unsupported("Listing is disabled on this contract".to_string())
+        );
+    }
+
+    /// Physically removes `k` and returns its value if it was still live.
+    fn internal_remove(&mut self, k: &String) -> Option<Value> {
+        self.internal_snapshot(k);
+        let initial_storage = env::storage_usage();
+        let hidden = self.is_hidden(k);
+        let removed = self.internal_take_value(k);
+        if let Some(value) = &removed {
+            self.internal_unindex_value(k, value);
+            self.internal_unlist_key(k);
+            let meta = self.meta.remove(k).unwrap();
+            if let Some(at) = meta.sweep_at() {
+                self.indexes.get_mut().expiry_index.remove(&(at, k.clone()));
+            }
+            self.internal_drop_history(k, &meta);
+            for tag in meta.tags.iter() {
+                self.internal_untag(k, tag);
+            }
+            self.internal_track_payer(&meta.payer, initial_storage);
+        }
+        removed.filter(|_| !hidden)
+    }
+}
+
+// 3. Tests
+//
+// Writing unit test are imprtant because smart contracts are often
+// immutable and sometimes responsible for managing sums of money.
+//
+// Writing good unit tests is a key component of secure and reliable
+// smart contract development.
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::get_logs;
+    use near_sdk::MockedBlockchain;
+    use near_sdk::{testing_env, VMContext};
+    use std::convert::TryInto;
+
+    /// Fluent builder of the mocked blockchain context, e.g.
+    /// `TestContext::new().predecessor("alice_near").deposit(1).set()`. Unless changed, the
+    /// contract runs as "alice_near", the owner [new_contract] sets up, and is called by
+    /// "carol_near" with 1 NEAR attached.
+    #[derive(Clone)]
+    struct TestContext {
+        context: VMContext,
+        // Successful results by their value, failed ones as None
+        promise_results: Vec<Option<Vec<u8>>>,
+    }
+
+    impl TestContext {
+        fn new() -> Self {
+            Self {
+                context: VMContext {
+                    current_account_id: "alice_near".to_string(),
+                    signer_account_id: "bob_near".to_string(),
+                    signer_account_pk: vec![0, 1, 2],
+                    predecessor_account_id: "carol_near".to_string(),
+                    input: vec![],
+                    block_index: 0,
+                    block_timestamp: 0,
+                    account_balance: 10u128.pow(30),
+                    account_locked_balance: 0,
+                    storage_usage: 0,
+                    attached_deposit: 10u128.pow(24),
+                    prepaid_gas: 10u64.pow(18),
+                    random_seed: vec![0, 1, 2],
+                    is_view: false,
+                    output_data_receivers: vec![],
+                    epoch_height: 0,
+                },
+                promise_results: vec![],
+            }
+        }
+
+        fn current(&mut self, account_id: &str) -> &mut Self {
+            self.context.current_account_id = account_id.to_string();
+            self
+        }
+
+        fn predecessor(&mut self, account_id: &str) -> &mut Self {
+            self.context.predecessor_account_id = account_id.to_string();
+            self
+        }
+
+        fn deposit(&mut self, amount: Balance) -> &mut Self {
+            self.context.attached_deposit = amount;
+            self
+        }
+
+        fn timestamp(&mut self, ns: u64) -> &mut Self {
+            self.context.block_timestamp = ns;
+            self
+        }
+
+        fn height(&mut self, height: u64) -> &mut Self {
+            self.context.block_index = height;
+            self
+        }
+
+        /// Moves the block timestamp `ns` nanoseconds forward.
+        fn advance_time(&mut self, ns: u64) -> &mut Self {
+            self.context.block_timestamp += ns;
+            self
+        }
+
+        /// Moves the block height `blocks` blocks forward; the timestamp is left as it is.
+        fn advance_blocks(&mut self, blocks: u64) -> &mut Self {
+            self.context.block_index += blocks;
+            self
+        }
+
+        fn view(&mut self) -> &mut Self {
+            self.context.is_view = true;
+            self
+        }
+
+        fn prepaid_gas(&mut self, gas: Gas) -> &mut Self {
+            self.context.prepaid_gas = gas;
+            self
+        }
+
+        /// Results of the promises the next call is a callback of
+        fn promise_results(&mut self, results: Vec<PromiseResult>) -> &mut Self {
+            self.promise_results = results
+                .into_iter()
+                .map(|result| match result {
+                    PromiseResult::Successful(value) => Some(value),
+                    PromiseResult::Failed => None,
+                    PromiseResult::NotReady => panic!("Callbacks only run on ready promises"),
+                })
+                .collect();
+            self
+        }
+
+        /// Installs the context, keeping the storage (and storage usage) of the context it
+        /// replaces; `env::take_blockchain_interface()` first to start from empty storage.
+        fn set(&self) {
+            let mut context = self.context.clone();
+            if let Some(interface) = env::take_blockchain_interface() {
+                env::set_blockchain_interface(interface);
+                context.storage_usage = env::storage_usage();
+            }
+            testing_env!(
+                context,
+                Default::default(),
+                Default::default(),
+                Default::default(),
+                self.promise_results
+                    .iter()
+                    .map(|result| match result {
+                        Some(value) => PromiseResult::Successful(value.clone()),
+                        None => PromiseResult::Failed,
+                    })
+                    .collect()
+            );
+        }
+    }
+
+    fn new_contract() -> KeyValue {
+        KeyValue::new("alice_near".try_into().unwrap(), None)
+    }
+
+    /// Receiver, method and arguments of each function call created so far. Read from the
+    /// Debug form of the receipts, since the mocked ones keep their fields private and cannot
+    /// serialize Borsh arguments.
+    fn created_calls() -> Vec<(String, String, Vec<u8>)> {
+        let bytes = |list: &str| -> Vec<u8> {
+            list.split(", ")
+                .filter(|byte| !byte.is_empty())
+                .map(|byte| byte.parse().unwrap())
+                .collect()
+        };
+        near_sdk::test_utils::get_created_receipts()
+            .iter()
+            .flat_map(|receipt| {
+                let debug = format!("{:?}", receipt);
+                let receiver = debug.split("receiver_id: \"").nth(1).unwrap();
+                let receiver = receiver.split('"').next().unwrap().to_string();
+                debug
+                    .split("method_name: [")
+                    .skip(1)
+                    .map(|call| {
+                        let (method, rest) = call.split_once(']').unwrap();
+                        let args = rest.split("args: [").nth(1).unwrap();
+                        let args = args.split(']').next().unwrap();
+                        let method = String::from_utf8(bytes(method)).unwrap();
+                        (receiver.clone(), method, bytes(args))
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    // Test 1
+    //
+    /// Test for [create_update] and [read]
+    #[test]
+    fn create_read_pair() {
+        TestContext::new().set();
+        let mut contract = new_contract();
+        contract.create_update("first_key".to_string(), "hello".to_string());
+
+        assert_eq!(
+            "hello".to_string(),
+            contract.read("first_key".to_string()).unwrap()
+        ); // expect("hello", "hello")
+    }
+
+    // Test 2
+    //
+    // Test for read non-existent pair
+    #[test]
+    fn read_nonexistent_pair() {
+        let mut context = TestContext::new();
+        context.set();
+        let contract = new_contract();
+        context.view().set();
+        assert_eq!(None, contract.read("first_key".to_string()));
+    }
+
+    // Test 3
+    //
+    // Test for [create_update_many] reporting created vs updated entries
+    #[test]
+    fn create_update_many_pairs() {
+        TestContext::new().set();
+        let mut contract = new_contract();
+        contract.create_update("first_key".to_string(), "hello".to_string());
+        let summary = contract.create_update_many(vec![
+            ("first_key".to_string(), "world".to_string()),
+            ("second_key".to_string(), "near".to_string()),
+            ("third_key".to_string(), "rust".to_string()),
+        ]);
+
+        assert_eq!(
+            BatchWriteSummary {
+                created: 2,
+                updated: 1
+            },
+            summary
+        );
+        assert_eq!(
+            "world".to_string(),
+            contract.read("first_key".to_string()).unwrap()
+        );
+        assert_eq!(
+            "near".to_string(),
+            contract.read("second_key".to_string()).unwrap()
+        );
+    }
+
+    // Test 4
+    //
+    // Test that [create_update_many] rejects batches above [MAX_BATCH_SIZE]
+    #[test]
+    #[should_panic(expected = "Batch size exceeds the limit")]
+    fn create_update_many_over_limit() {
+        TestContext::new().set();
+        let mut contract = new_contract();
+        let pairs = (0..=MAX_BATCH_SIZE)
+            .map(|i| (format!("key_{}", i), "value".to_string()))
+            .collect();
+        contract.create_update_many(pairs);
+    }
+
+    // Test 5
+    //
+    // Test for [read_many] returning values in request order
+    #[test]
+    fn read_many_pairs() {
+        TestContext::new().set();
+        let mut contract = new_contract();
+        contract.create_update("first_key".to_string(), "hello".to_string());
+        contract.create_update("second_key".to_string(), "world".to_string());
+
+        assert_eq!(
+            vec![Some("world".to_string()), None, Some("hello".to_string())],
+            contract.read_many(vec![
+                "second_key".to_string(),
+                "missing_key".to_string(),
+                "first_key".to_string(),
+            ])
+        );
+    }
+
+    // Test 6
+    //
+    // Test for [delete_many] counting only keys that existed
+    #[test]
+    fn delete_many_pairs() {
+        TestContext::new().set();
+        let mut contract = new_contract();
+        contract.create_update("first_key".to_string(), "hello".to_string());
+        contract.create_update("second_key".to_string(), "world".to_string());
+
+        let removed = contract.delete_many(vec![
+            "first_key".to_string(),
+            "missing_key".to_string(),
+            "second_key".to_string(),
+        ]);
+
+        assert_eq!(2, removed);
+        assert_eq!(None, contract.read("first_key".to_string()));
+        assert_eq!(None, contract.read("second_key".to_string()));
+    }
+
+    // Test 7
+    //
+    // Test for [keys] paging through stored keys
+    #[test]
+    fn keys_paginated() {
+        TestContext::new().set();
+        let mut contract = new_contract();
+        for i in 0..5 {
+            contract.create_update(format!("key_{}", i), "value".to_string());
+        }
+
+        // Keys are listed shard by shard, so consecutive pages cover each key exactly once
+        let mut keys = contract.keys(0, 2);
+        assert_eq!(2, keys.len());
+        keys.extend(contract.keys(2, 2));
+        let last = contract.keys(4, 10);
+        assert_eq!(1, last.len());
+        keys.extend(last);
+        keys.sort();
+        assert_eq!(
+            (0..5).map(|i| format!("key_{}", i)).collect::<Vec<_>>(),
+            keys
+        );
+        assert!(contract.keys(5, 10).is_empty());
+    }
+
+    // Test 8
+    //
+    // Test for [entries] paging through stored pairs
+    #[test]
+    fn entries_paginated() {
+        TestContext::new().set();
+        let mut contract = new_contract();
+        contract.create_update("first_key".to_string(), "hello".to_string());
+        contract.create_update("second_key".to_string(), "world".to_string());
+
+        assert_eq!(
+            vec![("second_key".to_string(), "world".to_string())],
+            contract.entries(1, 10)
+        );
+        assert_eq!(2, contract.entries(0, 10).len());
+    }
+
+    // Test 9
+    //
+    // Test for [len] and [is_empty]
+    #[test]
+    fn len_and_is_empty() {
+        TestContext::new().set();
+        let mut contract = new_contract();
+        assert!(contract.is_empty());
+
+        contract.create_update("first_key".to_string(), "hello".to_string());
+        contract.create_update("first_key".to_string(), "world".to_string());
+        contract.create_update("second_key".to_string(), "near".to_string());
+        assert_eq!(2, contract.len());
+        assert!(!contract.is_empty());
+    }
+
+    // Test 10
+    //
+    // Test for [contains_key]
+    #[test]
+    fn contains_key_check() {
+        TestContext::new().set();
+        let mut contract = new_contract();
+        contract.create_update("first_key".to_string(), "hello".to_string());
+
+        assert!(contract.contains_key("first_key".to_string()));
+        assert!(!contract.contains_key("second_key".to_string()));
+
+        contract.delete("first_key".to_string());
+        assert!(!contract.contains_key("first_key".to_string()));
+    }
+
+    // Test 11
+    //
+    // Test for [clear_all] wiping the store across several batches
+    #[test]
+    fn clear_all_in_batches() {
+        TestContext::new().predecessor("alice_near").set();
+        let mut contract = new_contract();
+        for i in 0..5 {
+            contract.create_update(format!("key_{}", i), "value".to_string());
+        }
+
+        assert_eq!(
+            ClearProgress {
+                removed: 3,
+                remaining: 2
+            },
+            contract.clear_all(3)
+        );
+        assert_eq!(
+            ClearProgress {
+                removed: 2,
+                remaining: 0
+            },
+            contract.clear_all(3)
+        );
+        assert!(contract.is_empty());
+    }
+
+    // Test 12
+    //
+    // Test that [clear_all] is restricted to the owner
+    #[test]
+    #[should_panic(expected = "Only the owner can call this method")]
+    fn clear_all_not_owner() {
+        TestContext::new().set();
+        let mut contract = new_contract();
+        contract.clear_all(10);
+    }
+
+    // Test 13
+    //
+    // Test for [keys_with_prefix] and [entries_with_prefix]
+    #[test]
+    fn prefix_scan() {
+        TestContext::new().set();
+        let mut contract = new_contract();
+        contract.create_update("user:1:name".to_string(), "alice".to_string());
+        contract.create_update("post:1".to_string(), "hello".to_string());
+        contract.create_update("user:2:name".to_string(), "bob".to_string());
+        contract.create_update("user:2:age".to_string(), "30".to_string());
+
+        assert_eq!(
+            vec!["user:2:age".to_string(), "user:2:name".to_string()],
+            contract.keys_with_prefix("user:".to_string(), 1, 10)
+        );
+        assert_eq!(
+            vec![("user:1:name".to_string(), "alice".to_string())],
+            contract.entries_with_prefix("user:".to_string(), 0, 1)
+        );
+        assert!(contract
+            .keys_with_prefix("comment:".to_string(), 0, 10)
+            .is_empty());
+    }
+
+    // Test 14
+    //
+    // Test for [range] returning pairs in lexicographic order
+    #[test]
+    fn range_scan() {
+        TestContext::new().set();
+        let mut contract = new_contract();
+        for k in ["d", "b", "a", "c", "e"].iter() {
+            contract.create_update(k.to_string(), k.to_uppercase());
+        }
+        contract.delete("c".to_string());
+
+        assert_eq!(
+            vec![
+                ("b".to_string(), "B".to_string()),
+                ("d".to_string(), "D".to_string())
+            ],
+            contract.range("b".to_string(), "e".to_string(), 10)
+        );
+        assert_eq!(
+            vec![("a".to_string(), "A".to_string())],
+            contract.range("a".to_string(), "z".to_string(), 1)
+        );
+    }
+
+    // Test 15
+    //
+    // Test for [update_if] compare-and-swap semantics
+    #[test]
+    fn update_if_compare_and_swap() {
+        TestContext::new().set();
+        let mut contract = new_contract();
+        contract.create_update("first_key".to_string(), "hello".to_string());
+
+        assert!(!contract.update_if(
+            "first_key".to_string(),
+            "stale".to_string(),
+            "world".to_string()
+        ));
+        assert_eq!(
+            "hello".to_string(),
+            contract.read("first_key".to_string()).unwrap()
+        );
+
+        assert!(contract.update_if(
+            "first_key".to_string(),
+            "hello".to_string(),
+            "world".to_string()
+        ));
+        assert_eq!(
+            "world".to_string(),
+            contract.read("first_key".to_string()).unwrap()
+        );
+
+        assert!(!contract.update_if(
+            "missing_key".to_string(),
+            "hello".to_string(),
+            "world".to_string()
+        ));
+        assert!(!contract.contains_key("missing_key".to_string()));
+    }
+
+    // Test 16
+    //
+    // Test for [create_only] on a new key
+    #[test]
+    fn create_only_new_key() {
+        TestContext::new().set();
+        let mut contract = new_contract();
+        contract.create_only("first_key".to_string(), "hello".to_string());
+        assert_eq!(
+            "hello".to_string(),
+            contract.read("first_key".to_string()).unwrap()
+        );
+    }
+
+    // Test 17
+    //
+    // Test that [create_only] refuses to overwrite an existing key
+    #[test]
+    #[should_panic(expected = "key_exists: Key 'first_key' already exists")]
+    fn create_only_existing_key() {
+        TestContext::new().set();
+        let mut contract = new_contract();
+        contract.create_update("first_key".to_string(), "hello".to_string());
+        contract.create_only("first_key".to_string(), "world".to_string());
+    }
+
+    // Test 18
+    //
+    // Test for [update_only] returning the replaced value
+    #[test]
+    fn update_only_existing_key() {
+        TestContext::new().set();
+        let mut contract = new_contract();
+        contract.create_update("first_key".to_string(), "hello".to_string());
+        assert_eq!(
+            "hello".to_string(),
+            contract.update_only("first_key".to_string(), "world".to_string())
+        );
+        assert_eq!(
+            "world".to_string(),
+            contract.read("first_key".to_string()).unwrap()
+        );
+    }
+
+    // Test 19
+    //
+    // Test that [update_only] refuses to create a new key
+    #[test]
+    #[should_panic(expected = "key_not_found: Key 'first_key' does not exist")]
+    fn update_only_missing_key() {
+        TestContext::new().set();
+        let mut contract = new_contract();
+        contract.update_only("first_key".to_string(), "hello".to_string());
+    }
+
+    // Test 20
+    //
+    // Test that [create_update] returns the value it replaced
+    #[test]
+    fn create_update_returns_previous() {
+        TestContext::new().set();
+        let mut contract = new_contract();
+        assert_eq!(
+            None,
+            contract.create_update("first_key".to_string(), "hello".to_string())
+        );
+        assert_eq!(
+            Some("hello".to_string()),
+            contract.create_update("first_key".to_string(), "world".to_string())
+        );
+    }
+
+    // Test 21
+    //
+    // Test for [increment] and [decrement]
+    #[test]
+    fn counters() {
+        TestContext::new().set();
+        let mut contract = new_contract();
+        assert_eq!(U128(5), contract.increment("hits".to_string(), U128(5)));
+        assert_eq!(U128(7), contract.increment("hits".to_string(), U128(2)));
+        assert_eq!(U128(4), contract.decrement("hits".to_string(), U128(3)));
+        assert_eq!("4".to_string(), contract.read("hits".to_string()).unwrap());
+    }
+
+    // Test 22
+    //
+    // Test that [decrement] below zero is rejected
+    #[test]
+    #[should_panic(expected = "Counter underflow")]
+    fn counter_underflow() {
+        TestContext::new().set();
+        let mut contract = new_contract();
+        contract.increment("hits".to_string(), U128(1));
+        contract.decrement("hits".to_string(), U128(2));
+    }
+
+    // Test 23
+    //
+    // Test that counters refuse to operate on non-numeric values
+    #[test]
+    #[should_panic(expected = "Value of 'first_key' is not a counter")]
+    fn counter_on_text_value() {
+        TestContext::new().set();
+        let mut contract = new_contract();
+        contract.create_update("first_key".to_string(), "hello".to_string());
+        contract.increment("first_key".to_string(), U128(1));
+    }
+
+    // Test 24
+    //
+    // Test for [append] creating and extending a value
+    #[test]
+    fn append_to_value() {
+        TestContext::new().set();
+        let mut contract = new_contract();
+        assert_eq!(
+            5,
+            contract.append("log".to_string(), "hello".to_string(), 100)
+        );
+        assert_eq!(
+            11,
+            contract.append("log".to_string(), " world".to_string(), 100)
+        );
+        assert_eq!(
+            "hello world".to_string(),
+            contract.read("log".to_string()).unwrap()
+        );
+    }
+
+    // Test 25
+    //
+    // Test that [append] enforces max_len
+    #[test]
+    #[should_panic(expected = "exceeding max_len of 8")]
+    fn append_over_max_len() {
+        TestContext::new().set();
+        let mut contract = new_contract();
+        contract.append("log".to_string(), "hello".to_string(), 8);
+        contract.append("log".to_string(), " world".to_string(), 8);
+    }
+
+    // Test 26
+    //
+    // Test that entries written with [create_update_with_ttl] disappear once expired
+    #[test]
+    fn ttl_expiry() {
+        let mut context = TestContext::new();
+        context.set();
+        let mut contract = new_contract();
+        contract.create_update_with_ttl("session".to_string(), "token".to_string(), U64(100));
+        contract.create_update("config".to_string(), "value".to_string());
+        assert_eq!(
+            "token".to_string(),
+            contract.read("session".to_string()).unwrap()
+        );
+
+        context.advance_time(100).set();
+        assert_eq!(None, contract.read("session".to_string()));
+        assert!(!contract.contains_key("session".to_string()));
+        assert_eq!(vec!["config".to_string()], contract.keys(0, 10));
+        assert_eq!(
+            "value".to_string(),
+            contract.read("config".to_string()).unwrap()
+        );
+    }
+
+    // Test 27
+    //
+    // Test that a plain [create_update] clears an existing TTL
+    #[test]
+    fn create_update_clears_ttl() {
+        let mut context = TestContext::new();
+        context.set();
+        let mut contract = new_contract();
+        contract.create_update_with_ttl("session".to_string(), "token".to_string(), U64(100));
+        contract.create_update("session".to_string(), "forever".to_string());
+
+        context.advance_time(1_000).set();
+        assert_eq!(
+            "forever".to_string(),
+            contract.read("session".to_string()).unwrap()
+        );
+    }
+
+    // Test 28
+    //
+    // Test for [purge_expired] removing only expired entries, in batches
+    #[test]
+    fn purge_expired_entries() {
+        let mut context = TestContext::new();
+        context.set();
+        let mut contract = new_contract();
+        for i in 0..3 {
+            contract.create_update_with_ttl(format!("session_{}", i), "token".to_string(), U64(10));
+        }
+        contract.create_update_with_ttl("later".to_string(), "token".to_string(), U64(1_000));
+        contract.create_update("config".to_string(), "value".to_string());
+        assert_eq!(0, contract.purge_expired(10));
+
+        context.advance_time(10).set();
+        assert_eq!(2, contract.purge_expired(2));
+        assert_eq!(1, contract.purge_expired(10));
+        assert_eq!(0, contract.purge_expired(10));
+        assert_eq!(2, contract.len());
+        assert!(contract.contains_key("later".to_string()));
+    }
+
+    // Test 29
+    //
+    // Test for [read_with_meta] tracking created and updated block info
+    #[test]
+    fn read_with_meta_timestamps() {
+        let mut context = TestContext::new();
+        context.timestamp(100).height(1).set();
+        let mut contract = new_contract();
+        contract.create_update("first_key".to_string(), "hello".to_string());
+
+        context.advance_time(100).advance_blocks(1).set();
+        contract.create_update("first_key".to_string(), "world".to_string());
+
+        assert_eq!(
+            Some(EntryWithMeta {
+                value: "world".to_string(),
+                created_at: U64(100),
+                created_height: U64(1),
+                created_by: "carol_near".to_string(),
+                updated_at: U64(200),
+                updated_height: U64(2),
+                updated_by: "carol_near".to_string(),
+                expires_at: None,
+            }),
+            contract.read_with_meta("first_key".to_string())
+        );
+        assert_eq!(None, contract.read_with_meta("second_key".to_string()));
+    }
+
+    // Test 30
+    //
+    // Test that the last writer is recorded in the metadata
+    #[test]
+    fn read_with_meta_writer() {
+        TestContext::new().set();
+        let mut contract = new_contract();
+        contract.create_update("first_key".to_string(), "hello".to_string());
+
+        TestContext::new().predecessor("alice_near").set();
+        contract.create_update("first_key".to_string(), "world".to_string());
+
+        assert_eq!(
+            "alice_near".to_string(),
+            contract
+                .read_with_meta("first_key".to_string())
+                .unwrap()
+                .updated_by
+        );
+    }
+
+    // Test 31
+    //
+    // Test for [history] returning previous values newest first, bounded by the depth
+    #[test]
+    fn value_history() {
+        TestContext::new().predecessor("alice_near").set();
+        let mut contract = new_contract();
+        contract.set_history_depth(2);
+        for v in ["one", "two", "three", "four"].iter() {
+            contract.create_update("first_key".to_string(), v.to_string());
+        }
+
+        let history = contract.history("first_key".to_string(), 0, 10);
+        assert_eq!(
+            vec![(U64(3), "three".to_string()), (U64(2), "two".to_string())],
+            history
+                .into_iter()
+                .map(|revision| (revision.version, revision.value.into_text()))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            Value::String("two".to_string()),
+            contract.history("first_key".to_string(), 1, 1)[0].value
+        );
+    }
+
+    // Test 32
+    //
+    // Test that deleting a key drops its history
+    #[test]
+    fn delete_drops_history() {
+        TestContext::new().set();
+        let mut contract = new_contract();
+        contract.create_update("first_key".to_string(), "hello".to_string());
+        contract.create_update("first_key".to_string(), "world".to_string());
+        assert_eq!(1, contract.history("first_key".to_string(), 0, 10).len());
+
+        contract.delete("first_key".to_string());
+        contract.create_update("first_key".to_string(), "again".to_string());
+        assert!(contract.history("first_key".to_string(), 0, 10).is_empty());
+    }
+
+    // Test 33
+    //
+    // Test for [rollback] restoring an earlier value as a new version
+    #[test]
+    fn rollback_to_version() {
+        TestContext::new().set();
+        let mut contract = new_contract();
+        for v in ["one", "two", "three"].iter() {
+            contract.create_update("first_key".to_string(), v.to_string());
+        }
+
+        assert_eq!(U64(4), contract.rollback("first_key".to_string(), U64(1)));
+        assert_eq!(
+            "one".to_string(),
+            contract.read("first_key".to_string()).unwrap()
+        );
+        assert_eq!(
+            Value::String("three".to_string()),
+            contract.history("first_key".to_string(), 0, 1)[0].value
+        );
+        let event: near_sdk::serde_json::Value = get_logs()
+            .iter()
+            .filter_map(|log| log.strip_prefix("EVENT_JSON:"))
+            .map(|json| near_sdk::serde_json::from_str(json).unwrap())
+            .find(|event: &near_sdk::serde_json::Value| event["event"] == "kv_rollback")
+            .unwrap();
+        assert_eq!("kv", event["standard"]);
+        assert_eq!("1", event["data"][0]["from_version"]);
+        assert_eq!("4", event["data"][0]["version"]);
+    }
+
+    // Test 34
+    //
+    // Test that only the owner or key owner may roll back a key
+    #[test]
+    #[should_panic(expected = "Only the owner or the key owner can roll back 'first_key'")]
+    fn rollback_unauthorized() {
+        TestContext::new().set();
+        let mut contract = new_contract();
+        contract.create_update("first_key".to_string(), "one".to_string());
+        contract.create_update("first_key".to_string(), "two".to_string());
+
+        TestContext::new().predecessor("dave_near").set();
+        contract.rollback("first_key".to_string(), U64(1));
+    }
+
+    // Test 35
+    //
+    // Test that soft-deleted keys are hidden and can be restored with [undelete]
+    #[test]
+    fn soft_delete_and_undelete() {
+        TestContext::new().predecessor("alice_near").set();
+        let mut contract = new_contract();
+        contract.set_tombstone_retention(Some(U64(100)));
+        contract.create_update("first_key".to_string(), "hello".to_string());
+        contract.delete("first_key".to_string());
+
+        assert_eq!(None, contract.read("first_key".to_string()));
+        assert!(!contract.contains_key("first_key".to_string()));
+        assert!(contract.keys(0, 10).is_empty());
+
+        contract.undelete("first_key".to_string());
+        assert_eq!(
+            "hello".to_string(),
+            contract.read("first_key".to_string()).unwrap()
+        );
+    }
+
+    // Test 36
+    //
+    // Test that tombstones are purged once the retention window has passed
+    #[test]
+    fn soft_delete_retention_passed() {
+        let mut context = TestContext::new();
+        context.predecessor("alice_near").set();
+        let mut contract = new_contract();
+        contract.set_tombstone_retention(Some(U64(100)));
+        contract.create_update("first_key".to_string(), "hello".to_string());
+        contract.delete("first_key".to_string());
+
+        context.advance_time(100).set();
+        assert_eq!(1, contract.purge_expired(10));
+        assert!(contract.is_empty());
+    }
+
+    // Test 37
+    //
+    // Test that [undelete] is refused after the retention window
+    #[test]
+    #[should_panic(expected = "Retention window of 'first_key' has passed")]
+    fn undelete_after_retention() {
+        let mut context = TestContext::new();
+        context.predecessor("alice_near").set();
+        let mut contract = new_contract();
+        contract.set_tombstone_retention(Some(U64(100)));
+        contract.create_update("first_key".to_string(), "hello".to_string());
+        contract.delete("first_key".to_string());
+
+        context.advance_time(100).set();
+        contract.undelete("first_key".to_string());
+    }
+
+    // Test 38
+    //
+    // Test for [rename] moving the value, metadata and history
+    #[test]
+    fn rename_key() {
+        TestContext::new().set();
+        let mut contract = new_contract();
+        contract.create_update("old_key".to_string(), "hello".to_string());
+        contract.create_update("old_key".to_string(), "world".to_string());
+        contract.rename("old_key".to_string(), "new_key".to_string());
+
+        assert_eq!(None, contract.read("old_key".to_string()));
+        assert_eq!(vec!["new_key".to_string()], contract.keys(0, 10));
+        let entry = contract.read_with_meta("new_key".to_string()).unwrap();
+        assert_eq!("world".to_string(), entry.value);
+        assert_eq!("carol_near".to_string(), entry.created_by);
+        assert_eq!(
+            Value::String("hello".to_string()),
+            contract.history("new_key".to_string(), 0, 10)[0].value
+        );
+    }
+
+    // Test 39
+    //
+    // Test that [rename] refuses to overwrite an existing key
+    #[test]
+    #[should_panic(expected = "Key 'new_key' already exists")]
+    fn rename_onto_existing_key() {
+        TestContext::new().set();
+        let mut contract = new_contract();
+        contract.create_update("old_key".to_string(), "hello".to_string());
+        contract.create_update("new_key".to_string(), "world".to_string());
+        contract.rename("old_key".to_string(), "new_key".to_string());
+    }
+
+    // Test 40
+    //
+    // Test for [copy] with and without overwrite
+    #[test]
+    fn copy_key() {
+        TestContext::new().set();
+        let mut contract = new_contract();
+        contract.create_update("src".to_string(), "hello".to_string());
+        contract.create_update("taken".to_string(), "world".to_string());
+
+        assert_eq!(
+            None,
+            contract.copy("src".to_string(), "dst".to_string(), false)
+        );
+        assert_eq!(
+            Some("world".to_string()),
+            contract.copy("src".to_string(), "taken".to_string(), true)
+        );
+        assert_eq!(
+            "hello".to_string(),
+            contract.read("dst".to_string()).unwrap()
+        );
+        assert_eq!(
+            "hello".to_string(),
+            contract.read("taken".to_string()).unwrap()
+        );
+        assert_eq!(
+            "hello".to_string(),
+            contract.read("src".to_string()).unwrap()
+        );
+    }
+
+    // Test 41
+    //
+    // Test that [copy] without overwrite refuses an existing destination
+    #[test]
+    #[should_panic(expected = "Key 'dst' already exists")]
+    fn copy_without_overwrite() {
+        TestContext::new().set();
+        let mut contract = new_contract();
+        contract.create_update("src".to_string(), "hello".to_string());
+        contract.create_update("dst".to_string(), "world".to_string());
+        contract.copy("src".to_string(), "dst".to_string(), false);
+    }
+
+    // Test 42
+    //
+    // Test that writes above the configured value size are rejected
+    #[test]
+    #[should_panic(expected = "Value of 'first_key' is 6 bytes, exceeding the limit of 5")]
+    fn value_size_limit() {
+        TestContext::new().predecessor("alice_near").set();
+        let mut contract = new_contract();
+        contract.set_size_limits(SizeLimits {
+            max_key_len: 16,
+            max_value_len: 5,
+        });
+        contract.create_update("first_key".to_string(), "hello".to_string());
+        contract.create_update("first_key".to_string(), "hello!".to_string());
+    }
+
+    // Test 43
+    //
+    // Test that oversized keys are rejected by the default limits
+    #[test]
+    #[should_panic(expected = "exceeding the limit of 256")]
+    fn key_size_limit() {
+        TestContext::new().set();
+        let mut contract = new_contract();
+        contract.create_update("k".repeat(257), "hello".to_string());
+    }
+
+    // Test 44
+    //
+    // Test for [create_update_typed] and [read_typed] keeping the type tag
+    #[test]
+    fn typed_values() {
+        TestContext::new().set();
+        let mut contract = new_contract();
+        contract.create_update_typed("count".to_string(), Value::Number(I128(-42)));
+        contract.create_update_typed("enabled".to_string(), Value::Bool(true));
+        contract.create_update_typed(
+            "profile".to_string(),
+            Value::Json(r#"{"name":"alice"}"#.to_string()),
+        );
+
+        assert_eq!(
+            Some(Value::Number(I128(-42))),
+            contract.read_typed("count".to_string())
+        );
+        assert_eq!(
+            Some(Value::Bool(true)),
+            contract.read_typed("enabled".to_string())
+        );
+        assert_eq!(
+            "-42".to_string(),
+            contract.read("count".to_string()).unwrap()
+        );
+        assert_eq!(
+            r#"{"type":"json","value":"{\"name\":\"alice\"}"}"#,
+            near_sdk::serde_json::to_string(&contract.read_typed("profile".to_string())).unwrap()
+        );
+    }
+
+    // Test 45
+    //
+    // Test that malformed JSON values are rejected
+    #[test]
+    #[should_panic(expected = "Value of 'profile' is not valid JSON")]
+    fn typed_value_invalid_json() {
+        TestContext::new().set();
+        let mut contract = new_contract();
+        contract.create_update_typed("profile".to_string(), Value::Json("{name".to_string()));
+    }
+
+    // Test 46
+    //
+    // Test for [create_update_bytes] and [read_bytes] round-tripping non UTF-8 data
+    #[test]
+    fn binary_values() {
+        TestContext::new().set();
+        let mut contract = new_contract();
+        let payload = vec![0u8, 159, 146, 150, 255];
+        contract.create_update_bytes("blob".to_string(), Base64VecU8(payload.clone()));
+        contract.create_update("text".to_string(), "hi".to_string());
+
+        assert_eq!(
+            Some(Base64VecU8(payload)),
+            contract.read_bytes("blob".to_string())
+        );
+        assert_eq!(
+            Some(Base64VecU8(b"hi".to_vec())),
+            contract.read_bytes("text".to_string())
+        );
+        assert_eq!(
+            "AJ+Slv8=".to_string(),
+            contract.read("blob".to_string()).unwrap()
+        );
+    }
+
+    // Test 47
+    //
+    // Test for [list_push], [list_pop] and [list_get]
+    #[test]
+    fn list_values() {
+        TestContext::new().set();
+        let mut contract = new_contract();
+        for v in ["a", "b", "c"].iter() {
+            contract.list_push("feed".to_string(), v.to_string());
+        }
+        contract.list_push("other".to_string(), "x".to_string());
+
+        assert_eq!(3, contract.list_len("feed".to_string()));
+        assert_eq!(
+            vec!["b".to_string(), "c".to_string()],
+            contract.list_get("feed".to_string(), 1, 10)
+        );
+        assert_eq!(Some("c".to_string()), contract.list_pop("feed".to_string()));
+        assert_eq!(Some("b".to_string()), contract.list_pop("feed".to_string()));
+        assert_eq!(Some("a".to_string()), contract.list_pop("feed".to_string()));
+        assert_eq!(None, contract.list_pop("feed".to_string()));
+        assert_eq!(
+            vec!["x".to_string()],
+            contract.list_get("other".to_string(), 0, 10)
+        );
+        assert_eq!(None, contract.read("feed".to_string()));
+    }
+
+    // Test 48
+    //
+    // Test for set membership operations
+    #[test]
+    fn set_values() {
+        TestContext::new().set();
+        let mut contract = new_contract();
+        assert!(contract.set_add("allowlist".to_string(), "alice".to_string()));
+        assert!(contract.set_add("allowlist".to_string(), "bob".to_string()));
+        assert!(!contract.set_add("allowlist".to_string(), "alice".to_string()));
+
+        assert_eq!(2, contract.set_len("allowlist".to_string()));
+        assert!(contract.set_contains("allowlist".to_string(), "bob".to_string()));
+        assert_eq!(
+            vec!["alice".to_string(), "bob".to_string()],
+            contract.set_members("allowlist".to_string(), 0, 10)
+        );
+
+        assert!(contract.set_remove("allowlist".to_string(), "alice".to_string()));
+        assert!(!contract.set_remove("allowlist".to_string(), "alice".to_string()));
+        assert!(!contract.set_contains("allowlist".to_string(), "alice".to_string()));
+        assert!(contract.set_remove("allowlist".to_string(), "bob".to_string()));
+        assert_eq!(0, contract.set_len("allowlist".to_string()));
+    }
+
+    // Test 49
+    //
+    // Test for hash field operations
+    #[test]
+    fn hash_values() {
+        TestContext::new().set();
+        let mut contract = new_contract();
+        let k = "user:1".to_string();
+        assert_eq!(
+            None,
+            contract.hset(k.clone(), "name".to_string(), "alice".to_string())
+        );
+        assert_eq!(
+            None,
+            contract.hset(k.clone(), "age".to_string(), "30".to_string())
+        );
+        assert_eq!(
+            Some("30".to_string()),
+            contract.hset(k.clone(), "age".to_string(), "31".to_string())
+        );
+
+        assert_eq!(
+            Some("31".to_string()),
+            contract.hget(k.clone(), "age".to_string())
+        );
+        assert_eq!(2, contract.hlen(k.clone()));
+        assert_eq!(
+            vec![
+                ("name".to_string(), "alice".to_string()),
+                ("age".to_string(), "31".to_string())
+            ],
+            contract.hgetall(k.clone(), 0, 10)
+        );
+
+        assert!(contract.hdel(k.clone(), "name".to_string()));
+        assert!(!contract.hdel(k.clone(), "name".to_string()));
+        assert_eq!(None, contract.hget(k.clone(), "name".to_string()));
+        assert!(contract.hdel(k.clone(), "age".to_string()));
+        assert!(contract.hgetall(k, 0, 10).is_empty());
+    }
+
+    // Test 50
+    //
+    // Test for sorted set ordering by score
+    #[test]
+    fn sorted_set_values() {
+        TestContext::new().set();
+        let mut contract = new_contract();
+        let k = "leaderboard".to_string();
+        assert!(contract.zadd(k.clone(), "alice".to_string(), I64(30)));
+        assert!(contract.zadd(k.clone(), "bob".to_string(), I64(10)));
+        assert!(contract.zadd(k.clone(), "carol".to_string(), I64(20)));
+        assert!(!contract.zadd(k.clone(), "bob".to_string(), I64(40)));
+
+        assert_eq!(
+            vec![
+                ("carol".to_string(), I64(20)),
+                ("alice".to_string(), I64(30))
+            ],
+            contract.zrange_by_score(k.clone(), I64(0), I64(30), 10)
+        );
+        assert_eq!(Some(0), contract.zrank(k.clone(), "carol".to_string()));
+        assert_eq!(Some(2), contract.zrank(k.clone(), "bob".to_string()));
+        assert_eq!(None, contract.zrank(k.clone(), "dave".to_string()));
+
+        assert!(contract.zrem(k.clone(), "carol".to_string()));
+        assert_eq!(Some(0), contract.zrank(k, "alice".to_string()));
+    }
+
+    // Test 51
+    //
+    // Test for [keys_sorted] cursor pagination
+    #[test]
+    fn keys_sorted_pagination() {
+        TestContext::new().set();
+        let mut contract = new_contract();
+        for k in ["delta", "alpha", "charlie", "bravo"].iter() {
+            contract.create_update(k.to_string(), "value".to_string());
+        }
+
+        let first_page = contract.keys_sorted(None, 2);
+        assert_eq!(vec!["alpha".to_string(), "bravo".to_string()], first_page);
+        assert_eq!(
+            vec!["charlie".to_string(), "delta".to_string()],
+            contract.keys_sorted(first_page.last().cloned(), 2)
+        );
+        assert!(contract
+            .keys_sorted(Some("delta".to_string()), 2)
+            .is_empty());
+    }
+
+    // Test 52
+    //
+    // Test for [keys_with_value] following writes, renames and deletes
+    #[test]
+    fn reverse_value_index() {
+        TestContext::new().set();
+        let mut contract = new_contract();
+        contract.create_update("a".to_string(), "shared".to_string());
+        contract.create_update("b".to_string(), "shared".to_string());
+        contract.create_update("c".to_string(), "other".to_string());
+        contract.create_update_typed("d".to_string(), Value::Number(I128(7)));
+
+        assert_eq!(
+            vec!["a".to_string(), "b".to_string()],
+            contract.keys_with_value("shared".to_string(), 0, 10)
+        );
+        assert_eq!(
+            vec!["d".to_string()],
+            contract.keys_with_value("7".to_string(), 0, 10)
+        );
+
+        contract.create_update("a".to_string(), "other".to_string());
+        contract.rename("b".to_string(), "e".to_string());
+        contract.delete("c".to_string());
+        assert_eq!(
+            vec!["e".to_string()],
+            contract.keys_with_value("shared".to_string(), 0, 10)
+        );
+        assert_eq!(
+            vec!["a".to_string()],
+            contract.keys_with_value("other".to_string(), 0, 10)
+        );
+    }
+
+    // Test 53
+    //
+    // Test for tagging keys and querying them by tag
+    #[test]
+    fn key_tags() {
+        TestContext::new().set();
+        let mut contract = new_contract();
+        contract.create_update_with_tags(
+            "post:1".to_string(),
+            "hello".to_string(),
+            vec!["news".to_string(), "featured".to_string()],
+        );
+        contract.create_update_with_tags(
+            "post:2".to_string(),
+            "world".to_string(),
+            vec!["news".to_string()],
+        );
+        assert!(contract.add_tag("post:2".to_string(), "archived".to_string()));
+        assert!(!contract.add_tag("post:2".to_string(), "news".to_string()));
+
+        assert_eq!(
+            vec!["post:1".to_string(), "post:2".to_string()],
+            contract.keys_by_tag("news".to_string(), 0, 10)
+        );
+        assert_eq!(
+            vec!["news".to_string(), "archived".to_string()],
+            contract.tags("post:2".to_string())
+        );
+
+        assert!(contract.remove_tag("post:1".to_string(), "news".to_string()));
+        contract.delete("post:2".to_string());
+        assert!(contract.keys_by_tag("news".to_string(), 0, 10).is_empty());
+        assert_eq!(
+            vec!["post:1".to_string()],
+            contract.keys_by_tag("featured".to_string(), 0, 10)
+        );
+    }
+
+    // Test 54
+    //
+    // Test that unique values mode rejects a value stored under another key
+    #[test]
+    #[should_panic(expected = "invalid_argument: Value already exists")]
+    fn unique_values_rejects_duplicate() {
+        TestContext::new().predecessor("alice_near").set();
+        let mut contract = new_contract();
+        contract.set_unique_values(true);
+        contract.create_update("alice".to_string(), "alice.near".to_string());
+        contract.create_update("alice".to_string(), "alice.near".to_string());
+        contract.create_update("mallory".to_string(), "alice.near".to_string());
+    }
+
+    // Test 55
+    //
+    // Test that a deleted value can be claimed again in unique values mode
+    #[test]
+    fn unique_values_after_delete() {
+        TestContext::new().predecessor("alice_near").set();
+        let mut contract = new_contract();
+        contract.set_unique_values(true);
+        contract.create_update("alice".to_string(), "alice.near".to_string());
+        contract.delete("alice".to_string());
+        contract.create_update("bob".to_string(), "alice.near".to_string());
+        assert_eq!(
+            vec!["bob".to_string()],
+            contract.keys_with_value("alice.near".to_string(), 0, 10)
+        );
+    }
+
+    // Test 56
+    //
+    // Test that the configured owner, not the contract account, gates administrative methods
+    #[test]
+    fn owner_gates_admin_methods() {
+        TestContext::new().predecessor("dave_near").set();
+        let config = Config {
+            history_depth: 3,
+            ..Config::default()
+        };
+        let mut contract = KeyValue::new("dave_near".try_into().unwrap(), Some(config));
+        assert_eq!("dave_near".to_string(), contract.owner());
+        assert_eq!(3, contract.history_depth());
+        contract.set_unique_values(true);
+        assert!(contract.config().unique_values);
+    }
+
+    // Test 57
+    //
+    // Test that the contract account itself is no longer treated as the owner
+    #[test]
+    #[should_panic(expected = "Only the owner can call this method")]
+    fn non_owner_cannot_configure() {
+        TestContext::new().predecessor("alice_near").set();
+        let mut contract = KeyValue::new("dave_near".try_into().unwrap(), None);
+        contract.set_history_depth(5);
+    }
+
+    // Test 58
+    //
+    // Test that another account cannot overwrite a key it did not create
+    #[test]
+    #[should_panic(expected = "Only the owner or the key owner can update 'profile'")]
+    fn key_owner_enforced_on_update() {
+        TestContext::new().set();
+        let mut contract = new_contract();
+        contract.create_update("profile".to_string(), "carol".to_string());
+
+        TestContext::new().predecessor("mallory_near").set();
+        contract.create_update("profile".to_string(), "mallory".to_string());
+    }
+
+    // Test 59
+    //
+    // Test that another account cannot delete a key it did not create, while the owner can
+    #[test]
+    fn key_owner_enforced_on_delete() {
+        TestContext::new().set();
+        let mut contract = new_contract();
+        contract.create_update("profile".to_string(), "carol".to_string());
+
+        let mut context = TestContext::new();
+        context.predecessor("mallory_near").set();
+        let attempt = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.delete("profile".to_string())
+        }));
+        assert!(attempt.is_err());
+
+        context.predecessor("alice_near").set();
+        contract.delete("profile".to_string());
+        assert_eq!(None, contract.read("profile".to_string()));
+    }
+
+    // Test 60
+    //
+    // Test that a writer may update keys created by other accounts
+    #[test]
+    fn writer_role_updates_any_key() {
+        let mut context = TestContext::new();
+        context.predecessor("alice_near").set();
+        let mut contract = new_contract();
+        assert!(contract.grant_role("dave_near".try_into().unwrap(), Role::Writer));
+        assert!(!contract.grant_role("dave_near".try_into().unwrap(), Role::Writer));
+        contract.create_update("first_key".to_string(), "hello".to_string());
+
+        context.predecessor("dave_near").set();
+        contract.create_update("first_key".to_string(), "world".to_string());
+        assert_eq!(
+            Some("world".to_string()),
+            contract.read("first_key".to_string())
+        );
+        assert!(contract.has_role("dave_near".try_into().unwrap(), Role::Writer));
+        assert!(!contract.has_role("dave_near".try_into().unwrap(), Role::Admin));
+    }
+
+    // Test 61
+    //
+    // Test that a reader-restricted account cannot write
+    #[test]
+    #[should_panic(expected = "Account 'carol_near' is restricted to reads")]
+    fn reader_role_cannot_write() {
+        let mut context = TestContext::new();
+        context.predecessor("alice_near").set();
+        let mut contract = new_contract();
+        contract.grant_role("carol_near".try_into().unwrap(), Role::Reader);
+
+        context.predecessor("carol_near").set();
+        contract.create_update("first_key".to_string(), "hello".to_string());
+    }
+
+    // Test 62
+    //
+    // Test that an admin may configure the contract but not grant admin
+    #[test]
+    #[should_panic(expected = "Only the owner can manage admins")]
+    fn admin_role_cannot_grant_admin() {
+        let mut context = TestContext::new();
+        context.predecessor("alice_near").set();
+        let mut contract = new_contract();
+        contract.grant_role("dave_near".try_into().unwrap(), Role::Admin);
+
+        context.predecessor("dave_near").set();
+        contract.set_history_depth(5);
+        assert!(contract.revoke_role("dave_near".try_into().unwrap(), Role::Admin));
+    }
+
+    // Test 63
+    //
+    // Test that allowlist mode rejects writes from accounts not on the list
+    #[test]
+    fn writer_allowlist() {
+        let mut context = TestContext::new();
+        context.predecessor("alice_near").set();
+        let mut contract = new_contract();
+        contract.set_allowlist_only(true);
+        assert!(contract.add_writer("carol_near".try_into().unwrap()));
+        assert_eq!(vec!["carol_near".to_string()], contract.list_writers(0, 10));
+
+        context.predecessor("carol_near").set();
+        contract.create_update("first_key".to_string(), "hello".to_string());
+
+        context.predecessor("alice_near").set();
+        assert!(contract.remove_writer("carol_near".try_into().unwrap()));
+
+        context.predecessor("carol_near").set();
+        let attempt = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.delete("first_key".to_string())
+        }));
+        assert!(attempt.is_err());
+        assert_eq!(
+            Some("hello".to_string()),
+            contract.read("first_key".to_string())
+        );
+    }
+
+    // Test 64
+    //
+    // Test that a moderator can blacklist an account, which then cannot write
+    #[test]
+    fn blacklisted_account_rejected() {
+        let mut context = TestContext::new();
+        context.predecessor("alice_near").set();
+        let mut contract = new_contract();
+        contract.grant_role("dave_near".try_into().unwrap(), Role::Moderator);
+
+        context.predecessor("dave_near").set();
+        assert!(contract.blacklist_add("carol_near".try_into().unwrap()));
+        assert!(!contract.blacklist_add("carol_near".try_into().unwrap()));
+        let logs = get_logs();
+        assert_eq!(1, logs.len());
+        let event: near_sdk::serde_json::Value =
+            near_sdk::serde_json::from_str(logs[0].strip_prefix("EVENT_JSON:").unwrap()).unwrap();
+        assert_eq!("kv_blacklist_add", event["event"]);
+        assert_eq!("carol_near", event["data"][0]["account_id"]);
+        assert!(contract.is_blacklisted("carol_near".try_into().unwrap()));
+
+        context.predecessor("carol_near").set();
+        let attempt = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.create_update("first_key".to_string(), "hello".to_string())
+        }));
+        assert!(attempt.is_err());
+    }
+
+    // Test 65
+    //
+    // Test that a direct transfer hands control of a key to the new owner
+    #[test]
+    fn transfer_key_direct() {
+        TestContext::new().set();
+        let mut contract = new_contract();
+        contract.create_update("profile".to_string(), "carol".to_string());
+        contract.transfer_key(
+            "profile".to_string(),
+            "dave_near".try_into().unwrap(),
+            false,
+        );
+        assert_eq!(
+            "transfer: key 'profile' from carol_near to dave_near".to_string(),
+            get_logs()[1]
+        );
+        assert_eq!(
+            Some("dave_near".to_string()),
+            contract.key_owner("profile".to_string())
+        );
+
+        let attempt = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.delete("profile".to_string())
+        }));
+        assert!(attempt.is_err());
+    }
+
+    // Test 66
+    //
+    // Test that a two-step transfer only takes effect once accepted by the new owner
+    #[test]
+    fn transfer_key_two_step() {
+        TestContext::new().set();
+        let mut contract = new_contract();
+        contract.create_update("profile".to_string(), "carol".to_string());
+        contract.transfer_key("profile".to_string(), "dave_near".try_into().unwrap(), true);
+        assert_eq!(
+            Some("carol_near".to_string()),
+            contract.key_owner("profile".to_string())
+        );
+
+        TestContext::new().predecessor("dave_near").set();
+        contract.accept_key("profile".to_string());
+        assert_eq!(
+            Some("dave_near".to_string()),
+            contract.key_owner("profile".to_string())
+        );
+    }
+
+    // Test 67
+    //
+    // Test that a write grant lets another account update, until revoked
+    #[test]
+    fn grant_and_revoke_write() {
+        TestContext::new().set();
+        let mut contract = new_contract();
+        contract.create_update("doc".to_string(), "draft".to_string());
+        assert!(contract.grant_write("doc".to_string(), "dave_near".try_into().unwrap()));
+        assert_eq!(
+            vec!["dave_near".to_string()],
+            contract.write_grants("doc".to_string())
+        );
+
+        let mut context = TestContext::new();
+        context.predecessor("dave_near").set();
+        contract.create_update("doc".to_string(), "edited".to_string());
+        assert_eq!(Some("edited".to_string()), contract.read("doc".to_string()));
+        assert_eq!(
+            Some("carol_near".to_string()),
+            contract.key_owner("doc".to_string())
+        );
+
+        context.predecessor("carol_near").set();
+        assert!(contract.revoke_write("doc".to_string(), "dave_near".try_into().unwrap()));
+
+        context.predecessor("dave_near").set();
+        let attempt = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.create_update("doc".to_string(), "again".to_string())
+        }));
+        assert!(attempt.is_err());
+    }
+
+    // Test 68
+    //
+    // Test that an unlisted key is left out of listings and plain reads
+    #[test]
+    fn unlisted_key_hidden_from_listings() {
+        TestContext::new().set();
+        let mut contract = new_contract();
+        contract.create_update("public".to_string(), "hello".to_string());
+        contract.create_update("secret".to_string(), "hunter2".to_string());
+        contract.set_unlisted("secret".to_string(), true);
+
+        assert_eq!(vec!["public".to_string()], contract.keys(0, 10));
+        assert_eq!(vec!["public".to_string()], contract.keys_sorted(None, 10));
+        assert_eq!(None, contract.read("secret".to_string()));
+        assert_eq!(
+            Some("hunter2".to_string()),
+            contract.read_private("secret".to_string())
+        );
+    }
+
+    // Test 69
+    //
+    // Test that only designated readers can read an unlisted key by exact key
+    #[test]
+    fn unlisted_key_designated_readers() {
+        TestContext::new().set();
+        let mut contract = new_contract();
+        contract.create_update("secret".to_string(), "hunter2".to_string());
+        contract.set_unlisted("secret".to_string(), true);
+        assert!(contract.add_reader("secret".to_string(), "dave_near".try_into().unwrap()));
+
+        let mut context = TestContext::new();
+        context.predecessor("dave_near").set();
+        assert_eq!(
+            Some("hunter2".to_string()),
+            contract.read_private("secret".to_string())
+        );
+
+        context.predecessor("mallory_near").set();
+        let attempt = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.read_private("secret".to_string())
+        }));
+        assert!(attempt.is_err());
+    }
+
+    // Test 70
+    //
+    // Test that with a multisig configured, clear_all needs enough confirmations
+    #[test]
+    fn multisig_clear_all() {
+        let mut context = TestContext::new();
+        context.predecessor("alice_near").set();
+        let mut contract = new_contract();
+        contract.create_update("first_key".to_string(), "hello".to_string());
+        contract.set_multisig(Multisig {
+            members: vec!["alice_near".to_string(), "dave_near".to_string()],
+            threshold: 2,
+        });
+        let attempt =
+            std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| contract.clear_all(10)));
+        assert!(attempt.is_err());
+
+        let id = contract.propose(AdminAction::ClearAll { limit: 10 });
+        let attempt =
+            std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| contract.execute(id)));
+        assert!(attempt.is_err());
+
+        context.predecessor("dave_near").set();
+        assert!(contract.confirm(id));
+        contract.execute(id);
+        assert_eq!(0, contract.len());
+        assert_eq!(None, contract.proposal(id));
+    }
+
+    // Test 71
+    //
+    // Test that a proposal cannot be confirmed once it has expired
+    #[test]
+    #[should_panic(expected = "Proposal 0 has expired")]
+    fn multisig_proposal_expires() {
+        let mut context = TestContext::new();
+        context.predecessor("alice_near").set();
+        let mut contract = new_contract();
+        contract.set_multisig(Multisig {
+            members: vec!["alice_near".to_string(), "dave_near".to_string()],
+            threshold: 2,
+        });
+        let id = contract.propose(AdminAction::SetConfig {
+            config: Config::default(),
+        });
+
+        context
+            .predecessor("dave_near")
+            .timestamp(PROPOSAL_LIFETIME_NS)
+            .set();
+        contract.confirm(id);
+    }
+
+    // Test 72
+    //
+    // Test that ownership only moves once the proposed owner accepts
+    #[test]
+    fn two_step_ownership_transfer() {
+        let mut context = TestContext::new();
+        context.predecessor("alice_near").set();
+        let mut contract = new_contract();
+        contract.propose_owner("dave_near".try_into().unwrap());
+        assert_eq!("alice_near".to_string(), contract.owner());
+        assert_eq!(Some("dave_near".to_string()), contract.pending_owner());
+
+        context.predecessor("dave_near").set();
+        contract.accept_ownership();
+        let event: near_sdk::serde_json::Value =
+            near_sdk::serde_json::from_str(get_logs()[0].strip_prefix("EVENT_JSON:").unwrap())
+                .unwrap();
+        assert_eq!("kv_owner_transferred", event["event"]);
+        assert_eq!("alice_near", event["data"][0]["old_owner_id"]);
+        assert_eq!("dave_near", event["data"][0]["new_owner_id"]);
+        assert_eq!("dave_near".to_string(), contract.owner());
+        assert_eq!(None, contract.pending_owner());
+    }
+
+    // Test 73
+    //
+    // Test that a cancelled ownership proposal can no longer be accepted
+    #[test]
+    #[should_panic(expected = "Only the proposed owner can accept ownership")]
+    fn cancelled_ownership_proposal() {
+        let mut context = TestContext::new();
+        context.predecessor("alice_near").set();
+        let mut contract = new_contract();
+        contract.propose_owner("dave_near".try_into().unwrap());
+        contract.cancel_owner_proposal();
+
+        context.predecessor("dave_near").set();
+        contract.accept_ownership();
+    }
+
+    // Test 74
+    //
+    // Test that writes are rejected while paused and reads stay available
+    #[test]
+    fn pause_blocks_writes() {
+        TestContext::new().predecessor("alice_near").set();
+        let mut contract = new_contract();
+        contract.create_update("first_key".to_string(), "hello".to_string());
+        contract.pause();
+        assert!(contract.is_paused());
+
+        let attempt = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.delete("first_key".to_string())
+        }));
+        assert!(attempt.is_err());
+        assert_eq!(
+            Some("hello".to_string()),
+            contract.read("first_key".to_string())
+        );
+
+        contract.unpause();
+        contract.delete("first_key".to_string());
+        assert_eq!(None, contract.read("first_key".to_string()));
+    }
+
+    // Test 75
+    //
+    // Test that pausing only delete leaves writes available
+    #[test]
+    fn pause_single_method() {
+        TestContext::new().predecessor("alice_near").set();
+        let mut contract = new_contract();
+        contract.set_paused_methods(PAUSE_DELETE);
+        contract.create_update("first_key".to_string(), "hello".to_string());
+
+        let attempt = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.delete("first_key".to_string())
+        }));
+        assert!(attempt.is_err());
+        assert_eq!(
+            Some("hello".to_string()),
+            contract.read("first_key".to_string())
+        );
+
+        contract.set_paused_methods(0);
+        contract.delete("first_key".to_string());
+        assert_eq!(None, contract.read("first_key".to_string()));
+    }
+
+    // Test 76
+    //
+    // Test that a queued action can only be executed after the timelock delay
+    #[test]
+    fn timelocked_clear_all() {
+        let mut context = TestContext::new();
+        context.predecessor("alice_near").set();
+        let mut contract = new_contract();
+        contract.create_update("first_key".to_string(), "hello".to_string());
+        contract.set_timelock_delay(U64(1_000));
+        let attempt =
+            std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| contract.clear_all(10)));
+        assert!(attempt.is_err());
+
+        let id = contract.queue(AdminAction::ClearAll { limit: 10 });
+        let attempt =
+            std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| contract.execute_queued(id)));
+        assert!(attempt.is_err());
+
+        context.timestamp(1_000).set();
+        contract.execute_queued(id);
+        assert_eq!(0, contract.len());
+        assert_eq!(None, contract.queued_action(id));
+    }
+
+    // Test 77
+    //
+    // Test that a cancelled action can no longer be executed
+    #[test]
+    #[should_panic(expected = "Action 0 is not queued")]
+    fn cancelled_queued_action() {
+        TestContext::new().predecessor("alice_near").set();
+        let mut contract = new_contract();
+        contract.set_timelock_delay(U64(1_000));
+        let id = contract.queue(AdminAction::ClearAll { limit: 10 });
+        contract.cancel_queued(id);
+        contract.execute_queued(id);
+    }
+
+    // Test 78
+    //
+    // Test that maintenance mode rejects writes, even the owner's, with the maintenance code
+    #[test]
+    #[should_panic(expected = "maintenance: The contract is read-only during maintenance")]
+    fn maintenance_mode_rejects_writes() {
+        TestContext::new().predecessor("alice_near").set();
+        let mut contract = new_contract();
+        contract.create_update("first_key".to_string(), "hello".to_string());
+        contract.set_maintenance(true);
+        assert_eq!(
+            Some("hello".to_string()),
+            contract.read("first_key".to_string())
+        );
+        contract.create_update("first_key".to_string(), "world".to_string());
+    }
+
+    // Test 79
+    //
+    // Test that a write is charged for its storage and deleting it refunds the bytes, except
+    // those of the audit log that outlives the key
+    #[test]
+    fn storage_charged_and_refunded() {
+        TestContext::new().set();
+        let mut contract = new_contract();
+        contract.create_update("first_key".to_string(), "hello".to_string());
+        let paid = contract.storage_paid("carol_near".try_into().unwrap()).0;
+        assert!(paid > 0);
+
+        let before_delete = env::storage_usage();
+        contract.delete("first_key".to_string());
+        let freed = before_delete - env::storage_usage();
+        assert_eq!(
+            paid - freed,
+            contract.storage_paid("carol_near".try_into().unwrap()).0
+        );
+        assert!(freed > 0);
+    }
+
+    // Test 80
+    //
+    // Test that a write without enough deposit for its storage is rejected
+    #[test]
+    #[should_panic(expected = "does not cover the storage cost")]
+    fn storage_deposit_required() {
+        TestContext::new().deposit(0).set();
+        let mut contract = new_contract();
+        contract.create_update("first_key".to_string(), "hello".to_string());
+    }
+
+    // Test 81
+    //
+    // Test that writes draw from a NEP-145 storage deposit when no deposit is attached
+    #[test]
+    fn storage_management_deposit() {
+        TestContext::new().set();
+        let mut contract = new_contract();
+        let min = contract.storage_balance_bounds().min.0;
+        let balance = contract.storage_deposit(None, None);
+        assert_eq!(10u128.pow(24) - min, balance.available.0);
+
+        TestContext::new().deposit(0).set();
+        contract.create_update("first_key".to_string(), "hello".to_string());
+        let balance = contract
+            .storage_balance_of("carol_near".try_into().unwrap())
+            .unwrap();
+        assert!(balance.available.0 < 10u128.pow(24) - min);
+        assert_eq!(10u128.pow(24), balance.total.0);
+    }
+
+    // Test 82
+    //
+    // Test that an account still paying for data cannot unregister
+    #[test]
+    #[should_panic(expected = "Can't unregister while the account still pays for stored data")]
+    fn storage_unregister_with_data() {
+        TestContext::new().set();
+        let mut contract = new_contract();
+        contract.storage_deposit(None, Some(true));
+        contract.create_update("first_key".to_string(), "hello".to_string());
+
+        TestContext::new().deposit(1).set();
+        contract.storage_unregister(None);
+    }
+
+    // Test 83
+    //
+    // Test that create_update accepts a deposit of exactly the storage cost and no less
+    #[test]
+    fn create_update_exact_storage_cost() {
+        TestContext::new().set();
+        let mut contract = new_contract();
+        contract.create_update("first_key".to_string(), "hello".to_string());
+        let bytes = contract.storage_paid("carol_near".try_into().unwrap()).0;
+        let cost = Balance::from(bytes) * env::storage_byte_cost();
+
+        // Start from empty storage so the write adds the same bytes again
+        env::take_blockchain_interface();
+        let mut context = TestContext::new();
+        context.deposit(cost).set();
+        let mut contract = new_contract();
+        contract.create_update("first_key".to_string(), "hello".to_string());
+
+        env::take_blockchain_interface();
+        context.deposit(cost - 1).set();
+        let mut contract = new_contract();
+        let attempt = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.create_update("first_key".to_string(), "hello".to_string())
+        }));
+        assert!(attempt.is_err());
+    }
+
+    // Test 84
+    //
+    // Test that the surplus of an over-funded write is transferred back to the caller
+    #[test]
+    fn excess_deposit_refunded() {
+        TestContext::new().set();
+        let mut contract = new_contract();
+        contract.create_update("first_key".to_string(), "hello".to_string());
+        let bytes = contract.storage_paid("carol_near".try_into().unwrap()).0;
+        let cost = Balance::from(bytes) * env::storage_byte_cost();
+
+        let receipts = near_sdk::test_utils::get_created_receipts();
+        assert_eq!(1, receipts.len());
+        let receipt = near_sdk::serde_json::to_string(&receipts[0]).unwrap();
+        assert!(receipt.contains(r#""receiver_id":"carol_near""#));
+        assert!(receipt.contains(&format!(
+            r#"{{"Transfer":{{"deposit":{}}}}}"#,
+            10u128.pow(24) - cost
+        )));
+    }
+
+    // Test 85
+    //
+    // Test that a write beyond the account's storage quota is rejected
+    #[test]
+    #[should_panic(expected = "Storage quota of 1000 bytes exceeded: carol_near uses")]
+    fn storage_quota_enforced() {
+        let mut context = TestContext::new();
+        context.predecessor("alice_near").set();
+        let mut contract = new_contract();
+        contract.set_storage_quota(Some(U64(1000)));
+
+        context.predecessor("carol_near").set();
+        contract.create_update("small".to_string(), "hello".to_string());
+        contract.create_update("large".to_string(), "x".repeat(1000));
+    }
+
+    // Test 86
+    //
+    // Test that flat write fees accumulate in the treasury and can be withdrawn by the owner
+    #[test]
+    fn write_fees_into_treasury() {
+        let mut context = TestContext::new();
+        context.predecessor("alice_near").set();
+        let mut contract = new_contract();
+        contract.set_write_fee(U128(1_000));
+
+        context.predecessor("carol_near").set();
+        contract.create_update_many(vec![
+            ("a".to_string(), "1".to_string()),
+            ("b".to_string(), "2".to_string()),
+        ]);
+        contract.delete("a".to_string());
+        assert_eq!(U128(2_000), contract.treasury());
+
+        context.predecessor("alice_near").set();
+        contract.withdraw_fees("dave_near".try_into().unwrap(), U128(1_500));
+        assert_eq!(U128(500), contract.treasury());
+    }
+
+    // Test 87
+    //
+    // Test that a premium entry is only served through read_paid, paying its owner
+    #[test]
+    fn premium_read_paid() {
+        TestContext::new().set();
+        let mut contract = new_contract();
+        contract.create_update("report".to_string(), "insights".to_string());
+        contract.set_premium("report".to_string(), Some(U128(5_000)));
+        assert_eq!(None, contract.read("report".to_string()));
+        assert_eq!(Some(U128(5_000)), contract.price("report".to_string()));
+
+        TestContext::new()
+            .predecessor("dave_near")
+            .deposit(5_000)
+            .set();
+        assert_eq!(
+            "insights".to_string(),
+            contract.read_paid("report".to_string())
+        );
+        let receipts = near_sdk::test_utils::get_created_receipts();
+        assert_eq!(1, receipts.len());
+        let receipt = near_sdk::serde_json::to_string(&receipts[0]).unwrap();
+        assert!(receipt.contains(r#""receiver_id":"carol_near""#));
+
+        let attempt = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.read_private("report".to_string())
+        }));
+        assert!(attempt.is_err());
+    }
+
+    // Test 88
+    //
+    // Test that the storage report lists the bytes each account pays for
+    #[test]
+    fn storage_report_per_account() {
+        TestContext::new().set();
+        let mut contract = new_contract();
+        contract.create_update("first_key".to_string(), "hello".to_string());
+
+        let report = contract.storage_report(None, None);
+        assert_eq!(env::storage_usage(), report.total_bytes.0);
+        assert_eq!(1, report.accounts.len());
+        assert_eq!("carol_near".to_string(), report.accounts[0].account_id);
+        assert_eq!(
+            contract.storage_paid("carol_near".try_into().unwrap()),
+            report.accounts[0].bytes
+        );
+    }
+
+    // Test 89
+    //
+    // Test that the fee schedule charges per-byte write fees and read fees into the treasury
+    #[test]
+    fn fee_schedule_charges() {
+        let mut context = TestContext::new();
+        context.predecessor("alice_near").set();
+        let mut contract = new_contract();
+        contract.set_fee_schedule(FeeSchedule {
+            per_byte: U128(10),
+            read_fee: U128(7),
+            ..FeeSchedule::default()
+        });
+        assert_eq!(U128(10), contract.fee_schedule().per_byte);
+
+        context.predecessor("carol_near").set();
+        contract.create_update("first_key".to_string(), "hello".to_string());
+        let bytes = contract.storage_paid("carol_near".try_into().unwrap()).0;
+        assert_eq!(U128(Balance::from(bytes) * 10), contract.treasury());
+
+        contract.read_paid("first_key".to_string());
+        assert_eq!(U128(Balance::from(bytes) * 10 + 7), contract.treasury());
+    }
+
+    // Test 90
+    //
+    // Test that writes and deletes are logged as NEP-297 events
+    #[test]
+    fn data_events() {
+        TestContext::new().set();
+        let mut contract = new_contract();
+        contract.create_update("first_key".to_string(), "hello".to_string());
+        contract.create_update("first_key".to_string(), "world".to_string());
+        contract.delete("first_key".to_string());
+
+        let events: Vec<near_sdk::serde_json::Value> = get_logs()
+            .iter()
+            .filter_map(|log| log.strip_prefix("EVENT_JSON:"))
+            .map(|json| near_sdk::serde_json::from_str(json).unwrap())
+            .collect();
+        assert_eq!(
+            vec!["kv_create", "kv_update", "kv_delete"],
+            events
+                .iter()
+                .map(|event| event["event"].as_str().unwrap())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            near_sdk::serde_json::json!({
+                "standard": "kv",
+                "version": "1.0.0",
+                "event": "kv_create",
+                "data": [{
+                    "key": "first_key",
+                    "actor": "carol_near",
+                    "block_height": "0",
+                    "block_timestamp": "0",
+                    "new_value": "hello",
+                }],
+            }),
+            events[0]
+        );
+    }
+
+    // Test 91
+    //
+    // Test that events carry the old and new values, hashing large or unlisted ones
+    #[test]
+    fn event_value_diff() {
+        TestContext::new().set();
+        let mut contract = new_contract();
+        let large = "x".repeat(EVENT_VALUE_INLINE_LIMIT + 1);
+        contract.create_update("first_key".to_string(), "hello".to_string());
+        contract.create_update("first_key".to_string(), large.clone());
+        contract.delete("first_key".to_string());
+        contract.create_update("private".to_string(), "secret".to_string());
+        contract.set_unlisted("private".to_string(), true);
+        contract.delete("private".to_string());
+
+        let data: Vec<near_sdk::serde_json::Value> = get_logs()
+            .iter()
+            .filter_map(|log| log.strip_prefix("EVENT_JSON:"))
+            .map(|json| {
+                near_sdk::serde_json::from_str::<near_sdk::serde_json::Value>(json).unwrap()
+            })
+            .map(|event| event["data"][0].clone())
+            .collect();
+        let large_hash = near_sdk::base64::encode(env::sha256(large.as_bytes()));
+        assert_eq!("hello", data[1]["old_value"]);
+        assert_eq!(large_hash.as_str(), data[1]["new_value_hash"]);
+        assert!(data[1].get("new_value").is_none());
+        assert_eq!(large_hash.as_str(), data[2]["old_value_hash"]);
+        assert!(data[2].get("new_value_hash").is_none());
+        assert!(data[4].get("old_value").is_none());
+        assert_eq!(
+            near_sdk::base64::encode(env::sha256(b"secret")).as_str(),
+            data[4]["old_value_hash"]
+        );
+    }
+
+    // Test 92
+    //
+    // Test that the audit log records every operation on a key and outlives it
+    #[test]
+    fn audit_log() {
+        let mut context = TestContext::new();
+        context.set();
+        let mut contract = new_contract();
+        contract.create_update("first_key".to_string(), "hello".to_string());
+        context.timestamp(5).set();
+        contract.create_update("first_key".to_string(), "world".to_string());
+        contract.delete("first_key".to_string());
+
+        let log = contract.audit("first_key".to_string(), 0, 10);
+        assert_eq!(
+            vec!["create", "update", "delete"],
+            log.iter()
+                .map(|entry| entry.operation.as_str())
+                .collect::<Vec<_>>()
+        );
+        let hello = Base64VecU8(env::sha256(b"hello"));
+        assert_eq!(None, log[0].old_hash);
+        assert_eq!(Some(hello), log[1].old_hash);
+        assert_eq!(U64(5), log[1].timestamp);
+        assert_eq!("carol_near".to_string(), log[2].actor);
+        assert_eq!(None, log[2].new_hash);
+
+        let page = contract.audit("first_key".to_string(), 1, 1);
+        assert_eq!(1, page.len());
+        assert_eq!("update", page[0].operation);
+        assert!(contract.audit("other".to_string(), 0, 10).is_empty());
+    }
+
+    // Test 93
+    //
+    // Test that stats count operations and distinct writers
+    #[test]
+    fn operation_stats() {
+        let mut context = TestContext::new();
+        context.set();
+        let mut contract = new_contract();
+        contract.create_update("first_key".to_string(), "hello".to_string());
+        contract.create_update("first_key".to_string(), "world".to_string());
+        contract.create_update("second_key".to_string(), "hi".to_string());
+        contract.delete("first_key".to_string());
+
+        context.predecessor("alice_near").set();
+        contract.delete("second_key".to_string());
+
+        assert_eq!(
+            Stats {
+                creates: U64(2),
+                updates: U64(1),
+                deletes: U64(2),
+                writers: U64(2),
+            },
+            contract.stats()
+        );
+    }
+
+    // Test 94
+    //
+    // Test that the change feed numbers mutations in order and pages from a sequence number
+    #[test]
+    fn change_feed() {
+        TestContext::new().set();
+        let mut contract = new_contract();
+        assert_eq!(U64(0), contract.last_seq());
+        contract.create_update("first_key".to_string(), "hello".to_string());
+        contract.create_update("second_key".to_string(), "hi".to_string());
+        contract.delete("first_key".to_string());
+        assert_eq!(U64(3), contract.last_seq());
+
+        let all = contract.changes_since(U64(0), 10);
+        assert_eq!(
+            vec![
+                (U64(1), "create", "first_key"),
+                (U64(2), "create", "second_key"),
+                (U64(3), "delete", "first_key"),
+            ],
+            all.iter()
+                .map(|change| (change.seq, change.operation.as_str(), change.key.as_str()))
+                .collect::<Vec<_>>()
+        );
+
+        let page = contract.changes_since(U64(1), 1);
+        assert_eq!(1, page.len());
+        assert_eq!(U64(2), page[0].seq);
+        assert!(contract.changes_since(U64(3), 10).is_empty());
+    }
+
+    // Test 95
+    //
+    // Test that a diff reports the net effect of a range of changes
+    #[test]
+    fn net_state_diff() {
+        TestContext::new().set();
+        let mut contract = new_contract();
+        contract.create_update("kept".to_string(), "1".to_string());
+        contract.create_update("dropped".to_string(), "1".to_string());
+        let from = contract.last_seq();
+
+        contract.create_update("kept".to_string(), "2".to_string());
+        contract.create_update("kept".to_string(), "3".to_string());
+        contract.delete("dropped".to_string());
+        contract.create_update("new".to_string(), "1".to_string());
+        contract.create_update("temp".to_string(), "1".to_string());
+        contract.delete("temp".to_string());
+
+        let diff = contract.diff(from, U64(u64::MAX));
+        assert_eq!(
+            StateDiff {
+                from_seq: U64(2),
+                to_seq: U64(8),
+                created: vec!["new".to_string()],
+                updated: vec!["kept".to_string()],
+                deleted: vec!["dropped".to_string()],
+            },
+            diff
+        );
+        assert!(contract.diff(U64(8), U64(8)).created.is_empty());
+    }
+
+    // Test 96
+    //
+    // Test that migrate upgrades state of the original layout in resumable batches and keeps
+    // current state as is
+    #[test]
+    fn migrate_state() {
+        TestContext::new()
+            .current("alice_near")
+            .predecessor("alice_near")
+            .set();
+        let mut old = KeyValueV0 {
+            pairs: UnorderedMap::new(PAIRS_PREFIX.to_vec()),
+        };
+        old.pairs
+            .insert(&"first_key".to_string(), &"hello".to_string());
+        old.pairs
+            .insert(&"second_key".to_string(), &"world".to_string());
+        old.pairs.insert(&"x".repeat(600), &"long".to_string());
+        env::state_write(&old);
+
+        let mut contract = KeyValue::migrate();
+        assert_eq!(U64(2), contract.migrate_batch(1));
+        assert_eq!(U64(0), contract.migrate_batch(10));
+        assert_eq!(U64(0), contract.migrate_batch(10));
+        assert_eq!(Some("long".to_string()), contract.read("x".repeat(600)));
+        assert_eq!(
+            Some("hello".to_string()),
+            contract.read("first_key".to_string())
+        );
+        assert_eq!(
+            Some("world".to_string()),
+            contract.read("second_key".to_string())
+        );
+        assert_eq!("alice_near".to_string(), contract.owner());
+
+        env::state_write(&contract);
+        let migrated = KeyValue::migrate();
+        assert_eq!(
+            Some("hello".to_string()),
+            migrated.read("first_key".to_string())
+        );
+    }
+
+    // Test 97
+    //
+    // Test that exported state, unlisted entries included, imports into a fresh contract
+    #[test]
+    fn export_import_state() {
+        let mut context = TestContext::new();
+        context.set();
+        let mut contract = new_contract();
+        contract.create_update_with_tags(
+            "profile".to_string(),
+            "carol".to_string(),
+            vec!["people".to_string()],
+        );
+        contract.create_update("private".to_string(), "secret".to_string());
+        contract.set_unlisted("private".to_string(), true);
+
+        context.predecessor("alice_near").set();
+        let exported = contract.export_state(0, 10);
+        assert_eq!(2, exported.len());
+
+        env::take_blockchain_interface();
+        context.set();
+        let mut replacement = new_contract();
+        assert_eq!(2, replacement.import_state(exported));
+        assert_eq!(
+            Some("carol_near".to_string()),
+            replacement.key_owner("profile".to_string())
+        );
+        assert_eq!(
+            vec!["profile".to_string()],
+            replacement.keys_by_tag("people".to_string(), 0, 10)
+        );
+        assert_eq!(None, replacement.read("private".to_string()));
+        assert_eq!(
+            Some("secret".to_string()),
+            replacement.read_private("private".to_string())
+        );
+    }
+
+    // Test 98
+    //
+    // Test that an upgrade deploys the new code and then calls migrate
+    #[test]
+    fn upgrade_deploys_and_migrates() {
+        TestContext::new().predecessor("alice_near").set();
+        let mut contract = new_contract();
+        contract.upgrade(Base64VecU8(vec![0, 97, 115, 109]));
+
+        let receipts = near_sdk::test_utils::get_created_receipts();
+        assert_eq!(1, receipts.len());
+        let receipt = near_sdk::serde_json::to_string(&receipts[0]).unwrap();
+        assert!(receipt.contains(r#""receiver_id":"alice_near""#));
+        assert!(receipt.contains(r#"{"DeployContract":{"code":[0,97,115,109]}}"#));
+        assert!(receipt.contains(r#""method_name":"migrate""#));
+    }
+
+    // Test 99
+    //
+    // Test that the source metadata names the crate version, repository and standards
+    #[test]
+    fn source_metadata() {
+        let mut context = TestContext::new();
+        context.set();
+        let contract = new_contract();
+        context.view().set();
+        let metadata = contract.contract_source_metadata();
+        assert_eq!(
+            Some(env!("CARGO_PKG_VERSION").to_string()),
+            metadata.version
+        );
+        assert_eq!(
+            Some("https://github.com/haziqkamel/first-near-smart-contract".to_string()),
+            metadata.link
+        );
+        assert!(metadata
+            .standards
+            .iter()
+            .any(|standard| standard.standard == "nep330"));
+    }
+
+    // Test 100
+    //
+    // Test that version reports the crate version and build commit
+    #[test]
+    fn build_version() {
+        let mut context = TestContext::new();
+        context.set();
+        let contract = new_contract();
+        context.view().set();
+        let build = contract.version();
+        assert_eq!(env!("CARGO_PKG_VERSION"), build.version);
+        assert!(!build.commit.is_empty());
+    }
+
+    // Test 101
+    //
+    // Test that the entries a call changes are replicated to the mirror with their metadata,
+    // and restored from their previous snapshots when the mirror rejects them
+    #[test]
+    fn mirror_writes() {
+        let mut context = TestContext::new();
+        context.predecessor("alice_near").set();
+        let mut contract = new_contract();
+        contract.set_mirror(Some("mirror_near".try_into().unwrap()));
+        context.predecessor("carol_near").set();
+        contract.create_update("first_key".to_string(), "hello".to_string());
+        let before = contract.internal_entry(&"first_key".to_string());
+
+        context.set();
+        contract.create_update_with_tags(
+            "first_key".to_string(),
+            "world".to_string(),
+            vec!["news".to_string()],
+        );
+        let calls = created_calls();
+        let mirrored: Vec<&(String, String, Vec<u8>)> = calls
+            .iter()
+            .filter(|(receiver, _, _)| receiver == "mirror_near")
+            .collect();
+        assert_eq!(1, mirrored.len());
+        assert_eq!("mirror_apply", mirrored[0].1);
+        let applied: Vec<(String, Option<MirroredEntry>)> =
+            BorshDeserialize::try_from_slice(&mirrored[0].2).unwrap();
+        let after = contract.internal_entry(&"first_key".to_string());
+        assert_eq!(vec![("first_key".to_string(), after.clone())], applied);
+        assert_eq!(vec!["news".to_string()], after.unwrap().meta.tags);
+
+        // The mirror rejected the batch
+        let (_, method, args) = calls.last().unwrap();
+        assert_eq!("on_mirror", method);
+        let changes: Vec<MirrorChange> = BorshDeserialize::try_from_slice(args).unwrap();
+        assert_eq!(before, changes[0].previous);
+        context
+            .predecessor("alice_near")
+            .promise_results(vec![PromiseResult::Failed])
+            .set();
+        assert!(!contract.on_mirror(changes));
+        assert_eq!(before, contract.internal_entry(&"first_key".to_string()));
+        assert!(contract.keys_by_tag("news".to_string(), 0, 10).is_empty());
+        assert!(created_calls()
+            .iter()
+            .all(|(receiver, _, _)| receiver != "mirror_near"));
+
+        // A later write supersedes a rejected one
+        context
+            .predecessor("carol_near")
+            .promise_results(vec![])
+            .set();
+        contract.create_update("first_key".to_string(), "again".to_string());
+        let (_, _, args) = created_calls().pop().unwrap();
+        let changes: Vec<MirrorChange> = BorshDeserialize::try_from_slice(&args).unwrap();
+        context.set();
+        contract.create_update("first_key".to_string(), "latest".to_string());
+        context
+            .predecessor("alice_near")
+            .promise_results(vec![PromiseResult::Failed])
+            .set();
+        assert!(!contract.on_mirror(changes));
+        assert_eq!(
+            Some("latest".to_string()),
+            contract.read("first_key".to_string())
+        );
+    }
+
+    // Test 102
+    //
+    // Test that read_through answers local keys and forwards missing ones upstream
+    #[test]
+    fn read_through_upstream() {
+        let mut context = TestContext::new();
+        context.predecessor("alice_near").set();
+        let mut contract = new_contract();
+        contract.create_update("first_key".to_string(), "hello".to_string());
+        match contract.read_through("missing".to_string()) {
+            PromiseOrValue::Value(answer) => assert_eq!(None, answer),
+            PromiseOrValue::Promise(_) => panic!("No upstream is configured"),
+        }
+
+        contract.set_upstream(Some("upstream_near".try_into().unwrap()));
+        match contract.read_through("first_key".to_string()) {
+            PromiseOrValue::Value(answer) => assert_eq!(Some("hello".to_string()), answer),
+            PromiseOrValue::Promise(_) => panic!("Local keys are answered directly"),
+        }
+        context.set();
+        assert!(matches!(
+            contract.read_through("missing".to_string()),
+            PromiseOrValue::Promise(_)
+        ));
+        let receipts: Vec<String> = near_sdk::test_utils::get_created_receipts()
+            .iter()
+            .map(|receipt| near_sdk::serde_json::to_string(receipt).unwrap())
+            .collect();
+        assert_eq!(2, receipts.len());
+        assert!(receipts[0].contains(r#""receiver_id":"upstream_near""#));
+        assert!(receipts[0].contains(r#""method_name":"read_through""#));
+        assert!(receipts[1].contains(r#""receiver_id":"alice_near""#));
+        assert!(receipts[1].contains(r#""method_name":"on_upstream_read""#));
+
+        context
+            .current("alice_near")
+            .predecessor("alice_near")
+            .promise_results(vec![PromiseResult::Successful(b"\"remote\"".to_vec())])
+            .set();
+        assert_eq!(
+            Some("remote".to_string()),
+            contract.on_upstream_read("missing".to_string())
+        );
+        context.promise_results(vec![PromiseResult::Failed]).set();
+        assert_eq!(None, contract.on_upstream_read("missing".to_string()));
+        assert_eq!(
+            vec!["upstream: lookup of 'missing' failed".to_string()],
+            get_logs()
+        );
+    }
+
+    // Test 103
+    //
+    // Test that only the Croncat manager can tick, and a tick purges expired entries
+    // and drops expired proposals
+    #[test]
+    fn croncat_tick_sweeps() {
+        let mut context = TestContext::new();
+        context.predecessor("alice_near").set();
+        let mut contract = new_contract();
+        contract.set_croncat_manager(Some("manager_near".try_into().unwrap()));
+        contract.create_update_with_ttl("first_key".to_string(), "hello".to_string(), U64(10));
+        contract.set_multisig(Multisig {
+            members: vec!["alice_near".to_string(), "bob_near".to_string()],
+            threshold: 2,
+        });
+        contract.propose(AdminAction::ClearAll { limit: 10 });
+
+        context.predecessor("carol_near").set();
+        let attempt = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.croncat_tick();
+        }));
+        assert!(attempt.is_err());
+
+        context
+            .predecessor("manager_near")
+            .timestamp(PROPOSAL_LIFETIME_NS + 1)
+            .set();
+        assert_eq!(
+            TickSummary {
+                purged: 1,
+                proposals_dropped: 1,
+            },
+            contract.croncat_tick()
+        );
+        assert_eq!(None, contract.proposal(U64(0)));
+    }
+
+    // Test 104
+    //
+    // Test that registered oracles push increasing rounds and reads flag stale values
+    #[test]
+    fn oracle_feeds() {
+        let mut context = TestContext::new();
+        context.predecessor("alice_near").timestamp(100).set();
+        let mut contract = new_contract();
+        assert!(contract.add_oracle("oracle_near".try_into().unwrap()));
+
+        context.predecessor("oracle_near").set();
+        let sign = oracle_signer(&mut contract);
+        contract.push_feed(
+            "near-usd".to_string(),
+            "3.14".to_string(),
+            U64(1),
+            U64(90),
+            sign("near-usd", "3.14", 1, 90),
+        );
+        let reading = contract
+            .read_feed("near-usd".to_string(), Some(U64(20)))
+            .unwrap();
+        assert_eq!("3.14".to_string(), reading.round.value);
+        assert_eq!("oracle_near".to_string(), reading.round.oracle);
+        assert_eq!(sign("near-usd", "3.14", 1, 90), reading.round.signature);
+        assert!(!reading.stale);
+        assert!(
+            contract
+                .read_feed("near-usd".to_string(), Some(U64(5)))
+                .unwrap()
+                .stale
+        );
+
+        let replay = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.push_feed(
+                "near-usd".to_string(),
+                "3.00".to_string(),
+                U64(1),
+                U64(95),
+                sign("near-usd", "3.00", 1, 95),
+            )
+        }));
+        assert!(replay.is_err());
+
+        context.predecessor("carol_near").set();
+        let outsider = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.push_feed(
+                "near-usd".to_string(),
+                "1.00".to_string(),
+                U64(2),
+                U64(95),
+                sign("near-usd", "1.00", 2, 95),
+            )
+        }));
+        assert!(outsider.is_err());
+    }
+
+    /// Registers a relay key for the calling oracle and returns a function signing rounds with
+    /// it, as [KeyValue::push_feed] expects.
+    fn oracle_signer(contract: &mut KeyValue) -> impl Fn(&str, &str, u64, u64) -> Base64VecU8 {
+        use ed25519_dalek::Signer;
+        let signing_key = ed25519_dalek::SigningKey::from_bytes(&[9; 32]);
+        let public_key = signing_key.verifying_key().to_bytes().to_vec();
+        contract.set_relay_key(Some(Base64VecU8(public_key)));
+        move |feed, value, round, observed_at| {
+            let round = (feed.to_string(), value.to_string(), round, observed_at);
+            let message = env::sha256(&round.try_to_vec().unwrap());
+            Base64VecU8(signing_key.sign(&message).to_bytes().to_vec())
+        }
+    }
+
+    // Test 105
+    //
+    // Test that transfers of the storage token credit the storage balance and others are refused
+    #[test]
+    fn storage_paid_in_tokens() {
+        let mut context = TestContext::new();
+        context.predecessor("alice_near").set();
+        let mut contract = new_contract();
+        contract.set_storage_token(Some(StorageToken {
+            token_id: "wrap_near".to_string(),
+            yocto_per_unit: U128(1),
+        }));
+        let min = contract.storage_balance_bounds().min.0;
+
+        context.predecessor("wrap_near").set();
+        contract.ft_on_transfer(
+            "carol_near".try_into().unwrap(),
+            U128(min + 500),
+            String::new(),
+        );
+        assert_eq!(
+            U128(500),
+            contract
+                .storage_balance_of("carol_near".try_into().unwrap())
+                .unwrap()
+                .available
+        );
+        contract.ft_on_transfer(
+            "carol_near".try_into().unwrap(),
+            U128(100),
+            "carol_near".to_string(),
+        );
+        assert_eq!(
+            U128(600),
+            contract
+                .storage_balance_of("carol_near".try_into().unwrap())
+                .unwrap()
+                .available
+        );
+
+        context.predecessor("other_token_near").set();
+        let attempt = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.ft_on_transfer("carol_near".try_into().unwrap(), U128(100), String::new())
+        }));
+        assert!(attempt.is_err());
+    }
+
+    // Test 106
+    //
+    // Test that gated keys are only written after the NFT lookup confirms the writer
+    #[test]
+    fn nft_gated_writes() {
+        let mut context = TestContext::new();
+        context.predecessor("alice_near").set();
+        let mut contract = new_contract();
+        contract.set_nft_gate(Some(NftGate {
+            prefix: "club/".to_string(),
+            nft_contract_id: "nft_near".to_string(),
+        }));
+
+        context.predecessor("carol_near").set();
+        let direct = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.create_update("club/carol".to_string(), "hi".to_string())
+        }));
+        assert!(direct.is_err());
+        contract.gated_write("club/carol".to_string(), "hi".to_string(), "1".to_string());
+        assert!(near_sdk::test_utils::get_created_receipts()
+            .iter()
+            .map(|receipt| near_sdk::serde_json::to_string(receipt).unwrap())
+            .any(|receipt| receipt.contains(r#""method_name":"nft_token""#)));
+
+        // Callbacks come from the contract account, which is not the owner here
+        context
+            .current("kv_near")
+            .predecessor("kv_near")
+            .promise_results(vec![PromiseResult::Successful(
+                br#"{"token_id":"1","owner_id":"dave_near"}"#.to_vec(),
+            )])
+            .set();
+        assert!(!contract.on_nft_verified(
+            "club/carol".to_string(),
+            "hi".to_string(),
+            "carol_near".to_string()
+        ));
+        assert_eq!(None, contract.read("club/carol".to_string()));
+
+        context
+            .promise_results(vec![PromiseResult::Successful(
+                br#"{"token_id":"1","owner_id":"carol_near"}"#.to_vec(),
+            )])
+            .set();
+        assert!(contract.on_nft_verified(
+            "club/carol".to_string(),
+            "hi".to_string(),
+            "carol_near".to_string()
+        ));
+        assert_eq!(
+            Some("hi".to_string()),
+            contract.read("club/carol".to_string())
+        );
+        assert_eq!(
+            Some("carol_near".to_string()),
+            contract.key_owner("club/carol".to_string())
+        );
+        assert!(contract.storage_paid("carol_near".try_into().unwrap()).0 > 0);
+    }
+
+    // Test 107
+    //
+    // Test that subscribers of a matching prefix are notified of writes and deletes
+    #[test]
+    fn subscriber_notifications() {
+        let mut context = TestContext::new();
+        context.predecessor("listener_near").set();
+        let mut contract = new_contract();
+        assert!(contract.subscribe("user/".to_string()));
+        assert!(!contract.subscribe("user/".to_string()));
+
+        context.predecessor("carol_near").set();
+        contract.create_update("user/carol".to_string(), "hi".to_string());
+        contract.create_update("other".to_string(), "hi".to_string());
+        contract.delete("user/carol".to_string());
+
+        let notifications: Vec<String> = near_sdk::test_utils::get_created_receipts()
+            .iter()
+            .map(|receipt| near_sdk::serde_json::to_string(receipt).unwrap())
+            .filter(|receipt| receipt.contains(r#""receiver_id":"listener_near""#))
+            .collect();
+        assert_eq!(2, notifications.len());
+        assert!(notifications
+            .iter()
+            .all(|receipt| receipt.contains(r#""method_name":"on_kv_change""#)));
+
+        context.predecessor("listener_near").set();
+        assert!(contract.unsubscribe("user/".to_string()));
+        assert!(contract.subscribers("user/".to_string()).is_empty());
+    }
+
+    // Test 108
+    //
+    // Test that each caller gets an isolated namespace with its own quota
+    #[test]
+    fn caller_namespaces() {
+        let mut context = TestContext::new();
+        context.predecessor("app_one_near").set();
+        let mut contract = new_contract();
+        assert_eq!(
+            None,
+            contract.ns_set("config".to_string(), "one".to_string())
+        );
+        contract.create_update("config".to_string(), "shared".to_string());
+        let used = contract.ns_usage("app_one_near".try_into().unwrap());
+        assert!(used.0 > 0);
+
+        context.predecessor("app_two_near").set();
+        contract.ns_set("config".to_string(), "two".to_string());
+        assert_eq!(
+            Some("one".to_string()),
+            contract.ns_get("app_one_near".try_into().unwrap(), "config".to_string())
+        );
+        assert_eq!(
+            Some("two".to_string()),
+            contract.ns_get("app_two_near".try_into().unwrap(), "config".to_string())
+        );
+        assert_eq!(
+            Some("shared".to_string()),
+            contract.read("config".to_string())
+        );
+        assert_eq!(
+            Some("two".to_string()),
+            contract.ns_remove("config".to_string())
+        );
+        assert_eq!(
+            U64(0),
+            contract.ns_usage("app_two_near".try_into().unwrap())
+        );
+
+        context.predecessor("alice_near").set();
+        contract.set_namespace_quota(Some(used));
+        context.predecessor("app_one_near").set();
+        let attempt = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.ns_set("more".to_string(), "data".to_string())
+        }));
+        assert!(attempt.is_err());
+    }
+
+    // Test 109
+    //
+    // Test that storage credits are bought, transferred and spent on writes
+    #[test]
+    fn storage_credit_token() {
+        let mut context = TestContext::new();
+        context.set();
+        let mut contract = new_contract();
+        contract.storage_deposit(None, Some(true));
+        contract.storage_deposit(Some("dave_near".try_into().unwrap()), Some(true));
+        assert_eq!(U128(10u128.pow(24)), contract.buy_credits());
+        assert_eq!(U128(10u128.pow(24)), contract.ft_total_supply());
+
+        context.deposit(1).set();
+        contract.ft_transfer("dave_near".try_into().unwrap(), U128(1000), None);
+        assert_eq!(
+            U128(1000),
+            contract.ft_balance_of("dave_near".try_into().unwrap())
+        );
+
+        context.deposit(0).set();
+        contract.create_update("first_key".to_string(), "hello".to_string());
+        let bytes = contract.storage_paid("carol_near".try_into().unwrap()).0;
+        let spent = Balance::from(bytes) * env::storage_byte_cost();
+        assert_eq!(
+            U128(10u128.pow(24) - 1000 - spent),
+            contract.ft_balance_of("carol_near".try_into().unwrap())
+        );
+        assert_eq!(U128(10u128.pow(24) - spent), contract.ft_total_supply());
+    }
+
+    // Test 110
+    //
+    // Test that key tokens report their owner and transferring one hands over the key
+    #[test]
+    fn key_tokens() {
+        let mut context = TestContext::new();
+        context.set();
+        let mut contract = new_contract();
+        contract.create_update("name".to_string(), "carol".to_string());
+        contract.grant_write("name".to_string(), "erin_near".try_into().unwrap());
+        let token = contract.nft_token("name".to_string()).unwrap();
+        assert_eq!("carol_near".to_string(), token.owner_id);
+        assert_eq!(1, contract.nft_tokens(None, None).len());
+
+        context.deposit(1).set();
+        contract.nft_transfer(
+            "dave_near".try_into().unwrap(),
+            "name".to_string(),
+            None,
+            None,
+        );
+        assert_eq!(
+            Some("dave_near".to_string()),
+            contract.key_owner("name".to_string())
+        );
+        assert!(contract.write_grants("name".to_string()).is_empty());
+
+        let attempt = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.nft_transfer(
+                "carol_near".try_into().unwrap(),
+                "name".to_string(),
+                None,
+                None,
+            )
+        }));
+        assert!(attempt.is_err());
+    }
+
+    // Test 111
+    //
+    // Test that a relayed write signed by its author is applied on their behalf exactly once
+    #[test]
+    fn relayed_write() {
+        use ed25519_dalek::Signer;
+        let mut context = TestContext::new();
+        context.predecessor("dave_near").set();
+        let mut contract = new_contract();
+        let signing_key = ed25519_dalek::SigningKey::from_bytes(&[7; 32]);
+        let public_key = signing_key.verifying_key().to_bytes().to_vec();
+        contract.set_relay_key(Some(Base64VecU8(public_key)));
+
+        let write = RelayedWrite {
+            author: "dave_near".to_string(),
+            receiver_id: "alice_near".to_string(),
+            nonce: U64(1),
+            expires_at: U64(1_000),
+            k: "name".to_string(),
+            v: "dave".to_string(),
+        };
+        let signature = |write: &RelayedWrite| {
+            let message = env::sha256(&write.try_to_vec().unwrap());
+            Base64VecU8(signing_key.sign(&message).to_bytes().to_vec())
+        };
+        context.predecessor("carol_near").set();
+        assert_eq!(None, contract.relay_write(write.clone(), signature(&write)));
+        assert_eq!(
+            Some("dave_near".to_string()),
+            contract.key_owner("name".to_string())
+        );
+        assert_eq!(
+            U64(1),
+            contract.relay_nonce("dave_near".try_into().unwrap())
+        );
+
+        let replay = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.relay_write(write.clone(), signature(&write))
+        }));
+        assert!(replay.is_err());
+        let forged = RelayedWrite {
+            nonce: U64(2),
+            ..write.clone()
+        };
+        let tampered = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.relay_write(forged.clone(), signature(&write))
+        }));
+        assert!(tampered.is_err());
+    }
+
+    // Test 112
+    //
+    // Test that the embedded ABI describes the contract methods and their arguments
+    #[test]
+    fn contract_abi() {
+        let abi: near_sdk::serde_json::Value =
+            near_sdk::serde_json::from_slice(&zstd::decode_all(CONTRACT_ABI).unwrap()).unwrap();
+        assert_eq!("0.4.0", abi["schema_version"]);
+        let functions = abi["body"]["functions"].as_array().unwrap();
+        let function = |name: &str| {
+            functions
+                .iter()
+                .find(|function| function["name"] == name)
+                .unwrap()
+                .clone()
+        };
+        assert_eq!("view", function("read")["kind"]);
+        let create_update = function("create_update");
+        assert_eq!("call", create_update["kind"]);
+        assert_eq!(
+            near_sdk::serde_json::json!(["payable"]),
+            create_update["modifiers"]
+        );
+        assert_eq!("k", create_update["params"]["args"][0]["name"]);
+        assert_eq!(
+            "#/definitions/Value",
+            function("create_update_typed")["params"]["args"][1]["type_schema"]["$ref"]
+        );
+        assert!(abi["body"]["root_schema"]["definitions"]["Value"].is_object());
+        let read_borsh = function("read_borsh");
+        assert_eq!("borsh", read_borsh["params"]["serialization_type"]);
+        assert_eq!("borsh", read_borsh["result"]["serialization_type"]);
+        assert!(!functions
+            .iter()
+            .any(|function| function["name"] == "internal_put"));
+    }
+
+    // Test 113
+    //
+    // Test that with listing disabled keys are still stored, counted and found, but not listed
+    #[test]
+    #[should_panic(expected = "Listing is disabled on this contract")]
+    fn unlisted_store() {
+        TestContext::new().set();
+        let config = Config {
+            listing: false,
+            ..Config::default()
+        };
+        let mut contract = KeyValue::new("alice_near".try_into().unwrap(), Some(config));
+        contract.create_update("first_key".to_string(), "hello".to_string());
+        contract.create_update("second_key".to_string(), "world".to_string());
+        contract.delete("second_key".to_string());
+        assert_eq!(
+            Some("hello".to_string()),
+            contract.read("first_key".to_string())
+        );
+        assert!(contract.contains_key("first_key".to_string()));
+        assert!(!contract.contains_key("second_key".to_string()));
+        assert_eq!(1, contract.len());
+        contract.keys(0, 10);
+    }
+
+    // Test 114
+    //
+    // Test that large values live in their own storage entry, which is dropped with the value
+    #[test]
+    fn large_values_stored_apart() {
+        TestContext::new().set();
+        let mut contract = new_contract();
+        let large = "x".repeat(LARGE_VALUE_THRESHOLD + 1);
+        let large_key = nested_prefix(LARGE_VALUES_PREFIX, "big");
+        contract.create_update("big".to_string(), large.clone());
+        assert!(env::storage_has_key(&large_key));
+        assert_eq!(Some(large.clone()), contract.read("big".to_string()));
+
+        contract.rename("big".to_string(), "moved".to_string());
+        assert!(!env::storage_has_key(&large_key));
+        assert_eq!(Some(large.clone()), contract.read("moved".to_string()));
+        assert_eq!(
+            Some(large),
+            contract.create_update("moved".to_string(), "small".to_string())
+        );
+        assert!(!env::storage_has_key(&nested_prefix(
+            LARGE_VALUES_PREFIX,
+            "moved"
+        )));
+        assert_eq!(
+            Some("small".to_string()),
+            contract.read("moved".to_string())
+        );
+    }
+
+    // Test 115
+    //
+    // Test that the key list is spread over shards and listing pages walk through all of them
+    #[test]
+    fn key_list_sharded() {
+        let context = TestContext::new();
+        context.set();
+        let mut contract = new_contract();
+        for i in 0..40 {
+            context.set();
+            contract.create_update(format!("key_{}", i), format!("value_{}", i));
+        }
+        let shard_sizes: Vec<u64> = (0..KEY_LIST_SHARDS)
+            .filter_map(|shard| {
+                contract
+                    .indexes
+                    .get()
+                    .key_list
+                    .get(&shard)
+                    .map(|keys| keys.len())
+            })
+            .collect();
+        assert!(shard_sizes.len() > 1);
+        assert_eq!(40, shard_sizes.iter().sum::<u64>());
+
+        let mut keys: Vec<String> = (0..4)
+            .flat_map(|page| contract.keys(page * 10, 10))
+            .collect();
+        keys.sort();
+        keys.dedup();
+        assert_eq!(40, keys.len());
+
+        contract.delete("key_0".to_string());
+        assert_eq!(39, contract.len());
+        assert_eq!(39, contract.keys(0, 100).len());
+    }
+
+    // Test 116
+    //
+    // Test that the indexes, audit log and ACL are only loaded when a call touches them
+    #[test]
+    fn lazy_state() {
+        TestContext::new().predecessor("alice_near").set();
+        let mut contract = new_contract();
+        contract.create_update("name".to_string(), "alice".to_string());
+        contract.grant_role("dave_near".try_into().unwrap(), Role::Writer);
+        env::state_write(&contract);
+
+        let restored: KeyValue = env::state_read().unwrap();
+        assert_eq!(Some("alice".to_string()), restored.read("name".to_string()));
+        assert!(restored.indexes.value.get().is_none());
+        assert!(restored.audit_log.value.get().is_none());
+        assert!(restored.acl.value.get().is_none());
+        assert_eq!(U64(1), restored.stats().creates);
+        assert!(restored.has_role("dave_near".try_into().unwrap(), Role::Writer));
+        assert_eq!(vec!["name".to_string()], restored.keys(0, 10));
+    }
+
+    // Test 117
+    //
+    // Test that the Borsh method variants behave like their JSON counterparts
+    #[test]
+    fn borsh_variants() {
+        TestContext::new().set();
+        let mut contract = new_contract();
+        assert_eq!(
+            None,
+            contract.create_update_borsh("first_key".to_string(), "hello".to_string())
+        );
+        let summary = contract.create_update_many_borsh(vec![
+            ("first_key".to_string(), "hi".to_string()),
+            ("second_key".to_string(), "world".to_string()),
+        ]);
+        assert_eq!(
+            BatchWriteSummary {
+                created: 1,
+                updated: 1
+            },
+            summary
+        );
+        assert_eq!(
+            Some("hi".to_string()),
+            contract.read_borsh("first_key".to_string())
+        );
+        assert_eq!(
+            vec![Some("world".to_string()), None],
+            contract.read_many_borsh(vec!["second_key".to_string(), "missing".to_string()])
+        );
+    }
+
+    // Test 118
+    //
+    // Test that read_raw returns the bare payload of each kind of value
+    #[test]
+    fn raw_reads() {
+        TestContext::new().set();
+        let mut contract = new_contract();
+        let returned = |contract: &KeyValue, k: &str| {
+            contract.read_raw(k.to_string());
+            let blockchain = env::take_blockchain_interface().unwrap();
+            let outcome = blockchain.as_mocked_blockchain().unwrap().outcome();
+            env::set_blockchain_interface(blockchain);
+            match outcome.return_data {
+                near_sdk::ReturnData::Value(bytes) => bytes,
+                _ => vec![],
+            }
+        };
+        contract.create_update("text".to_string(), "hello".to_string());
+        contract.create_update_bytes("blob".to_string(), Base64VecU8(vec![0, 159, 255]));
+        contract.create_update_typed("number".to_string(), Value::Number(I128(42)));
+        let large = "x".repeat(LARGE_VALUE_THRESHOLD + 1);
+        contract.create_update("large".to_string(), large.clone());
+
+        assert_eq!(b"hello".to_vec(), returned(&contract, "text"));
+        assert_eq!(vec![0, 159, 255], returned(&contract, "blob"));
+        assert_eq!(b"42".to_vec(), returned(&contract, "number"));
+        assert_eq!(large.into_bytes(), returned(&contract, "large"));
+    }
+
+    /// Gas a method may burn above its recorded baseline before `gas_regressions` fails, in
+    /// percent.
+    const GAS_REGRESSION_MARGIN: u64 = 10;
+
+    // Test 119
+    //
+    // Test that the gas the core read, write and delete methods burn in the mocked VM stays
+    // within GAS_REGRESSION_MARGIN of its recorded baseline. Only these hot paths are tracked,
+    // not every public method; lower a baseline when a change makes a method cheaper, and raise
+    // it deliberately when a feature has to cost more.
+    #[test]
+    fn gas_regressions() {
+        let context = TestContext::new();
+        context.set();
+        let mut contract = new_contract();
+        let mut measure = |name: &str, baseline: u64, call: &dyn Fn(&mut KeyValue)| {
+            context.set();
+            call(&mut contract);
+            (name.to_string(), env::used_gas(), baseline)
+        };
+        let pairs: Vec<(String, String)> = (0..10)
+            .map(|i| (format!("key_{}", i), "value".repeat(20)))
+            .collect();
+        let keys: Vec<String> = pairs.iter().map(|(k, _)| k.clone()).collect();
+        let results = vec![
+            measure("create_update (new)", 3_215_000_000_000, &|c| {
+                c.create_update("key".to_string(), "value".repeat(20));
+            }),
+            measure("create_update (update)", 3_288_000_000_000, &|c| {
+                c.create_update("key".to_string(), "other".repeat(20));
+            }),
+            measure("read", 150_000_000_000, &|c| {
+                c.read("key".to_string());
+            }),
+            measure("read_raw", 155_000_000_000, &|c| {
+                c.read_raw("key".to_string())
+            }),
+            measure("contains_key", 127_000_000_000, &|c| {
+                c.contains_key("key".to_string());
+            }),
+            measure("create_update_many (10)", 39_323_000_000_000, &|c| {
+                c.create_update_many(pairs.clone());
+            }),
+            measure("read_many (10)", 1_403_000_000_000, &|c| {
+                c.read_many(keys.clone());
+            }),
+            measure("keys (10)", 2_220_000_000_000, &|c| {
+                c.keys(0, 10);
+            }),
+            measure("delete", 5_089_000_000_000, &|c| {
+                c.delete("key".to_string());
+            }),
+        ];
+        for (name, used, baseline) in results {
+            assert!(
+                used <= baseline + baseline * GAS_REGRESSION_MARGIN / 100,
+                "{} burnt {} gas, more than {}% over its baseline of {}",
+                name,
+                used,
+                GAS_REGRESSION_MARGIN,
+                baseline
+            );
+        }
+    }
+
+    /// A write or delete of the stored pairs, as generated by `pair_ops`
+    #[derive(Clone, Debug)]
+    enum PairOp {
+        Write(String, String),
+        Delete(String),
+    }
+
+    /// Random sequences of writes and deletes over a small key space, so keys get rewritten and
+    /// deleted keys get written again.
+    fn pair_ops() -> impl proptest::strategy::Strategy<Value = Vec<PairOp>> {
+        use proptest::prelude::*;
+        let key = "[a-d]{1,2}";
+        let op = prop_oneof![
+            3 => (key, "\\PC{0,16}").prop_map(|(k, v)| PairOp::Write(k, v)),
+            1 => key.prop_map(PairOp::Delete),
+        ];
+        proptest::collection::vec(op, 0..40)
+    }
+
+    proptest::proptest! {
+        // Test 120
+        //
+        // Property test that, checked against a BTreeMap model after every operation, a read
+        // after [create_update] returns the written value, a read after [delete] returns None,
+        // and [len] matches the number of distinct keys holding a value.
+        #[test]
+        fn pairs_match_model(ops in pair_ops()) {
+            // `testing_env!` carries storage over from the previous case on this thread
+            env::take_blockchain_interface();
+            TestContext::new().set();
+            let mut contract = new_contract();
+            let mut model = BTreeMap::new();
+            for op in ops {
+                match op {
+                    PairOp::Write(k, v) => {
+                        let previous = contract.create_update(k.clone(), v.clone());
+                        proptest::prop_assert_eq!(model.insert(k.clone(), v), previous);
+                        proptest::prop_assert_eq!(model.get(&k).cloned(), contract.read(k));
+                    }
+                    PairOp::Delete(k) => {
+                        contract.delete(k.clone());
+                        model.remove(&k);
+                        proptest::prop_assert_eq!(None, contract.read(k));
+                    }
+                }
+                proptest::prop_assert_eq!(model.len() as u64, contract.len());
+            }
+            for (k, v) in model {
+                proptest::prop_assert_eq!(Some(v), contract.read(k));
+            }
+        }
+    }
+
+    // Test 121
+    //
+    // Test that errors render as their stable code followed by the message
+    #[test]
+    fn error_codes() {
+        assert_eq!(
+            "key_not_found: Key 'k' does not exist",
+            KvError::KeyNotFound("k".to_string()).to_string()
+        );
+        assert_eq!(
+            "key_too_large: Key is 300 bytes, exceeding the limit of 256",
+            KvError::KeyTooLarge { len: 300, max: 256 }.to_string()
+        );
+        assert_eq!(
+            "maintenance: The contract is read-only during maintenance",
+            KvError::Maintenance.to_string()
+        );
+        assert_eq!(
+            "unauthorized: Only the owner can call this method",
+            KvError::Unauthorized("Only the owner can call this method".to_string()).to_string()
+        );
+    }
+
+    // Test 122
+    //
+    // Test for [read_or_default] falling back to the default only for missing keys
+    #[test]
+    fn read_or_default_pair() {
+        TestContext::new().set();
+        let mut contract = new_contract();
+        contract.create_update("theme".to_string(), "dark".to_string());
+
+        assert_eq!(
+            "dark".to_string(),
+            contract.read_or_default("theme".to_string(), "light".to_string())
+        );
+        assert_eq!(
+            "en".to_string(),
+            contract.read_or_default("locale".to_string(), "en".to_string())
+        );
+    }
+
+    // Test 123
+    //
+    // Test that [export] pages through listed entries in key order with their metadata
+    #[test]
+    fn export_pages() {
+        let mut context = TestContext::new();
+        context.timestamp(100).height(1).set();
+        let mut contract = new_contract();
+        contract.create_update("b".to_string(), "second".to_string());
+        contract.create_update_typed("a".to_string(), Value::Number(I128(1)));
+        contract.create_update("c".to_string(), "third".to_string());
+        contract.create_update("hidden".to_string(), "secret".to_string());
+        contract.set_unlisted("hidden".to_string(), true);
+
+        context.advance_time(100).advance_blocks(1).set();
+        contract.create_update("b".to_string(), "updated".to_string());
+
+        let page = contract.export(None, 2);
+        assert_eq!(
+            vec!["a".to_string(), "b".to_string()],
+            page.iter()
+                .map(|entry| entry.key.clone())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(Value::Number(I128(1)), page[0].value);
+        assert_eq!(
+            ExportedEntry {
+                key: "b".to_string(),
+                value: Value::String("updated".to_string()),
+                created_at: U64(100),
+                created_height: U64(1),
+                created_by: "carol_near".to_string(),
+                updated_at: U64(200),
+                updated_height: U64(2),
+                updated_by: "carol_near".to_string(),
+                version: U64(2),
+                expires_at: None,
+                tags: vec![],
+            },
+            page[1]
+        );
+        let next = contract.export(Some(page[1].key.clone()), 2);
+        assert_eq!(1, next.len());
+        assert_eq!("c", next[0].key);
+    }
+
+    // Test 124
+    //
+    // Test that [import] takes entries from [export] with their owner and timestamps
+    #[test]
+    fn import_exported_entries() {
+        let mut context = TestContext::new();
+        context.timestamp(100).height(1).set();
+        let mut contract = new_contract();
+        contract.create_update_with_tags(
+            "profile".to_string(),
+            "carol".to_string(),
+            vec!["people".to_string()],
+        );
+        context.advance_time(100).advance_blocks(1).set();
+        contract.create_update("profile".to_string(), "carol v2".to_string());
+        let exported = near_sdk::serde_json::to_string(&contract.export(None, 10)).unwrap();
+
+        env::take_blockchain_interface();
+        context
+            .predecessor("alice_near")
+            .timestamp(1_000)
+            .height(10)
+            .set();
+        let mut replacement = new_contract();
+        let entries: Vec<ImportEntry> = near_sdk::serde_json::from_str(&exported).unwrap();
+        assert_eq!(1, replacement.import(entries));
+        assert_eq!(
+            Some(EntryWithMeta {
+                value: "carol v2".to_string(),
+                created_at: U64(100),
+                created_height: U64(1),
+                created_by: "carol_near".to_string(),
+                updated_at: U64(200),
+                updated_height: U64(2),
+                updated_by: "carol_near".to_string(),
+                expires_at: None,
+            }),
+            replacement.read_with_meta("profile".to_string())
+        );
+        assert_eq!(
+            vec!["profile".to_string()],
+            replacement.keys_by_tag("people".to_string(), 0, 10)
+        );
+    }
+
+    // Test 125
+    //
+    // Test that only the owner can [import]
+    #[test]
+    #[should_panic(expected = "Only the owner can call this method")]
+    fn import_requires_owner() {
+        TestContext::new().set();
+        let mut contract = new_contract();
+        contract.import(vec![]);
+    }
+
+    // Test 126
+    //
+    // Test for [read_many_with_missing] separating found pairs from missing keys
+    #[test]
+    fn read_many_with_missing_pairs() {
+        TestContext::new().set();
+        let mut contract = new_contract();
+        contract.create_update("first_key".to_string(), "hello".to_string());
+        contract.create_update("second_key".to_string(), "world".to_string());
+
+        assert_eq!(
+            ReadManyResult {
+                found: vec![
+                    ("second_key".to_string(), "world".to_string()),
+                    ("first_key".to_string(), "hello".to_string()),
+                ],
+                missing: vec!["missing_key".to_string()],
+            },
+            contract.read_many_with_missing(vec![
+                "second_key".to_string(),
+                "missing_key".to_string(),
+                "first_key".to_string(),
+            ])
+        );
+    }
+
+    // Test 127
+    //
+    // Test that with key normalization enabled, keys differing in surrounding whitespace, case
+    // or Unicode composition address the same entry
+    #[test]
+    fn key_normalization() {
+        TestContext::new().set();
+        let config = Config {
+            key_normalization: KeyNormalization {
+                trim: true,
+                lowercase: true,
+                nfc: true,
+            },
+            ..Config::default()
+        };
+        let mut contract = KeyValue::new("alice_near".try_into().unwrap(), Some(config));
+        contract.create_update("Foo ".to_string(), "bar".to_string());
+        contract.create_update("caf\u{65}\u{301}".to_string(), "latte".to_string());
+
+        assert_eq!(Some("bar".to_string()), contract.read("foo".to_string()));
+        assert_eq!(
+            Some("bar".to_string()),
+            contract.create_update(" FOO".to_string(), "baz".to_string())
+        );
+        assert_eq!(
+            Some("latte".to_string()),
+            contract.read("CAF\u{c9}".to_string())
+        );
+        assert_eq!(
+            vec!["caf\u{e9}".to_string(), "foo".to_string()],
+            contract.keys_sorted(None, 10)
+        );
+        contract.delete("\tfoo\n".to_string());
+        assert!(!contract.contains_key("foo".to_string()));
+    }
+
+    // Test 128
+    //
+    // Test that another account cannot take over a soft-deleted key during its retention window
+    #[test]
+    #[should_panic(expected = "Only the owner or the key owner can update 'first_key'")]
+    fn write_soft_deleted_key_of_other_account() {
+        let mut context = TestContext::new();
+        context.predecessor("alice_near").set();
+        let mut contract = new_contract();
+        contract.set_tombstone_retention(Some(U64(100)));
+
+        context.predecessor("carol_near").set();
+        contract.create_update("first_key".to_string(), "hello".to_string());
+        contract.delete("first_key".to_string());
+
+        context.predecessor("dave_near").set();
+        contract.create_update("first_key".to_string(), "mine".to_string());
+    }
+
+    // Test 129
+    //
+    // Test that [rename] refuses to replace another account's soft-deleted key
+    #[test]
+    #[should_panic(expected = "key_exists: Key 'taken' already exists")]
+    fn rename_onto_soft_deleted_key_of_other_account() {
+        let mut context = TestContext::new();
+        context.predecessor("alice_near").set();
+        let mut contract = new_contract();
+        contract.set_tombstone_retention(Some(U64(100)));
+
+        context.predecessor("carol_near").set();
+        contract.create_update("taken".to_string(), "hello".to_string());
+        contract.delete("taken".to_string());
+
+        context.predecessor("dave_near").set();
+        contract.create_update("mine".to_string(), "world".to_string());
+        contract.rename("mine".to_string(), "taken".to_string());
+    }
+
+    // Test 130
+    //
+    // Test that [rename] cannot move an entry into the NFT-gated namespace
+    #[test]
+    #[should_panic(expected = "Writes under 'club/' require an NFT of nft_near, use gated_write")]
+    fn rename_into_gated_namespace() {
+        let mut context = TestContext::new();
+        context.predecessor("alice_near").set();
+        let mut contract = new_contract();
+        contract.set_nft_gate(Some(NftGate {
+            prefix: "club/".to_string(),
+            nft_contract_id: "nft_near".to_string(),
+        }));
+
+        context.predecessor("carol_near").set();
+        contract.create_update("carol".to_string(), "hi".to_string());
+        contract.rename("carol".to_string(), "club/carol".to_string());
+    }
+
+    // Test 131
+    //
+    // Test that with unique values [rename] still moves a value, which only its source holds
+    #[test]
+    fn rename_with_unique_values() {
+        TestContext::new().predecessor("alice_near").set();
+        let mut contract = new_contract();
+        contract.set_unique_values(true);
+        contract.create_update("old".to_string(), "hello".to_string());
+        contract.rename("old".to_string(), "new".to_string());
+        assert_eq!(Some("hello".to_string()), contract.read("new".to_string()));
+    }
+
+    // Test 132
+    //
+    // Test that a relayed write addresses the normalized key the author signed for
+    #[test]
+    fn relayed_write_normalizes_key() {
+        use ed25519_dalek::Signer;
+        let mut context = TestContext::new();
+        context.predecessor("alice_near").set();
+        let config = Config {
+            key_normalization: KeyNormalization {
+                trim: true,
+                lowercase: true,
+                nfc: true,
+            },
+            ..Config::default()
+        };
+        let mut contract = KeyValue::new("alice_near".try_into().unwrap(), Some(config));
+        context.predecessor("dave_near").set();
+        let signing_key = ed25519_dalek::SigningKey::from_bytes(&[7; 32]);
+        let public_key = signing_key.verifying_key().to_bytes().to_vec();
+        contract.set_relay_key(Some(Base64VecU8(public_key)));
+
+        let write = RelayedWrite {
+            author: "dave_near".to_string(),
+            receiver_id: "alice_near".to_string(),
+            nonce: U64(1),
+            expires_at: U64(1_000),
+            k: " Name".to_string(),
+            v: "dave".to_string(),
+        };
+        let message = env::sha256(&write.try_to_vec().unwrap());
+        let signature = Base64VecU8(signing_key.sign(&message).to_bytes().to_vec());
+        context.predecessor("carol_near").set();
+        contract.relay_write(write, signature);
+        assert_eq!(Some("dave".to_string()), contract.read("name".to_string()));
+        assert_eq!(vec!["name".to_string()], contract.keys_sorted(None, 10));
+    }
+
+    // Test 133
+    //
+    // Test that key normalization cannot be changed while only collections hold keys
+    #[test]
+    #[should_panic(expected = "Key normalization can only be changed while no keys are stored")]
+    fn key_normalization_change_with_collections() {
+        let mut context = TestContext::new();
+        context.predecessor("alice_near").set();
+        let mut contract = new_contract();
+        contract.list_push("Queue".to_string(), "job".to_string());
+        contract.set_timelock_delay(U64(1_000));
+        let id = contract.queue(AdminAction::SetConfig {
+            config: Config {
+                key_normalization: KeyNormalization {
+                    trim: false,
+                    lowercase: true,
+                    nfc: false,
+                },
+                ..Config::default()
+            },
+        });
+
+        context.timestamp(1_000).set();
+        contract.execute_queued(id);
+    }
+
+    // Test 134
+    //
+    // Test that changed lazy state is written back before storage is settled, and that it is
+    // never written as a side effect of serializing the root state
+    #[test]
+    fn lazy_state_flushed_before_settle() {
+        TestContext::new().predecessor("alice_near").set();
+        let mut contract = new_contract();
+        contract.create_update("name".to_string(), "alice".to_string());
+        assert!(!contract.indexes.dirty);
+        assert!(!contract.audit_log.dirty);
+
+        contract.audit_log.get_mut();
+        assert!(contract.try_to_vec().is_err());
+    }
+
+    // Test 135
+    //
+    // Test that a verified gated write is checked and recorded as made by the writer, and that
+    // the deposit is refunded if the writer may no longer write
+    #[test]
+    fn nft_verified_write_checks_writer() {
+        let mut context = TestContext::new();
+        context.predecessor("alice_near").set();
+        let mut contract = new_contract();
+        contract.set_nft_gate(Some(NftGate {
+            prefix: "club/".to_string(),
+            nft_contract_id: "nft_near".to_string(),
+        }));
+        contract.blacklist_add("carol_near".try_into().unwrap());
+
+        context
+            .current("kv_near")
+            .predecessor("kv_near")
+            .promise_results(vec![PromiseResult::Successful(
+                br#"{"token_id":"1","owner_id":"carol_near"}"#.to_vec(),
+            )])
+            .set();
+        assert!(!contract.on_nft_verified(
+            "club/carol".to_string(),
+            "hi".to_string(),
+            "carol_near".to_string()
+        ));
+        assert_eq!(
+            vec!["nft gate: unauthorized: Account 'carol_near' is blacklisted".to_string()],
+            get_logs()
+        );
+        assert!(near_sdk::test_utils::get_created_receipts()
+            .iter()
+            .map(|receipt| near_sdk::serde_json::to_string(receipt).unwrap())
+            .any(|receipt| receipt.contains(r#""receiver_id":"carol_near""#)
+                && receipt.contains("Transfer")));
+        assert_eq!(None, contract.read("club/carol".to_string()));
+
+        context
+            .current("alice_near")
+            .predecessor("alice_near")
+            .set();
+        contract.blacklist_remove("carol_near".try_into().unwrap());
+        context.current("kv_near").predecessor("kv_near").set();
+        assert!(contract.on_nft_verified(
+            "club/carol".to_string(),
+            "hi".to_string(),
+            "carol_near".to_string()
+        ));
+        assert_eq!(
+            "carol_near".to_string(),
+            contract.audit("club/carol".to_string(), 0, 10)[0].actor
+        );
+    }
+
+    // Test 136
+    //
+    // Test that writes only look up subscribed prefix lengths, and none once all subscriptions
+    // are gone
+    #[test]
+    fn subscribed_prefix_lengths() {
+        let mut context = TestContext::new();
+        context.predecessor("listener_near").set();
+        let mut contract = new_contract();
+        contract.subscribe("user/".to_string());
+        contract.subscribe("u".to_string());
+        context.predecessor("other_near").set();
+        contract.subscribe("user/".to_string());
+        assert_eq!(
+            vec![(1, 1), (5, 1)],
+            contract
+                .subscribed_prefix_lens
+                .iter()
+                .map(|(len, count)| (*len, *count))
+                .collect::<Vec<_>>()
+        );
+
+        contract.unsubscribe("user/".to_string());
+        context.predecessor("listener_near").set();
+        contract.unsubscribe("u".to_string());
+        assert_eq!(1, contract.subscribed_prefix_lens.len());
+        contract.unsubscribe("user/".to_string());
+        assert!(contract.subscribed_prefix_lens.is_empty());
+
+        context.predecessor("carol_near").set();
+        contract.create_update("user/carol".to_string(), "hi".to_string());
+        assert!(!near_sdk::test_utils::get_created_receipts()
+            .iter()
+            .map(|receipt| near_sdk::serde_json::to_string(receipt).unwrap())
+            .any(|receipt| receipt.contains("on_kv_change")));
+    }
+
+    // Test 137
+    //
+    // Test that [update_if] refuses an account that may not write the key before comparing,
+    // so a failed swap reveals nothing about the value
+    #[test]
+    #[should_panic(expected = "Only the owner or the key owner can update 'secret'")]
+    fn update_if_checks_access_before_comparing() {
+        let mut context = TestContext::new();
+        context.set();
+        let mut contract = new_contract();
+        contract.create_update("secret".to_string(), "42".to_string());
+        contract.set_premium("secret".to_string(), Some(U128(100)));
+
+        context.predecessor("dave_near").set();
+        contract.update_if("secret".to_string(), "41".to_string(), "0".to_string());
+    }
+
+    // Test 138
+    //
+    // Test that a write grant does not let its holder read a premium counter by incrementing it
+    #[test]
+    #[should_panic(expected = "'hits' is a premium entry, read it with read_paid")]
+    fn increment_premium_entry_requires_read_access() {
+        let mut context = TestContext::new();
+        context.set();
+        let mut contract = new_contract();
+        contract.increment("hits".to_string(), U128(5));
+        contract.set_premium("hits".to_string(), Some(U128(100)));
+        contract.grant_write("hits".to_string(), "dave_near".try_into().unwrap());
+
+        context.predecessor("dave_near").set();
+        contract.increment("hits".to_string(), U128(1));
+    }
+
+    // Test 139
+    //
+    // Test that storage of an entry is billed to and refunded to the account that pays for
+    // it, whether a grantee grows it or the owner deletes it
+    #[test]
+    fn storage_settled_with_entry_payer() {
+        let mut context = TestContext::new();
+        context.set();
+        let mut contract = new_contract();
+        contract.create_update("first_key".to_string(), "hi".to_string());
+        contract.grant_write("first_key".to_string(), "dave_near".try_into().unwrap());
+        let paid = contract.storage_paid("carol_near".try_into().unwrap()).0;
+
+        context.predecessor("dave_near").set();
+        contract.create_update("first_key".to_string(), "hello world".to_string());
+        let grown = contract.storage_paid("carol_near".try_into().unwrap()).0;
+        assert!(grown > paid);
+        assert_eq!(
+            U64(0),
+            contract.storage_paid("dave_near".try_into().unwrap())
+        );
+
+        context.predecessor("alice_near").set();
+        let before_delete = env::storage_usage();
+        contract.delete("first_key".to_string());
+        let freed = before_delete - env::storage_usage();
+        assert_eq!(
+            grown - freed,
+            contract.storage_paid("carol_near".try_into().unwrap()).0
+        );
+        assert_eq!(
+            U64(0),
+            contract.storage_paid("alice_near".try_into().unwrap())
+        );
+    }
+
+    // Test 140
+    //
+    // Test that pausing blocks storage deposits, credits and paid reads
+    #[test]
+    fn pause_blocks_storage_methods() {
+        let mut context = TestContext::new();
+        context.predecessor("alice_near").set();
+        let mut contract = new_contract();
+        contract.storage_deposit(None, None);
+        contract.pause();
+
+        let attempts: [fn(&mut KeyValue); 4] = [
+            |contract| {
+                contract.storage_deposit(None, None);
+            },
+            |contract| {
+                contract.buy_credits();
+            },
+            |contract| {
+                contract.read_paid("first_key".to_string());
+            },
+            |contract| {
+                contract.unsubscribe("first".to_string());
+            },
+        ];
+        for attempt in attempts {
+            let result =
+                std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| attempt(&mut contract)));
+            assert!(result.is_err());
+        }
+        context.deposit(1).set();
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.storage_withdraw(None)
+        }));
+        assert!(result.is_err());
+
+        contract.unpause();
+        contract.set_paused_methods(PAUSE_STORAGE);
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.storage_withdraw(None)
+        }));
+        assert!(result.is_err());
+        contract.set_paused_methods(0);
+        contract.storage_withdraw(None);
+    }
+
+    // Test 141
+    //
+    // Test that abi.json lists every public contract method with its kind, modifiers, doc,
+    // argument names and serialization, and a definition for every serde type. Argument and
+    // result schemas are not compared and must be updated by hand.
+    #[test]
+    fn contract_abi_matches_methods() {
+        use near_sdk::serde_json::{json, Value as Json};
+        let abi: Json =
+            near_sdk::serde_json::from_slice(&zstd::decode_all(CONTRACT_ABI).unwrap()).unwrap();
+        let file = syn::parse_file(include_str!("lib.rs")).unwrap();
+        let has_attr = |attrs: &[syn::Attribute], name: &str| {
+            attrs.iter().any(|attr| attr.path().is_ident(name))
+        };
+        let is_borsh = |attrs: &[syn::Attribute], name: &str| {
+            attrs.iter().any(|attr| {
+                attr.path().is_ident(name)
+                    && attr
+                        .parse_args::<syn::Ident>()
+                        .is_ok_and(|serializer| serializer == "borsh")
+            })
+        };
+        let serialization = |borsh: bool| if borsh { "borsh" } else { "json" };
+        let doc = |attrs: &[syn::Attribute]| {
+            let lines: Vec<String> = attrs
+                .iter()
+                .filter_map(|attr| match &attr.meta {
+                    syn::Meta::NameValue(doc) if doc.path.is_ident("doc") => match &doc.value {
+                        syn::Expr::Lit(syn::ExprLit {
+                            lit: syn::Lit::Str(line),
+                            ..
+                        }) => Some(line.value().trim().to_string()),
+                        _ => None,
+                    },
+                    _ => None,
+                })
+                .collect();
+            Some(lines.join("\n")).filter(|doc| !doc.is_empty())
+        };
+
+        let methods: Vec<Json> = file
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                syn::Item::Impl(block) if has_attr(&block.attrs, "near_bindgen") => Some(block),
+                _ => None,
+            })
+            .flat_map(|block| &block.items)
+            .filter_map(|item| match item {
+                syn::ImplItem::Fn(method) if matches!(method.vis, syn::Visibility::Public(_)) => {
+                    Some(method)
+                }
+                _ => None,
+            })
+            .map(|method| {
+                let signature = &method.sig;
+                let is_view = matches!(
+                    signature.inputs.first(),
+                    Some(syn::FnArg::Receiver(receiver)) if receiver.mutability.is_none()
+                );
+                let args: Vec<&syn::PatType> = signature
+                    .inputs
+                    .iter()
+                    .filter_map(|input| match input {
+                        syn::FnArg::Typed(arg) => Some(arg),
+                        syn::FnArg::Receiver(_) => None,
+                    })
+                    .collect();
+                let modifiers: Vec<&str> = ["init", "payable", "private"]
+                    .into_iter()
+                    .filter(|modifier| has_attr(&method.attrs, modifier))
+                    .collect();
+                let returns_value = match &signature.output {
+                    syn::ReturnType::Type(_, ty) => !matches!(
+                        &**ty,
+                        syn::Type::Path(path) if path.path.is_ident("Promise")
+                    ),
+                    syn::ReturnType::Default => false,
+                };
+                json!({
+                    "name": signature.ident.to_string(),
+                    "kind": if is_view { "view" } else { "call" },
+                    "doc": doc(&method.attrs),
+                    "modifiers": modifiers,
+                    "params": args.iter().map(|arg| match &*arg.pat {
+                        syn::Pat::Ident(ident) => ident.ident.to_string(),
+                        _ => "_".to_string(),
+                    }).collect::<Vec<_>>(),
+                    "params_serialization": (!args.is_empty()).then(|| serialization(
+                        args.iter().any(|arg| is_borsh(&arg.attrs, "serializer"))
+                    )),
+                    "result_serialization": returns_value.then(|| serialization(
+                        is_borsh(&method.attrs, "result_serializer")
+                    )),
+                })
+            })
+            .collect();
+        let listed: Vec<Json> = abi["body"]["functions"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|function| {
+                json!({
+                    "name": function["name"],
+                    "kind": function["kind"],
+                    "doc": function["doc"],
+                    "modifiers": function.get("modifiers").cloned().unwrap_or(json!([])),
+                    "params": function["params"]["args"]
+                        .as_array()
+                        .map(|args| args.iter().map(|arg| arg["name"].clone()).collect())
+                        .unwrap_or(json!([])),
+                    "params_serialization": function["params"]["serialization_type"],
+                    "result_serialization": function["result"]["serialization_type"],
+                })
+            })
+            .collect();
+        assert_eq!(methods.len(), listed.len());
+        for (method, function) in methods.iter().zip(&listed) {
+            assert_eq!(method, function, "abi.json is out of date");
+        }
+
+        let mut serde_types: Vec<String> = file
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                syn::Item::Struct(item) => Some((&item.ident, &item.attrs)),
+                syn::Item::Enum(item) => Some((&item.ident, &item.attrs)),
+                _ => None,
+            })
+            .filter(|(_, attrs)| {
+                attrs.iter().any(|attr| {
+                    let mut serde = false;
+                    if attr.path().is_ident("derive") {
+                        let _ = attr.parse_nested_meta(|meta| {
+                            serde |= meta.path.is_ident("Serialize")
+                                || meta.path.is_ident("Deserialize");
+                            Ok(())
+                        });
+                    }
+                    serde
+                })
+            })
+            .map(|(ident, _)| ident.to_string())
+            .collect();
+        serde_types.sort();
+        let definitions: Vec<String> = abi["body"]["root_schema"]["definitions"]
+            .as_object()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect();
+        assert_eq!(serde_types, definitions);
+    }
+
+    // Test 142
+    //
+    // Test that a price plus read fee beyond u128 fails as an overflow
+    #[test]
+    #[should_panic(expected = "overflow: Read price overflow")]
+    fn read_paid_price_overflow() {
+        let mut context = TestContext::new();
+        context.set();
+        let mut contract = new_contract();
+        context.predecessor("alice_near").set();
+        contract.set_read_fee(U128(1));
+        context.predecessor("carol_near").set();
+        contract.create_update("report".to_string(), "insights".to_string());
+        contract.set_premium("report".to_string(), Some(U128(u128::MAX)));
+
+        context.predecessor("dave_near").set();
+        contract.read_paid("report".to_string());
+    }
+
+    // Test 143
+    //
+    // Test that a batch write reaches the mirror as a single call, and that a call changing more
+    // keys than its gas can mirror is rejected
+    #[test]
+    #[should_panic(expected = "Mirroring 40 changed keys needs")]
+    fn mirror_batch_gas() {
+        let mut context = TestContext::new();
+        context.predecessor("alice_near").set();
+        let mut contract = new_contract();
+        contract.set_mirror(Some("mirror_near".try_into().unwrap()));
+        let pairs = |count: usize| -> Vec<(String, String)> {
+            (0..count)
+                .map(|i| (format!("key_{}", i), "value".to_string()))
+                .collect()
+        };
+
+        context.prepaid_gas(300_000_000_000_000).set();
+        contract.create_update_many(pairs(5));
+        let mirrored: Vec<(String, String, Vec<u8>)> = created_calls()
+            .into_iter()
+            .filter(|(receiver, _, _)| receiver == "mirror_near")
+            .collect();
+        assert_eq!(1, mirrored.len());
+        let applied: Vec<(String, Option<MirroredEntry>)> =
+            BorshDeserialize::try_from_slice(&mirrored[0].2).unwrap();
+        assert_eq!(5, applied.len());
+
+        context.set();
+        contract.create_update_many(pairs(40));
+    }
+
+    // Test 144
+    //
+    // Test that purged entries are removed from the mirror, and that the mirror applies
+    // replicated entries only from its source, metadata included
+    #[test]
+    fn mirror_purge_and_apply() {
+        let mut context = TestContext::new();
+        context.predecessor("alice_near").set();
+        let mut contract = new_contract();
+        contract.set_mirror(Some("mirror_near".try_into().unwrap()));
+        context.predecessor("carol_near").set();
+        contract.create_update_with_ttl("temp".to_string(), "soon".to_string(), U64(10));
+        context.advance_time(10).set();
+        assert_eq!(1, contract.purge_expired(10));
+        let (receiver, method, args) = created_calls()
+            .into_iter()
+            .find(|(_, method, _)| method == "mirror_apply")
+            .unwrap();
+        assert_eq!("mirror_near", receiver);
+        let applied: Vec<(String, Option<MirroredEntry>)> =
+            BorshDeserialize::try_from_slice(&args).unwrap();
+        assert_eq!(vec![("temp".to_string(), None)], applied);
+        assert_eq!("mirror_apply", method);
+
+        // The mirror, starting from empty storage
+        env::take_blockchain_interface();
+        context
+            .current("mirror_near")
+            .predecessor("alice_near")
+            .timestamp(0)
+            .set();
+        let mut mirror = new_contract();
+        mirror.set_mirror_source(Some("source_near".try_into().unwrap()));
+        context.predecessor("source_near").set();
+        mirror.storage_deposit(None, None);
+        let mut meta = EntryMeta::new("carol_near".to_string(), "carol_near".to_string());
+        meta.version = 3;
+        meta.oldest_version = 1;
+        meta.tags = vec!["news".to_string()];
+        meta.price = Some(5_000);
+        meta.expires_at = Some(1_000);
+        let entry = MirroredEntry {
+            value: Value::String("insights".to_string()),
+            meta,
+        };
+        context.deposit(0).set();
+        mirror.mirror_apply(vec![("report".to_string(), Some(entry))]);
+        let stored = mirror.internal_entry(&"report".to_string()).unwrap();
+        assert_eq!("source_near", stored.meta.payer);
+        assert_eq!(3, stored.meta.oldest_version);
+        assert_eq!(Some(U128(5_000)), mirror.price("report".to_string()));
+        assert_eq!(None, mirror.read("report".to_string()));
+        assert_eq!(
+            Some("carol_near".to_string()),
+            mirror.key_owner("report".to_string())
+        );
+        assert_eq!(vec!["news".to_string()], mirror.tags("report".to_string()));
+        assert_eq!(1, mirror.len());
+
+        mirror.mirror_apply(vec![("report".to_string(), None)]);
+        assert_eq!(None, mirror.internal_entry(&"report".to_string()));
+        assert_eq!(0, mirror.len());
+
+        context.predecessor("carol_near").set();
+        let attempt =
+            std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| mirror.mirror_apply(vec![])));
+        assert!(attempt.is_err());
+    }
+
+    // Test 145
+    //
+    // Test that an oracle round with a forged or tampered signature is rejected
+    #[test]
+    fn oracle_round_signatures() {
+        use ed25519_dalek::Signer;
+        let mut context = TestContext::new();
+        context.predecessor("alice_near").timestamp(100).set();
+        let mut contract = new_contract();
+        contract.add_oracle("oracle_near".try_into().unwrap());
+        context.predecessor("oracle_near").set();
+        let sign = oracle_signer(&mut contract);
+
+        let push = |contract: &mut KeyValue, value: &str, round: u64, signature: Base64VecU8| {
+            std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                contract.push_feed(
+                    "near-usd".to_string(),
+                    value.to_string(),
+                    U64(round),
+                    U64(90),
+                    signature,
+                )
+            }))
+        };
+        // Signed for another value or round
+        assert!(push(&mut contract, "9.99", 1, sign("near-usd", "3.14", 1, 90)).is_err());
+        assert!(push(&mut contract, "3.14", 2, sign("near-usd", "3.14", 1, 90)).is_err());
+        // Signed with a key other than the oracle's
+        let forger = ed25519_dalek::SigningKey::from_bytes(&[3; 32]);
+        let round = ("near-usd".to_string(), "3.14".to_string(), 1u64, 90u64);
+        let forged = forger.sign(&env::sha256(&round.try_to_vec().unwrap()));
+        assert!(push(
+            &mut contract,
+            "3.14",
+            1,
+            Base64VecU8(forged.to_bytes().to_vec())
+        )
+        .is_err());
+        assert!(push(&mut contract, "3.14", 1, Base64VecU8(vec![0; 10])).is_err());
+        assert_eq!(None, contract.read_feed("near-usd".to_string(), None));
+
+        assert!(push(&mut contract, "3.14", 1, sign("near-usd", "3.14", 1, 90)).is_ok());
+        assert_eq!(
+            "3.14".to_string(),
+            contract
+                .read_feed("near-usd".to_string(), None)
+                .unwrap()
+                .round
+                .value
+        );
+    }
+
+    // Test 146
+    //
+    // Test that transferring a key token settles the storage it frees and requires exactly one
+    // yoctoNEAR
+    #[test]
+    fn key_token_transfer_settles_storage() {
+        let mut context = TestContext::new();
+        context.set();
+        let mut contract = new_contract();
+        contract.create_update("name".to_string(), "carol".to_string());
+        contract.grant_write("name".to_string(), "erin_near".try_into().unwrap());
+        let paid = contract.storage_paid("carol_near".try_into().unwrap()).0;
+
+        context.deposit(2).set();
+        let attempt = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.nft_transfer(
+                "dave_near".try_into().unwrap(),
+                "name".to_string(),
+                None,
+                None,
+            )
+        }));
+        assert!(attempt.is_err());
+
+        context.deposit(1).set();
+        contract.nft_transfer(
+            "dave_near".try_into().unwrap(),
+            "name".to_string(),
+            None,
+            None,
+        );
+        assert!(contract.storage_paid("carol_near".try_into().unwrap()).0 < paid);
+        assert!(near_sdk::test_utils::get_created_receipts()
+            .iter()
+            .map(|receipt| format!("{:?}", receipt))
+            .any(|receipt| receipt.contains(r#"receiver_id: "carol_near""#)
+                && receipt.contains("Transfer")));
+    }
+
+    // Test 147
+    //
+    // Test that read_raw panics on an absent key instead of returning an empty result
+    #[test]
+    #[should_panic(expected = "key_not_found: Key 'missing' does not exist")]
+    fn read_raw_missing_key() {
+        TestContext::new().set();
+        let mut contract = new_contract();
+        contract.create_update("empty".to_string(), String::new());
+        contract.read_raw("empty".to_string());
+        contract.read_raw("missing".to_string());
     }
 }